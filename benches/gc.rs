@@ -0,0 +1,46 @@
+//! Compares the garbage collectors' pauses on a program that keeps a large tree alive while
+//! it churns through short-lived ones, the case generational collection is for. Run with
+//! `cargo bench --bench gc`.
+
+use std::time::Instant;
+
+use rlox::lox::{Backend, Lox};
+use rlox::vm::gc::GcMode;
+
+const PROGRAM: &str = "
+class Node {
+  init(left, right) {
+    this.left = left;
+    this.right = right;
+  }
+  check() {
+    if (this.left == nil) return 1;
+    return 1 + this.left.check() + this.right.check();
+  }
+}
+fun tree(depth) {
+  if (depth == 0) return Node(nil, nil);
+  return Node(tree(depth - 1), tree(depth - 1));
+}
+var long = tree(16);
+var checked = 0;
+for (var i = 0; i < 2000; i = i + 1) {
+  checked = checked + tree(6).check();
+}
+if (checked != 254000 or long.check() != 131071) print \"wrong result\";
+";
+
+fn main() {
+    for (name, mode) in [
+        ("mark-sweep", GcMode::MarkSweep),
+        ("generational", GcMode::Generational),
+    ] {
+        let mut lox = Lox::new().backend(Backend::Vm).gc(mode);
+        let mut vm = lox.vm(&[]);
+        let start = Instant::now();
+        lox.run_in_vm(&mut vm, PROGRAM).expect("the benchmark runs");
+        println!("{}: ran in {:?}", name, start.elapsed());
+        print!("{}", vm.heap().stats());
+        println!();
+    }
+}
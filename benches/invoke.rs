@@ -0,0 +1,124 @@
+//! Times the vm on call-heavy programs compiled with and without its fused instructions:
+//! `Invoke`, which calls methods without allocating a bound method for them, and the
+//! superinstructions `rlox run --profile` showed pairs of instructions to be worth fusing into.
+//!
+//! ```text
+//! cargo bench --bench invoke
+//! ```
+
+use std::time::{Duration, Instant};
+
+use rlox::lox::{Backend, Lox};
+
+const RUNS: usize = 5;
+
+const FIB: &str = "
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+if (fib(27) != 196418) print \"wrong result\";
+";
+
+const METHOD_CALLS: &str = "
+class Counter {
+  init() {
+    this.count = 0;
+  }
+  increment() {
+    this.count = this.count + 1;
+    return this;
+  }
+}
+var counter = Counter();
+for (var i = 0; i < 100000; i = i + 1) {
+  counter.increment().increment().increment();
+}
+if (counter.count != 300000) print \"wrong result\";
+";
+
+const SUPER_CALLS: &str = "
+class Base {
+  init(size) {
+    this.size = size;
+  }
+  area() {
+    return this.size * this.size;
+  }
+}
+class Scaled < Base {
+  area() {
+    return super.area() * 2;
+  }
+}
+var shape = Scaled(3);
+var total = 0;
+for (var i = 0; i < 100000; i = i + 1) {
+  total = total + shape.area();
+}
+if (total != 1800000) print \"wrong result\";
+";
+
+const BINARY_TREES: &str = "
+class Tree {
+  init(left, right) {
+    this.left = left;
+    this.right = right;
+  }
+  check() {
+    if (this.left == nil) return 1;
+    return 1 + this.left.check() + this.right.check();
+  }
+}
+fun tree(depth) {
+  if (depth == 0) return Tree(nil, nil);
+  return Tree(tree(depth - 1), tree(depth - 1));
+}
+var checked = 0;
+for (var i = 0; i < 20; i = i + 1) {
+  checked = checked + tree(12).check();
+}
+if (checked != 163820) print \"wrong result\";
+";
+
+fn main() {
+    for (name, program) in [
+        ("fib", FIB),
+        ("method calls", METHOD_CALLS),
+        ("super calls", SUPER_CALLS),
+        ("binary trees", BINARY_TREES),
+    ] {
+        for fused in [true, false] {
+            let mut times: Vec<Duration> = (0..RUNS).map(|_| time(program, fused)).collect();
+            times.sort();
+            println!(
+                "{:<13} {:<6} fastest {:?}, median {:?}, {} instructions",
+                name,
+                if fused { "fused" } else { "plain" },
+                times[0],
+                times[RUNS / 2],
+                instructions(program, fused)
+            );
+        }
+    }
+}
+
+fn lox(fused: bool) -> Lox {
+    Lox::new().backend(Backend::Vm).optimize(fused)
+}
+
+fn time(program: &str, fused: bool) -> Duration {
+    let mut lox = lox(fused);
+    let mut vm = lox.vm(&[]);
+    let start = Instant::now();
+    lox.run_in_vm(&mut vm, program).expect("the benchmark runs");
+    start.elapsed()
+}
+
+// how many instructions the program runs, from a separate run: profiling slows the vm down.
+fn instructions(program: &str, fused: bool) -> usize {
+    let mut lox = lox(fused).profile(true);
+    let mut vm = lox.vm(&[]);
+    lox.run_in_vm(&mut vm, program).expect("the benchmark runs");
+    vm.profile().map_or(0, |profile| profile.total())
+}
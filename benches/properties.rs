@@ -0,0 +1,114 @@
+//! Times the vm on programs dominated by field accesses and method calls, with and without
+//! the inline caches of property instructions:
+//!
+//! ```text
+//! cargo bench --bench properties
+//! ```
+
+use std::time::{Duration, Instant};
+
+use rlox::lox::{Backend, Lox};
+
+const RUNS: usize = 5;
+
+const METHOD_CALLS: &str = "
+class Counter {
+  init() {
+    this.count = 0;
+  }
+  increment() {
+    this.count = this.count + 1;
+    return this;
+  }
+}
+var counter = Counter();
+for (var i = 0; i < 100000; i = i + 1) {
+  counter.increment().increment().increment();
+}
+if (counter.count != 300000) print \"wrong result\";
+";
+
+const FIELD_ACCESS: &str = "
+class Vector {
+  init(x, y, z) {
+    this.x = x;
+    this.y = y;
+    this.z = z;
+  }
+}
+var a = Vector(1, 2, 3);
+var b = Vector(4, 5, 6);
+var dot = 0;
+for (var i = 0; i < 100000; i = i + 1) {
+  dot = dot + a.x * b.x + a.y * b.y + a.z * b.z;
+  a.x = b.y;
+  b.y = a.x;
+}
+if (dot != 32 + 99999 * 48) print \"wrong result\";
+";
+
+const POLYMORPHIC_CALLS: &str = "
+class Shape {
+  init(size) {
+    this.size = size;
+  }
+  scaled(factor) {
+    return this.area() * factor;
+  }
+}
+class Square < Shape {
+  area() {
+    return this.size * this.size;
+  }
+}
+class Circle < Shape {
+  area() {
+    return 3 * this.size * this.size;
+  }
+}
+class Segment < Shape {
+  area() {
+    return 0;
+  }
+}
+var square = Square(2);
+var circle = Circle(1);
+var segment = Segment(5);
+var total = 0;
+for (var i = 0; i < 50000; i = i + 1) {
+  total = total + square.scaled(2) + circle.scaled(2) + segment.scaled(2);
+}
+if (total != 700000) print \"wrong result\";
+";
+
+fn main() {
+    for (name, program) in [
+        ("method calls", METHOD_CALLS),
+        ("field access", FIELD_ACCESS),
+        ("polymorphic calls", POLYMORPHIC_CALLS),
+    ] {
+        for inline_caching in [true, false] {
+            let mut runs: Vec<(Duration, usize)> =
+                (0..RUNS).map(|_| time(program, inline_caching)).collect();
+            runs.sort();
+            println!(
+                "{:<18} {:<9} fastest {:?}, median {:?}, {} lookups by name",
+                name,
+                if inline_caching { "cached" } else { "uncached" },
+                runs[0].0,
+                runs[RUNS / 2].0,
+                runs[0].1
+            );
+        }
+    }
+}
+
+// how long the program took, and how many properties it looked up by name.
+fn time(program: &str, inline_caching: bool) -> (Duration, usize) {
+    let mut lox = Lox::new().backend(Backend::Vm);
+    let mut vm = lox.vm(&[]);
+    vm.set_inline_caching(inline_caching);
+    let start = Instant::now();
+    lox.run_in_vm(&mut vm, program).expect("the benchmark runs");
+    (start.elapsed(), vm.cache_misses())
+}
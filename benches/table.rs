@@ -0,0 +1,117 @@
+//! Compares the vm's `Table` with std's `HashMap` on the operations the vm does on globals,
+//! fields and methods: inserting, finding and missing string keys, and removing them. Run with
+//! `cargo bench --bench table`.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rlox::vm::object::{Heap, ObjRef};
+use rlox::vm::table::Table;
+
+const KEYS: usize = 100_000;
+const RUNS: usize = 5;
+
+// the operations on both kinds of map.
+trait Map {
+    fn new() -> Self;
+    fn insert(&mut self, key: ObjRef, value: usize);
+    fn get(&self, key: ObjRef) -> Option<usize>;
+    fn remove(&mut self, key: ObjRef);
+}
+
+impl Map for Table<ObjRef, usize> {
+    fn new() -> Self {
+        Table::new()
+    }
+    fn insert(&mut self, key: ObjRef, value: usize) {
+        Table::insert(self, key, value);
+    }
+    fn get(&self, key: ObjRef) -> Option<usize> {
+        Table::get(self, &key).copied()
+    }
+    fn remove(&mut self, key: ObjRef) {
+        Table::remove(self, &key);
+    }
+}
+
+impl Map for HashMap<ObjRef, usize> {
+    fn new() -> Self {
+        HashMap::new()
+    }
+    fn insert(&mut self, key: ObjRef, value: usize) {
+        HashMap::insert(self, key, value);
+    }
+    fn get(&self, key: ObjRef) -> Option<usize> {
+        HashMap::get(self, &key).copied()
+    }
+    fn remove(&mut self, key: ObjRef) {
+        HashMap::remove(self, &key);
+    }
+}
+
+// how long each operation takes on every key: inserting, finding, missing, and removing half
+// of the keys and inserting them again.
+fn bench<M: Map>(present: &[ObjRef], absent: &[ObjRef]) -> [Duration; 4] {
+    let mut map = M::new();
+    let start = Instant::now();
+    for (i, key) in present.iter().enumerate() {
+        map.insert(*key, i);
+    }
+    let insert = start.elapsed();
+
+    let start = Instant::now();
+    for key in present {
+        black_box(map.get(*key));
+    }
+    let hit = start.elapsed();
+
+    let start = Instant::now();
+    for key in absent {
+        black_box(map.get(*key));
+    }
+    let miss = start.elapsed();
+
+    let start = Instant::now();
+    for key in present.iter().step_by(2) {
+        map.remove(*key);
+    }
+    for (i, key) in present.iter().enumerate().step_by(2) {
+        map.insert(*key, i);
+    }
+    let churn = start.elapsed();
+    [insert, hit, miss, churn]
+}
+
+fn report(name: &str, runs: Vec<[Duration; 4]>) {
+    let fastest = |operation: usize| runs.iter().map(|run| run[operation]).min().unwrap();
+    println!(
+        "{:<8} insert {:>10?}  hit {:>10?}  miss {:>10?}  remove+insert {:>10?}",
+        name,
+        fastest(0),
+        fastest(1),
+        fastest(2),
+        fastest(3)
+    );
+}
+
+fn main() {
+    let mut heap = Heap::new();
+    let keys: Vec<ObjRef> = (0..KEYS * 2)
+        .map(|i| heap.intern(&format!("key{}", i)))
+        .collect();
+    let (present, absent) = keys.split_at(KEYS);
+    println!("{} string keys, fastest of {} runs", KEYS, RUNS);
+    report(
+        "Table",
+        (0..RUNS)
+            .map(|_| bench::<Table<ObjRef, usize>>(present, absent))
+            .collect(),
+    );
+    report(
+        "HashMap",
+        (0..RUNS)
+            .map(|_| bench::<HashMap<ObjRef, usize>>(present, absent))
+            .collect(),
+    );
+}
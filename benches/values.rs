@@ -0,0 +1,89 @@
+//! Times the vm on programs dominated by arithmetic, object allocation and string building.
+//! Compare the representations of values by running it both ways:
+//!
+//! ```text
+//! cargo bench --bench values
+//! cargo bench --bench values --features nan-boxing
+//! ```
+
+use std::mem;
+use std::time::{Duration, Instant};
+
+use rlox::lox::{Backend, Lox};
+use rlox::vm::value::Value;
+
+const RUNS: usize = 5;
+
+const FIB: &str = "
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+if (fib(27) != 196418) print \"wrong result\";
+";
+
+const BINARY_TREES: &str = "
+class Tree {
+  init(left, right) {
+    this.left = left;
+    this.right = right;
+  }
+  check() {
+    if (this.left == nil) return 1;
+    return 1 + this.left.check() + this.right.check();
+  }
+}
+fun tree(depth) {
+  if (depth == 0) return Tree(nil, nil);
+  return Tree(tree(depth - 1), tree(depth - 1));
+}
+var checked = 0;
+for (var i = 0; i < 20; i = i + 1) {
+  checked = checked + tree(12).check();
+}
+if (checked != 163820) print \"wrong result\";
+";
+
+const STRING_CONCATENATION: &str = "
+var text = \"\";
+for (var i = 0; i < 10000; i = i + 1) {
+  var word = \"word \" + \"by \" + \"word\";
+  text = text + \"x\";
+}
+if (text == \"\") print \"wrong result\";
+";
+
+fn main() {
+    let representation = if cfg!(feature = "nan-boxing") {
+        "nan-boxed"
+    } else {
+        "tagged enum"
+    };
+    println!(
+        "values: {}, {} bytes each",
+        representation,
+        mem::size_of::<Value>()
+    );
+    for (name, program) in [
+        ("fib", FIB),
+        ("binary trees", BINARY_TREES),
+        ("string concatenation", STRING_CONCATENATION),
+    ] {
+        let mut times: Vec<Duration> = (0..RUNS).map(|_| time(program)).collect();
+        times.sort();
+        println!(
+            "{:<22} fastest {:?}, median {:?}",
+            name,
+            times[0],
+            times[RUNS / 2]
+        );
+    }
+}
+
+fn time(program: &str) -> Duration {
+    let mut lox = Lox::new().backend(Backend::Vm);
+    let mut vm = lox.vm(&[]);
+    let start = Instant::now();
+    lox.run_in_vm(&mut vm, program).expect("the benchmark runs");
+    start.elapsed()
+}
@@ -0,0 +1,402 @@
+//! Lossless concrete syntax tree, for tools that have to reproduce or edit source text.
+//!
+//! The tree has two layers. The green layer is immutable and position independent: a node has
+//! a kind, its length in bytes and its children, and a token has a kind and its text, so the
+//! text of any subtree is the concatenation of its tokens. Edits build new green nodes that
+//! share every unchanged subtree with the old ones. The red layer, `SyntaxNode` and
+//! `SyntaxToken`, is a cheap cursor over a green tree that adds parents and absolute offsets.
+//!
+//! Node kinds are the node types of the json export (`Program`, `Var`, `Binary`, ...). Token
+//! kinds are the token type names from the book (`IDENTIFIER`, `LEFT_PAREN`, ...) plus
+//! `WHITESPACE`, `COMMENT`, and `ERROR` for characters the scanner rejected. Every byte of the
+//! source belongs to exactly one token; tokens that aren't part of a node, such as the remains
+//! of a statement that failed to parse, are children of the innermost node around them.
+
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::lox::Lox;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::tree::Node;
+use crate::types::Token;
+
+#[derive(Debug, PartialEq)]
+pub struct GreenToken {
+    kind: &'static str,
+    text: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GreenNode {
+    kind: &'static str,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenToken {
+    pub fn new(kind: &'static str, text: &str) -> Rc<GreenToken> {
+        Rc::new(GreenToken {
+            kind,
+            text: text.to_string(),
+        })
+    }
+
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl GreenNode {
+    pub fn new(kind: &'static str, children: Vec<GreenElement>) -> Rc<GreenNode> {
+        let len = children.iter().map(GreenElement::len).sum();
+        Rc::new(GreenNode {
+            kind,
+            len,
+            children,
+        })
+    }
+
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// A copy of this node with child `index` replaced.
+    pub fn replace_child(&self, index: usize, child: GreenElement) -> Rc<GreenNode> {
+        let mut children = self.children.clone();
+        children[index] = child;
+        GreenNode::new(self.kind, children)
+    }
+}
+
+impl GreenElement {
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => write!(f, "{}", node)?,
+                GreenElement::Token(token) => write!(f, "{}", token.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    // position among the parent's children, and absolute byte offset.
+    index: usize,
+    offset: usize,
+}
+
+/// A node of a green tree, seen from the root.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+/// A token of a green tree, seen from the root.
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    parent: SyntaxNode,
+    index: usize,
+    offset: usize,
+}
+
+#[derive(Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> SyntaxNode {
+        SyntaxNode(Rc::new(NodeData {
+            green,
+            parent: None,
+            index: 0,
+            offset: 0,
+        }))
+    }
+
+    pub fn kind(&self) -> &'static str {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    /// Byte range of the node in the source of the whole tree.
+    pub fn range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.len
+    }
+
+    pub fn text(&self) -> String {
+        self.0.green.to_string()
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        let mut elements = vec![];
+        for (index, child) in self.0.green.children.iter().enumerate() {
+            elements.push(match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: Rc::clone(green),
+                    parent: Some(self.clone()),
+                    index,
+                    offset,
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: Rc::clone(green),
+                    parent: self.clone(),
+                    index,
+                    offset,
+                }),
+            });
+            offset += child.len();
+        }
+        elements
+    }
+
+    /// Child nodes, without the tokens between them.
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    /// Every token under this node, in source order.
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = vec![];
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// The token that contains byte `offset`, if the offset is within this node.
+    pub fn token_at(&self, offset: usize) -> Option<SyntaxToken> {
+        if !self.range().contains(&offset) {
+            return None;
+        }
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) if node.range().contains(&offset) => {
+                    return node.token_at(offset)
+                }
+                SyntaxElement::Token(token) if token.range().contains(&offset) => {
+                    return Some(token)
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// The root of a new tree in which this node is replaced by `replacement`. Everything
+    /// outside of it, comments included, is kept as it is.
+    pub fn replace_with(&self, replacement: Rc<GreenNode>) -> Rc<GreenNode> {
+        match &self.0.parent {
+            Some(parent) => parent.replace_with(
+                parent
+                    .0
+                    .green
+                    .replace_child(self.0.index, GreenElement::Node(replacement)),
+            ),
+            None => replacement,
+        }
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> &'static str {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+
+    /// Whitespace and comments.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.green.kind, "WHITESPACE" | "COMMENT")
+    }
+
+    /// The root of a new tree in which this token is replaced by `replacement`.
+    pub fn replace_with(&self, replacement: Rc<GreenToken>) -> Rc<GreenNode> {
+        self.parent.replace_with(
+            self.parent
+                .0
+                .green
+                .replace_child(self.index, GreenElement::Token(replacement)),
+        )
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+// one line per node and token, indented by depth: `Binary@4..9`, `PLUS@6..7 "+"`.
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write(f: &mut fmt::Formatter<'_>, node: &SyntaxNode, depth: usize) -> fmt::Result {
+            let range = node.range();
+            writeln!(
+                f,
+                "{:indent$}{}@{}..{}",
+                "",
+                node.kind(),
+                range.start,
+                range.end,
+                indent = depth * 2
+            )?;
+            for element in node.children_with_tokens() {
+                match element {
+                    SyntaxElement::Node(child) => write(f, &child, depth + 1)?,
+                    SyntaxElement::Token(token) => {
+                        let range = token.range();
+                        writeln!(
+                            f,
+                            "{:indent$}{}@{}..{} {:?}",
+                            "",
+                            token.kind(),
+                            range.start,
+                            range.end,
+                            token.text(),
+                            indent = (depth + 1) * 2
+                        )?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        write(f, self, 0)
+    }
+}
+
+/// Scans and parses `source` into a lossless tree whose text is exactly `source`. Errors are
+/// reported to `lox`; the tree is built regardless.
+pub fn parse(source: &str, lox: &mut Lox) -> SyntaxNode {
+    let tokens = Scanner::new(source).trivia(true).scan_tokens(lox);
+
+    // the scanner skips over characters it rejects; they become ERROR tokens.
+    let mut pieces: Vec<(&'static str, Range<usize>)> = vec![];
+    let mut end = 0;
+    for token in &tokens {
+        let span = token.span();
+        // the end of file token sits at the very end, so this also covers trailing errors.
+        if span.start > end {
+            pieces.push(("ERROR", end..span.start));
+        }
+        if let Token::Eof { .. } = token {
+            break;
+        }
+        pieces.push((token.kind_name(), span.start..span.end));
+        end = span.end;
+    }
+
+    let tokens: Vec<Token> = tokens
+        .into_iter()
+        .filter(|token| !matches!(token, Token::Trivia { .. }))
+        .collect();
+    let statements = Parser::new(&tokens).parse(lox);
+
+    let mut builder = Builder {
+        source,
+        pieces,
+        next: 0,
+    };
+    let program = Node::program(&statements);
+    SyntaxNode::new_root(builder.node(&program, 0..source.len()))
+}
+
+// assigns tokens to the innermost syntax tree node whose span covers them.
+struct Builder<'s> {
+    source: &'s str,
+    pieces: Vec<(&'static str, Range<usize>)>,
+    next: usize,
+}
+
+impl<'s> Builder<'s> {
+    fn node(&mut self, node: &Node, range: Range<usize>) -> Rc<GreenNode> {
+        let mut children = vec![];
+        for (_, child) in &node.children {
+            self.tokens_before(child.span.start, &mut children);
+            let child_range = child.span.start..child.span.end;
+            children.push(GreenElement::Node(self.node(child, child_range)));
+        }
+        self.tokens_before(range.end, &mut children);
+        GreenNode::new(node.kind, children)
+    }
+
+    fn tokens_before(&mut self, end: usize, children: &mut Vec<GreenElement>) {
+        while let Some((kind, range)) = self.pieces.get(self.next) {
+            if range.start >= end {
+                break;
+            }
+            children.push(GreenElement::Token(GreenToken::new(
+                kind,
+                &self.source[range.clone()],
+            )));
+            self.next += 1;
+        }
+    }
+}
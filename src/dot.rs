@@ -0,0 +1,64 @@
+//! Graphviz DOT export of the syntax tree, e.g. `rlox ast --format dot f.lox | dot -Tsvg`.
+//!
+//! Each node is labelled with its type followed by its attributes (operators, literal values,
+//! identifiers); each edge is labelled with the role the child plays in its parent.
+
+use std::fmt::Write;
+
+use crate::parser::Stmt;
+use crate::tree::{Attribute, Node};
+
+pub fn ast(statements: &[Stmt]) -> String {
+    let mut out = String::from("digraph ast {\n");
+    out.push_str("  ordering=out;\n");
+    out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+    out.push_str("  edge [fontname=\"monospace\", fontsize=10];\n");
+    let mut next_id = 0;
+    write_node(&Node::program(statements), &mut next_id, &mut out);
+    out.push_str("}\n");
+    out
+}
+
+// writes the node and its subtree, returning the node's id.
+fn write_node(node: &Node, next_id: &mut usize, out: &mut String) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    let mut label = String::from(node.kind);
+    for (_, attribute) in &node.attributes {
+        label.push('\n');
+        match (node.kind, attribute) {
+            // quote string literals so they can't be mistaken for identifiers.
+            ("Literal", Attribute::String(value)) => label.push_str(&format!("\"{}\"", value)),
+            _ => label.push_str(&attribute_label(attribute)),
+        }
+    }
+    writeln!(out, "  n{} [label=\"{}\"];", id, escape(&label)).unwrap();
+    for (role, child) in &node.children {
+        let child_id = write_node(child, next_id, out);
+        writeln!(out, "  n{} -> n{} [label=\"{}\"];", id, child_id, role).unwrap();
+    }
+    id
+}
+
+fn attribute_label(attribute: &Attribute) -> String {
+    match attribute {
+        Attribute::Nil => String::from("nil"),
+        Attribute::Bool(value) => value.to_string(),
+        Attribute::Number(value) => value.to_string(),
+        Attribute::String(value) => value.to_string(),
+        Attribute::List(values) => format!("({})", values.join(", ")),
+    }
+}
+
+fn escape(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
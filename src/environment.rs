@@ -0,0 +1,68 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::value::Value;
+
+pub struct Environment<'a> {
+    values: HashMap<&'a str, Value<'a>>,
+    enclosing: Option<Rc<RefCell<Environment<'a>>>>,
+}
+
+impl<'a> Environment<'a> {
+    pub fn new(enclosing: Option<Rc<RefCell<Environment<'a>>>>) -> Environment<'a> {
+        Environment {
+            values: HashMap::new(),
+            enclosing,
+        }
+    }
+
+    pub fn define(&mut self, name: &'a str, value: Value<'a>) {
+        self.values.insert(name, value);
+    }
+
+    /// Variables defined directly in this environment, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = (&'a str, &Value<'a>)> {
+        self.values.iter().map(|(name, value)| (*name, value))
+    }
+
+    /// Looks `name` up in this environment and then in the enclosing ones.
+    pub fn get(&self, name: &str) -> Option<Value<'a>> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref()?.borrow().get(name),
+        }
+    }
+
+    /// Assigns to an existing variable; returns false if `name` isn't defined anywhere.
+    pub fn assign(&mut self, name: &str, value: Value<'a>) -> bool {
+        match self.values.get_mut(name) {
+            Some(slot) => {
+                *slot = value;
+                true
+            }
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+                None => false,
+            },
+        }
+    }
+
+    /// Looks `name` up exactly `distance` environments up, as computed by the resolver.
+    pub fn get_at(&self, distance: usize, name: &str) -> Option<Value<'a>> {
+        if distance == 0 {
+            return self.values.get(name).cloned();
+        }
+        self.enclosing.as_ref()?.borrow().get_at(distance - 1, name)
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &'a str, value: Value<'a>) {
+        if distance == 0 {
+            self.values.insert(name, value);
+            return;
+        }
+        if let Some(enclosing) = &self.enclosing {
+            enclosing.borrow_mut().assign_at(distance - 1, name, value);
+        }
+    }
+}
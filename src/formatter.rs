@@ -0,0 +1,384 @@
+//! The opinionated source formatter behind `rlox fmt`.
+//!
+//! Statements are laid out one per line, blocks and class bodies are indented by two spaces,
+//! and expressions are printed the way `AstPrinter::Source` prints them: single spaces around
+//! binary operators and only the parentheses the tree needs. An expression that would run past
+//! `WIDTH` has the argument lists of its calls broken up, one argument per line.
+//!
+//! Comments aren't part of the syntax tree, so they're taken from a trivia scan of the source
+//! and placed by position: a comment before a statement stays on its own line before it, a
+//! comment after a statement on the same line stays after it, and a comment inside a statement
+//! that isn't inside one of its blocks follows the statement. A single blank line between
+//! statements is kept; longer runs of blank lines are collapsed.
+//!
+//! Formatting is idempotent: formatting the output again leaves it unchanged.
+
+use crate::parser::{Expr, ExprKind, Function, Precedence, Stmt, StmtKind};
+use crate::printer::AstPrinter;
+use crate::scanner::Scanner;
+use crate::types::{Span, Token, Trivia};
+
+/// Lines are kept within this many columns where the syntax allows it.
+pub const WIDTH: usize = 80;
+
+const INDENT: &str = "  ";
+
+/// Formats a program that parsed without errors; `statements` must come from `source`.
+pub fn format(source: &str, statements: &[Stmt]) -> String {
+    let comments = Scanner::new(source)
+        .trivia(true)
+        .scan_ignoring_errors()
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Trivia {
+                span,
+                token: Trivia::Comment,
+            } => Some(span),
+            _ => None,
+        })
+        .collect();
+    let mut formatter = Formatter {
+        source,
+        comments,
+        next_comment: 0,
+        out: String::new(),
+        indent: 0,
+    };
+    formatter.items(statements, |stmt| stmt.span, Formatter::stmt, source.len());
+    if !formatter.out.is_empty() {
+        formatter.out.push('\n');
+    }
+    formatter.out
+}
+
+struct Formatter<'s> {
+    source: &'s str,
+    comments: Vec<Span>,
+    next_comment: usize,
+    out: String,
+    indent: usize,
+}
+
+impl<'s> Formatter<'s> {
+    fn push(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn column(&self) -> usize {
+        let line = match self.out.rfind('\n') {
+            Some(i) => &self.out[i + 1..],
+            None => &self.out,
+        };
+        line.chars().count()
+    }
+
+    // the next comment, if it starts before `end`.
+    fn comment_before(&mut self, end: usize) -> Option<&'s str> {
+        let span = *self.comments.get(self.next_comment)?;
+        if span.start >= end {
+            return None;
+        }
+        self.next_comment += 1;
+        Some(&self.source[span.start..span.end])
+    }
+
+    // starts the line for something at `start` in the source, keeping one blank line if there
+    // was at least one since `previous`, the end of whatever came before it at this level.
+    fn line_for(&mut self, previous: Option<usize>, start: usize) {
+        if self.out.is_empty() {
+            return;
+        }
+        if let Some(previous) = previous {
+            if self.source[previous..start].matches('\n').count() > 1 {
+                self.out.push('\n');
+            }
+        }
+        self.newline();
+    }
+
+    /// Prints statements or methods, each on its own line along with the comments around
+    /// them, up to the comments before `end`. Returns whether anything was printed.
+    fn items<T>(
+        &mut self,
+        items: &[T],
+        span: fn(&T) -> Span,
+        print: fn(&mut Formatter<'s>, &T),
+        end: usize,
+    ) -> bool {
+        let mut previous = None;
+        for (i, item) in items.iter().enumerate() {
+            let item_span = span(item);
+            previous = self.comments_before(previous, item_span.start);
+            self.line_for(previous, item_span.start);
+            print(self, item);
+            let limit = items.get(i + 1).map_or(end, |next| span(next).start);
+            self.trailing_comments(item_span.end, limit);
+            previous = Some(item_span.end);
+        }
+        previous = self.comments_before(previous, end);
+        !items.is_empty() || previous.is_some()
+    }
+
+    // comments on their own lines, before something at `start`.
+    fn comments_before(&mut self, mut previous: Option<usize>, start: usize) -> Option<usize> {
+        while let Some(span) = self.comments.get(self.next_comment).copied() {
+            if span.start >= start {
+                break;
+            }
+            let comment = self.comment_before(start).unwrap();
+            self.line_for(previous, span.start);
+            self.push(comment);
+            previous = Some(span.end);
+        }
+        previous
+    }
+
+    // comments left inside an item that ended at `end`, and one on the same line after it.
+    // The first goes at the end of the item's last line, the rest on lines of their own.
+    fn trailing_comments(&mut self, end: usize, limit: usize) {
+        let line_end = self.source[end..]
+            .find('\n')
+            .map_or(self.source.len(), |i| end + i);
+        let mut first = true;
+        while let Some(span) = self.comments.get(self.next_comment).copied() {
+            if span.start >= limit || (span.start >= end && span.start >= line_end) {
+                break;
+            }
+            let comment = self.comment_before(limit).unwrap();
+            if first {
+                self.push(" ");
+            } else {
+                self.newline();
+            }
+            self.push(comment);
+            first = false;
+        }
+    }
+
+    fn block(&mut self, statements: &[Stmt], end: usize) {
+        self.push("{");
+        self.indent += 1;
+        let printed = self.items(statements, |stmt| stmt.span, Formatter::stmt, end);
+        self.indent -= 1;
+        if printed {
+            self.newline();
+        }
+        self.push("}");
+    }
+
+    // a statement's closing brace is its last byte.
+    fn block_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Block(statements) => self.block(statements, stmt.span.end - 1),
+            _ => self.block(std::slice::from_ref(stmt), stmt.span.end),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Expression(expr) => {
+                self.expr(expr);
+                self.push(";");
+            }
+            StmtKind::Print(expr) => {
+                self.push("print ");
+                self.expr(expr);
+                self.push(";");
+            }
+            StmtKind::Var { name, initializer } => {
+                self.push(&format!("var {}", name.lexeme));
+                if let Some(initializer) = initializer {
+                    self.push(" = ");
+                    self.expr(initializer);
+                }
+                self.push(";");
+            }
+            StmtKind::Block(_) => self.block_stmt(stmt),
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.push("if (");
+                self.expr(condition);
+                self.push(") ");
+                // an else-less `if` as the then-branch would steal our `else` when re-parsed.
+                let dangling = else_branch.is_some()
+                    && matches!(
+                        then_branch.kind,
+                        StmtKind::If {
+                            else_branch: None,
+                            ..
+                        }
+                    );
+                if dangling {
+                    self.block_stmt(then_branch);
+                } else {
+                    self.stmt(then_branch);
+                }
+                if let Some(else_branch) = else_branch {
+                    self.push(" else ");
+                    self.stmt(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.push("while (");
+                self.expr(condition);
+                self.push(") ");
+                self.stmt(body);
+            }
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                self.push("for (");
+                match initializer {
+                    Some(initializer) => self.stmt(initializer),
+                    None => self.push(";"),
+                }
+                if let Some(condition) = condition {
+                    self.push(" ");
+                    self.expr(condition);
+                }
+                self.push(";");
+                if let Some(increment) = increment {
+                    self.push(" ");
+                    self.expr(increment);
+                }
+                self.push(") ");
+                self.stmt(body);
+            }
+            StmtKind::Function(function) => {
+                self.push("fun ");
+                self.function(function);
+            }
+            StmtKind::Return(value) => {
+                self.push("return");
+                if let Some(value) = value {
+                    self.push(" ");
+                    self.expr(value);
+                }
+                self.push(";");
+            }
+            StmtKind::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                self.push(&format!("class {} ", name.lexeme));
+                if let Some(superclass) = superclass {
+                    self.push("< ");
+                    self.expr(superclass);
+                    self.push(" ");
+                }
+                self.push("{");
+                self.indent += 1;
+                let printed = self.items(
+                    methods,
+                    |method| method.span,
+                    |formatter, method| formatter.function(method),
+                    stmt.span.end - 1,
+                );
+                self.indent -= 1;
+                if printed {
+                    self.newline();
+                }
+                self.push("}");
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        let params: Vec<&str> = function.params.iter().map(|param| param.lexeme).collect();
+        self.push(&format!("{}({}) ", function.name.lexeme, params.join(", ")));
+        self.block(&function.body, function.span.end - 1);
+    }
+
+    // an expression on one line if it fits, leaving room for the `;`, `,` or `)` after it.
+    // Otherwise its calls spread their arguments over several lines.
+    fn expr(&mut self, expr: &Expr) {
+        let flat = AstPrinter::source(expr);
+        if self.column() + flat.chars().count() < WIDTH {
+            self.push(&flat);
+            return;
+        }
+        match &expr.kind {
+            ExprKind::Assign(name, value) => {
+                self.push(&format!("{} = ", name.lexeme));
+                self.expr(value);
+            }
+            ExprKind::Binary(left, operator, right) => {
+                self.infix(left, operator.as_str(), right, expr.precedence())
+            }
+            ExprKind::Logical(left, operator, right) => {
+                self.infix(left, operator.as_str(), right, expr.precedence())
+            }
+            ExprKind::Grouping(inner) => {
+                self.push("(");
+                self.expr(inner);
+                self.push(")");
+            }
+            ExprKind::Unary(operator, operand) => {
+                self.push(operator.as_str());
+                self.operand(operand, operand.precedence() < Precedence::Unary);
+            }
+            ExprKind::Call(callee, arguments) => {
+                self.operand(callee, callee.precedence() < Precedence::Call);
+                if arguments.is_empty() {
+                    self.push("()");
+                    return;
+                }
+                self.push("(");
+                self.indent += 1;
+                for (i, argument) in arguments.iter().enumerate() {
+                    self.newline();
+                    self.expr(argument);
+                    if i + 1 < arguments.len() {
+                        self.push(",");
+                    }
+                }
+                self.indent -= 1;
+                self.newline();
+                self.push(")");
+            }
+            ExprKind::Get(object, name) => {
+                self.operand(object, object.precedence() < Precedence::Call);
+                self.push(&format!(".{}", name.lexeme));
+            }
+            ExprKind::Set(object, name, value) => {
+                self.operand(object, object.precedence() < Precedence::Call);
+                self.push(&format!(".{} = ", name.lexeme));
+                self.expr(value);
+            }
+            ExprKind::Literal(_) | ExprKind::Variable(_) | ExprKind::This | ExprKind::Super(_) => {
+                self.push(&flat)
+            }
+        }
+    }
+
+    // the same parenthesization as `AstPrinter::Source`.
+    fn infix(&mut self, left: &Expr, operator: &str, right: &Expr, precedence: Precedence) {
+        self.operand(left, left.precedence() < precedence);
+        self.push(&format!(" {} ", operator));
+        self.operand(right, right.precedence() <= precedence);
+    }
+
+    fn operand(&mut self, expr: &Expr, parenthesize: bool) {
+        if parenthesize {
+            self.push("(");
+            self.expr(expr);
+            self.push(")");
+        } else {
+            self.expr(expr);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::scanner::Scanner;
+use crate::types::{Literal, Token, Trivia};
+
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// How a piece of source is colored. Identifiers, punctuation and whitespace are left plain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Highlight {
+    Keyword,
+    String,
+    Number,
+    Operator,
+    Comment,
+}
+
+impl Highlight {
+    /// How a token is colored; `None` for identifiers, punctuation and whitespace.
+    pub fn of(token: &Token) -> Option<Highlight> {
+        match token {
+            Token::Keyword { .. } => Some(Highlight::Keyword),
+            Token::Literal {
+                token: Literal::String { .. },
+                ..
+            } => Some(Highlight::String),
+            Token::Literal {
+                token: Literal::Number { .. },
+                ..
+            } => Some(Highlight::Number),
+            Token::Operator { .. } => Some(Highlight::Operator),
+            Token::Trivia {
+                token: Trivia::Comment,
+                ..
+            } => Some(Highlight::Comment),
+            _ => None,
+        }
+    }
+
+    fn ansi(&self) -> &'static str {
+        match self {
+            Highlight::Keyword => "\x1b[35m",
+            Highlight::String => "\x1b[32m",
+            Highlight::Number => "\x1b[36m",
+            Highlight::Operator => "\x1b[33m",
+            Highlight::Comment => "\x1b[90m",
+        }
+    }
+
+    /// CSS class of the `<span>` around this kind of source in HTML output.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Highlight::Keyword => "keyword",
+            Highlight::String => "string",
+            Highlight::Number => "number",
+            Highlight::Operator => "operator",
+            Highlight::Comment => "comment",
+        }
+    }
+}
+
+/// Splits `source` into consecutive pieces and how each is highlighted; together the pieces
+/// make up the whole source. Invalid input is left plain rather than reported.
+pub fn pieces(source: &str) -> Vec<(&str, Option<Highlight>)> {
+    let mut pieces = vec![];
+    let mut end = 0;
+    for token in Scanner::new(source).trivia(true).scan_ignoring_errors() {
+        let span = token.span();
+        if span.start > end {
+            // characters the scanner skipped as errors.
+            pieces.push((&source[end..span.start], None));
+        }
+        if span.end > span.start {
+            pieces.push((&source[span.start..span.end], Highlight::of(&token)));
+        }
+        end = span.end;
+    }
+    pieces
+}
+
+/// `source` with ANSI color escapes, for a terminal.
+pub fn ansi(source: &str) -> String {
+    let mut out = String::new();
+    for (text, highlight) in pieces(source) {
+        match highlight {
+            Some(highlight) => {
+                out.push_str(highlight.ansi());
+                out.push_str(text);
+                out.push_str(ANSI_RESET);
+            }
+            None => out.push_str(text),
+        }
+    }
+    out
+}
+
+/// `source` as an HTML fragment, `<pre class="lox">...</pre>`, with highlighted pieces wrapped
+/// in `<span>`s whose classes are given by `Highlight::class`.
+pub fn html(source: &str) -> String {
+    let mut out = String::from("<pre class=\"lox\">");
+    for (text, highlight) in pieces(source) {
+        match highlight {
+            Some(highlight) => {
+                out.push_str(&format!("<span class=\"{}\">", highlight.class()));
+                escape_html(text, &mut out);
+                out.push_str("</span>");
+            }
+            None => escape_html(text, &mut out),
+        }
+    }
+    out.push_str("</pre>\n");
+    out
+}
+
+fn escape_html(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
@@ -0,0 +1,576 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::environment::Environment;
+use crate::parser::{BinaryOp, Expr, ExprKind, LiteralValue, LogicalOp, Stmt, StmtKind, UnaryOp};
+use crate::printer::AstPrinter;
+use crate::resolver::Locals;
+use crate::value::{LoxClass, LoxFunction, LoxInstance, NativeFunction, Value};
+use crate::vm::FRAMES_MAX;
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl RuntimeError {
+    fn new(expr: &Expr, message: &str) -> RuntimeError {
+        RuntimeError {
+            line: expr.span.line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.line)
+    }
+}
+
+// why execution of a statement stopped early: a `return` unwinding to its call, or an error.
+enum Unwind<'a> {
+    Return(Value<'a>),
+    Error(RuntimeError),
+}
+
+impl<'a> From<RuntimeError> for Unwind<'a> {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+/// Tree-walking evaluator. Globals and resolved locals accumulate across calls to
+/// `interpret`, so a program can be fed to it in pieces.
+pub struct Interpreter<'a> {
+    globals: Rc<RefCell<Environment<'a>>>,
+    environment: Rc<RefCell<Environment<'a>>>,
+    locals: Locals,
+    // how many functions are running.
+    depth: usize,
+    trace: bool,
+    echo: bool,
+}
+
+impl<'a> Default for Interpreter<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new() -> Interpreter<'a> {
+        let globals = Rc::new(RefCell::new(Environment::new(None)));
+        let mut interpreter = Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: Locals::new(),
+            depth: 0,
+            trace: false,
+            echo: false,
+        };
+        interpreter.define_native("clock", 0, |_| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|error| error.to_string())?;
+            Ok(Value::Number(now.as_secs_f64()))
+        });
+        interpreter
+    }
+
+    /// Prints each statement to stderr before executing it.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Makes top-level expression statements print their value, as a REPL does, and keep it
+    /// in the global `_`. `nil` results, such as those of most calls, are neither printed nor
+    /// kept, as in Python's REPL: `_` still holds the last result worth looking at after a
+    /// call made for its side effects.
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    pub fn define_native(
+        &mut self,
+        name: &'static str,
+        arity: usize,
+        function: impl Fn(&[Value<'a>]) -> Result<Value<'a>, String> + 'a,
+    ) {
+        let native = NativeFunction {
+            name,
+            arity,
+            function: Box::new(function),
+        };
+        self.globals
+            .borrow_mut()
+            .define(name, Value::Native(Rc::new(native)));
+    }
+
+    /// Global variables and their values, sorted by name.
+    pub fn globals(&self) -> Vec<(&'a str, Value<'a>)> {
+        let globals = self.globals.borrow();
+        let mut values: Vec<_> = globals
+            .values()
+            .map(|(name, value)| (name, value.clone()))
+            .collect();
+        values.sort_by_key(|(name, _)| *name);
+        values
+    }
+
+    pub fn interpret(
+        &mut self,
+        statements: &[Stmt<'a>],
+        locals: Locals,
+    ) -> Result<(), RuntimeError> {
+        self.locals.extend(locals);
+        for statement in statements {
+            let result = match &statement.kind {
+                StmtKind::Expression(expr) if self.echo => {
+                    if self.trace {
+                        self.trace_statement(statement);
+                    }
+                    self.echo(expr)
+                }
+                _ => self.execute(statement),
+            };
+            match result {
+                Ok(()) => {}
+                Err(Unwind::Error(error)) => return Err(error),
+                // the resolver rejects top-level returns, so this can't happen.
+                Err(Unwind::Return(_)) => unreachable!("return outside of a function"),
+            }
+        }
+        Ok(())
+    }
+
+    // prints and keeps the value of an expression statement, unless it's `nil`.
+    fn echo(&mut self, expr: &Expr<'a>) -> Result<(), Unwind<'a>> {
+        let value = self.evaluate(expr)?;
+        if !matches!(value, Value::Nil) {
+            println!("{:?}", value);
+            self.globals.borrow_mut().define("_", value);
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, stmt: &Stmt<'a>) -> Result<(), Unwind<'a>> {
+        if self.trace {
+            self.trace_statement(stmt);
+        }
+        match &stmt.kind {
+            StmtKind::Expression(expr) => {
+                self.evaluate(expr)?;
+            }
+            StmtKind::Print(expr) => {
+                let value = self.evaluate(expr)?;
+                println!("{}", value);
+            }
+            StmtKind::Var { name, initializer } => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Nil,
+                };
+                self.environment.borrow_mut().define(name.lexeme, value);
+            }
+            StmtKind::Block(statements) => {
+                let environment = Environment::new(Some(Rc::clone(&self.environment)));
+                self.execute_block(statements, Rc::new(RefCell::new(environment)))?;
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.evaluate(condition)?.is_truthy() {
+                    self.execute(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)?;
+                }
+            }
+            StmtKind::While { condition, body } => {
+                while self.evaluate(condition)?.is_truthy() {
+                    self.execute(body)?;
+                }
+            }
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                let environment = Environment::new(Some(Rc::clone(&self.environment)));
+                let previous =
+                    std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+                let result = self.execute_for(initializer.as_deref(), condition, increment, body);
+                self.environment = previous;
+                result?;
+            }
+            StmtKind::Function(declaration) => {
+                let function = LoxFunction {
+                    declaration: Rc::clone(declaration),
+                    closure: Rc::clone(&self.environment),
+                    is_initializer: false,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(declaration.name.lexeme, Value::Function(Rc::new(function)));
+            }
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value));
+            }
+            StmtKind::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass {
+                    Some(expr) => match self.evaluate(expr)? {
+                        Value::Class(class) => Some(class),
+                        _ => {
+                            return Err(
+                                RuntimeError::new(expr, "Superclass must be a class.").into()
+                            )
+                        }
+                    },
+                    None => None,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme, Value::Nil);
+
+                // methods close over an environment holding `super`, if there is a superclass.
+                let closure = match &superclass {
+                    Some(superclass) => {
+                        let mut environment = Environment::new(Some(Rc::clone(&self.environment)));
+                        environment.define("super", Value::Class(Rc::clone(superclass)));
+                        Rc::new(RefCell::new(environment))
+                    }
+                    None => Rc::clone(&self.environment),
+                };
+                let methods: HashMap<_, _> = methods
+                    .iter()
+                    .map(|method| {
+                        let function = LoxFunction {
+                            declaration: Rc::clone(method),
+                            closure: Rc::clone(&closure),
+                            is_initializer: method.name.lexeme == "init",
+                        };
+                        (method.name.lexeme, Rc::new(function))
+                    })
+                    .collect();
+                let class = LoxClass {
+                    name: name.lexeme,
+                    superclass,
+                    methods,
+                };
+                self.environment
+                    .borrow_mut()
+                    .assign(name.lexeme, Value::Class(Rc::new(class)));
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_for(
+        &mut self,
+        initializer: Option<&Stmt<'a>>,
+        condition: &Option<Expr<'a>>,
+        increment: &Option<Expr<'a>>,
+        body: &Stmt<'a>,
+    ) -> Result<(), Unwind<'a>> {
+        if let Some(initializer) = initializer {
+            self.execute(initializer)?;
+        }
+        loop {
+            if let Some(condition) = condition {
+                if !self.evaluate(condition)?.is_truthy() {
+                    return Ok(());
+                }
+            }
+            self.execute(body)?;
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
+            }
+        }
+    }
+
+    fn execute_block(
+        &mut self,
+        statements: &[Stmt<'a>],
+        environment: Rc<RefCell<Environment<'a>>>,
+    ) -> Result<(), Unwind<'a>> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
+        self.environment = previous;
+        result
+    }
+
+    fn evaluate(&mut self, expr: &Expr<'a>) -> Result<Value<'a>, RuntimeError> {
+        match &expr.kind {
+            ExprKind::Literal(value) => Ok(match value {
+                LiteralValue::Nil => Value::Nil,
+                LiteralValue::True => Value::Bool(true),
+                LiteralValue::False => Value::Bool(false),
+                LiteralValue::Number(number) => Value::Number(*number),
+                LiteralValue::String(string) => Value::String(Rc::from(&**string)),
+            }),
+            ExprKind::Variable(name) => self.look_up_variable(expr, name),
+            ExprKind::Assign(name, value) => {
+                let value = self.evaluate(value)?;
+                match self.locals.get(&expr.id) {
+                    Some(distance) => {
+                        self.environment.borrow_mut().assign_at(
+                            *distance,
+                            name.lexeme,
+                            value.clone(),
+                        );
+                    }
+                    None => {
+                        if !self.globals.borrow_mut().assign(name.lexeme, value.clone()) {
+                            return Err(RuntimeError::new(
+                                expr,
+                                &format!("Undefined variable '{}'.", name.lexeme),
+                            ));
+                        }
+                    }
+                }
+                Ok(value)
+            }
+            ExprKind::Binary(left, operator, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                binary(expr, *operator, left, right)
+            }
+            ExprKind::Logical(left, operator, right) => {
+                let left = self.evaluate(left)?;
+                let short_circuits = match operator {
+                    LogicalOp::Or => left.is_truthy(),
+                    LogicalOp::And => !left.is_truthy(),
+                };
+                if short_circuits {
+                    Ok(left)
+                } else {
+                    self.evaluate(right)
+                }
+            }
+            ExprKind::Grouping(inner) => self.evaluate(inner),
+            ExprKind::Unary(operator, operand) => {
+                let operand = self.evaluate(operand)?;
+                match (operator, operand) {
+                    (UnaryOp::Minus, Value::Number(number)) => Ok(Value::Number(-number)),
+                    (UnaryOp::Minus, _) => {
+                        Err(RuntimeError::new(expr, "Operand must be a number."))
+                    }
+                    (UnaryOp::Bang, operand) => Ok(Value::Bool(!operand.is_truthy())),
+                }
+            }
+            ExprKind::Call(callee, arguments) => {
+                let callee = self.evaluate(callee)?;
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    values.push(self.evaluate(argument)?);
+                }
+                self.call(expr, callee, values)
+            }
+            ExprKind::Get(object, name) => match self.evaluate(object)? {
+                Value::Instance(instance) => {
+                    if let Some(value) = instance.borrow().fields.get(name.lexeme) {
+                        return Ok(value.clone());
+                    }
+                    let class = Rc::clone(&instance.borrow().class);
+                    match class.find_method(name.lexeme) {
+                        Some(method) => Ok(Value::Function(Rc::new(
+                            method.bind(Value::Instance(instance)),
+                        ))),
+                        None => Err(RuntimeError::new(
+                            expr,
+                            &format!("Undefined property '{}'.", name.lexeme),
+                        )),
+                    }
+                }
+                _ => Err(RuntimeError::new(expr, "Only instances have properties.")),
+            },
+            ExprKind::Set(object, name, value) => match self.evaluate(object)? {
+                Value::Instance(instance) => {
+                    let value = self.evaluate(value)?;
+                    instance
+                        .borrow_mut()
+                        .fields
+                        .insert(name.lexeme, value.clone());
+                    Ok(value)
+                }
+                _ => Err(RuntimeError::new(expr, "Only instances have fields.")),
+            },
+            ExprKind::This => self.look_up_variable(expr, "this"),
+            ExprKind::Super(method) => {
+                let distance = self.locals[&expr.id];
+                let superclass = self.environment.borrow().get_at(distance, "super");
+                // `this` is always bound one environment inside the one holding `super`.
+                let instance = self.environment.borrow().get_at(distance - 1, "this");
+                match (superclass, instance) {
+                    (Some(Value::Class(superclass)), Some(instance)) => {
+                        match superclass.find_method(method.lexeme) {
+                            Some(found) => Ok(Value::Function(Rc::new(found.bind(instance)))),
+                            None => Err(RuntimeError::new(
+                                expr,
+                                &format!("Undefined property '{}'.", method.lexeme),
+                            )),
+                        }
+                    }
+                    _ => unreachable!("'super' resolved outside of a subclass"),
+                }
+            }
+        }
+    }
+
+    fn look_up_variable(&self, expr: &Expr<'a>, name: &str) -> Result<Value<'a>, RuntimeError> {
+        let value = match self.locals.get(&expr.id) {
+            Some(distance) => self.environment.borrow().get_at(*distance, name),
+            None => self.globals.borrow().get(name),
+        };
+        value.ok_or_else(|| RuntimeError::new(expr, &format!("Undefined variable '{}'.", name)))
+    }
+
+    fn call(
+        &mut self,
+        expr: &Expr<'a>,
+        callee: Value<'a>,
+        arguments: Vec<Value<'a>>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let arity = match &callee {
+            Value::Function(function) => function.arity(),
+            Value::Native(native) => native.arity,
+            Value::Class(class) => class.arity(),
+            _ => {
+                return Err(RuntimeError::new(
+                    expr,
+                    "Can only call functions and classes.",
+                ))
+            }
+        };
+        if arguments.len() != arity {
+            return Err(RuntimeError::new(
+                expr,
+                &format!("Expected {} arguments but got {}.", arity, arguments.len()),
+            ));
+        }
+        match callee {
+            Value::Function(function) => self.call_function(expr, &function, arguments),
+            Value::Native(native) => {
+                (native.function)(&arguments).map_err(|message| RuntimeError::new(expr, &message))
+            }
+            Value::Class(class) => {
+                let instance = Value::Instance(Rc::new(RefCell::new(LoxInstance {
+                    class: Rc::clone(&class),
+                    fields: HashMap::new(),
+                })));
+                if let Some(initializer) = class.find_method("init") {
+                    let initializer = initializer.bind(instance.clone());
+                    self.call_function(expr, &initializer, arguments)?;
+                }
+                Ok(instance)
+            }
+            _ => unreachable!("arity checked above"),
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        expr: &Expr<'a>,
+        function: &LoxFunction<'a>,
+        arguments: Vec<Value<'a>>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        // as deep as the vm goes, where the script takes a frame of its own.
+        if self.depth + 1 == FRAMES_MAX {
+            return Err(RuntimeError::new(expr, "Stack overflow."));
+        }
+        let mut environment = Environment::new(Some(Rc::clone(&function.closure)));
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme, argument);
+        }
+        self.depth += 1;
+        let result = self.execute_block(
+            &function.declaration.body,
+            Rc::new(RefCell::new(environment)),
+        );
+        self.depth -= 1;
+        let value = match result {
+            Ok(()) => Value::Nil,
+            Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(error)) => return Err(error),
+        };
+        // initializers always return the instance, even from an early `return;`.
+        if function.is_initializer {
+            return Ok(function
+                .closure
+                .borrow()
+                .get_at(0, "this")
+                .expect("initializer bound to an instance"));
+        }
+        Ok(value)
+    }
+
+    fn trace_statement(&self, stmt: &Stmt<'a>) {
+        // compound statements are traced one level deep; their parts are traced as they run.
+        let summary = match &stmt.kind {
+            StmtKind::Block(_) => String::from("{ ... }"),
+            StmtKind::If { condition, .. } => format!("if ({}) ...", condition),
+            StmtKind::While { condition, .. } => format!("while ({}) ...", condition),
+            StmtKind::For { .. } => String::from("for (...) ..."),
+            StmtKind::Function(function) => format!("fun {}(...)", function.name.lexeme),
+            StmtKind::Class { name, .. } => format!("class {}", name.lexeme),
+            _ => AstPrinter::Source.print_program(std::slice::from_ref(stmt)),
+        };
+        eprintln!("[line {}] {}", stmt.span.line, summary.trim_end());
+    }
+}
+
+fn binary<'a>(
+    expr: &Expr<'a>,
+    operator: BinaryOp,
+    left: Value<'a>,
+    right: Value<'a>,
+) -> Result<Value<'a>, RuntimeError> {
+    match operator {
+        BinaryOp::EqualEqual => return Ok(Value::Bool(left == right)),
+        BinaryOp::BangEqual => return Ok(Value::Bool(left != right)),
+        BinaryOp::Plus => {
+            return match (left, right) {
+                (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+                (Value::String(a), Value::String(b)) => {
+                    Ok(Value::String(Rc::from(format!("{}{}", a, b))))
+                }
+                _ => Err(RuntimeError::new(
+                    expr,
+                    "Operands must be two numbers or two strings.",
+                )),
+            }
+        }
+        _ => {}
+    }
+    let (a, b) = match (left, right) {
+        (Value::Number(a), Value::Number(b)) => (a, b),
+        _ => return Err(RuntimeError::new(expr, "Operands must be numbers.")),
+    };
+    Ok(match operator {
+        BinaryOp::Minus => Value::Number(a - b),
+        BinaryOp::Star => Value::Number(a * b),
+        BinaryOp::Slash => Value::Number(a / b),
+        BinaryOp::Greater => Value::Bool(a > b),
+        BinaryOp::GreaterEqual => Value::Bool(a >= b),
+        BinaryOp::Less => Value::Bool(a < b),
+        BinaryOp::LessEqual => Value::Bool(a <= b),
+        BinaryOp::EqualEqual | BinaryOp::BangEqual | BinaryOp::Plus => unreachable!(),
+    })
+}
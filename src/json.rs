@@ -0,0 +1,115 @@
+//! JSON export of the scanner and parser output, for tools that consume rlox's front end
+//! without linking the crate.
+//!
+//! Every document is an object with a `"version"` field, currently `1`. Fields are only ever
+//! added within a version; renaming or removing one bumps it.
+//!
+//! A span is `{"start": <byte offset>, "end": <byte offset>, "line": <1-based line>}`, where
+//! `start..end` is a half-open byte range into the source file.
+//!
+//! `rlox tokens --format json` prints `{"version": 1, "tokens": [<token>...]}`. A token is
+//! `{"kind": <kind>, "lexeme": <source text>, "span": <span>}`, plus `"value"` (a number or a
+//! string without its quotes) for `NUMBER` and `STRING` tokens. Kinds are the token type names
+//! from the book: `LEFT_PAREN`, `RIGHT_PAREN`, `LEFT_BRACE`, `RIGHT_BRACE`, `COMMA`, `DOT`,
+//! `SEMICOLON`, `MINUS`, `PLUS`, `STAR`, `SLASH`, `BANG`, `BANG_EQUAL`, `EQUAL`, `EQUAL_EQUAL`,
+//! `GREATER`, `GREATER_EQUAL`, `LESS`, `LESS_EQUAL`, `IDENTIFIER`, `STRING`, `NUMBER`, one kind
+//! per keyword (`AND`, `CLASS`, ... `WHILE`) and a final `EOF` with an empty lexeme. With
+//! `--trivia`, runs of whitespace and comments are included as `WHITESPACE` and `COMMENT` tokens.
+//!
+//! `rlox ast --format json` prints `{"version": 1, "ast": <node>}` rooted at a `Program` node.
+//! A node is `{"type": <type>, "span": <span>, "children": [{"role": <role>, "node": <node>}...]}`
+//! plus the type's attributes. Optional children are omitted when absent; repeated ones
+//! appear once per element, in source order.
+//!
+//! | type | attributes | children |
+//! |------|------------|----------|
+//! | `Program` | | `statement`* |
+//! | `Expression`, `Print` | | `expression` |
+//! | `Var` | `name` | `initializer`? |
+//! | `Block` | | `statement`* |
+//! | `If` | | `condition`, `then`, `else`? |
+//! | `While` | | `condition`, `body` |
+//! | `For` | | `initializer`?, `condition`?, `increment`?, `body` |
+//! | `Function` | `name`, `params` (array of strings) | `body`* |
+//! | `Return` | | `value`? |
+//! | `Class` | `name`, `superclass` (string or null) | `method`* (`Function` nodes) |
+//! | `Literal` | `value` (null, boolean, number or string) | |
+//! | `Variable` | `name` | |
+//! | `Assign` | `name` | `value` |
+//! | `Binary` | `operator` (`==`, `!=`, `<`, `<=`, `>`, `>=`, `+`, `-`, `*`, `/`) | `left`, `right` |
+//! | `Logical` | `operator` (`and`, `or`) | `left`, `right` |
+//! | `Grouping` | | `expression` |
+//! | `Unary` | `operator` (`-`, `!`) | `operand` |
+//! | `Call` | | `callee`, `argument`* |
+//! | `Get` | `name` | `object` |
+//! | `Set` | `name` | `object`, `value` |
+//! | `This` | | |
+//! | `Super` | `method` | |
+
+use serde_json::{json, Map, Value};
+
+use crate::parser::Stmt;
+use crate::tree::{Attribute, Node};
+use crate::types::{Literal, Span, Token};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub fn tokens(source: &str, tokens: &[Token]) -> Value {
+    let tokens: Vec<Value> = tokens
+        .iter()
+        .map(|token| token_json(source, token))
+        .collect();
+    json!({ "version": SCHEMA_VERSION, "tokens": tokens })
+}
+
+pub fn ast(statements: &[Stmt]) -> Value {
+    json!({ "version": SCHEMA_VERSION, "ast": node_json(&Node::program(statements)) })
+}
+
+fn span_json(span: Span) -> Value {
+    json!({ "start": span.start, "end": span.end, "line": span.line })
+}
+
+fn token_json(source: &str, token: &Token) -> Value {
+    let span = token.span();
+    let mut object = json!({
+        "kind": token.kind_name(),
+        "lexeme": &source[span.start..span.end],
+        "span": span_json(span),
+    });
+    match token {
+        Token::Literal {
+            token: Literal::Number { literal },
+            ..
+        } => object["value"] = json!(literal),
+        Token::Literal {
+            token: Literal::String { literal, .. },
+            ..
+        } => object["value"] = json!(literal),
+        _ => {}
+    }
+    object
+}
+
+fn node_json(node: &Node) -> Value {
+    let mut object = Map::new();
+    object.insert("type".into(), json!(node.kind));
+    object.insert("span".into(), span_json(node.span));
+    for (name, attribute) in &node.attributes {
+        let value = match attribute {
+            Attribute::Nil => Value::Null,
+            Attribute::Bool(value) => json!(value),
+            Attribute::Number(value) => json!(value),
+            Attribute::String(value) => json!(value),
+            Attribute::List(values) => json!(values),
+        };
+        object.insert((*name).into(), value);
+    }
+    let children: Vec<Value> = node
+        .children
+        .iter()
+        .map(|(role, child)| json!({ "role": role, "node": node_json(child) }))
+        .collect();
+    object.insert("children".into(), Value::Array(children));
+    Value::Object(object)
+}
@@ -0,0 +1,21 @@
+pub mod cst;
+pub mod dot;
+pub mod environment;
+pub mod formatter;
+pub mod highlight;
+pub mod interpreter;
+pub mod json;
+pub mod lint;
+pub mod lox;
+pub mod lsp;
+pub mod optimizer;
+pub mod parser;
+pub mod printer;
+pub mod repl;
+pub mod resolver;
+pub mod scanner;
+pub mod symbols;
+pub mod tree;
+pub mod types;
+pub mod value;
+pub mod vm;
@@ -0,0 +1,617 @@
+//! Lints: checks for code that is valid Lox but probably not what was meant.
+//!
+//! The pass runs over a resolved program and reports each finding as a `Diagnostic` with the
+//! lint that produced it, its severity and a span. A lint's severity comes from a `Config`,
+//! usually read from a `.loxlint` file, and can be lowered to `allow` for a single line with a
+//! comment at the end of that line, or on a line of its own just before it:
+//!
+//! ```text
+//! // lox-lint: allow(unused-variable, shadowing)
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::parser::{BinaryOp, Expr, ExprKind, Function, Stmt, StmtKind};
+use crate::resolver::Locals;
+use crate::scanner::Scanner;
+use crate::types::{Span, Token, Trivia};
+
+const ALLOW_COMMENT: &str = "lox-lint:";
+
+// the natives every interpreter defines, with their arities.
+const NATIVES: &[(&str, usize)] = &[("clock", 0), ("argc", 0), ("argv", 1)];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A local variable, parameter, function or class that is never read.
+    UnusedVariable,
+    /// Statements after a `return` in the same block.
+    UnreachableCode,
+    /// Assignment to a global that no top-level declaration defines.
+    UndeclaredGlobal,
+    /// Comparing an expression with itself, e.g. `x == x`.
+    SelfComparison,
+    /// A block statement with nothing in it, not even a comment.
+    EmptyBlock,
+    /// A local declaration that hides a local of an enclosing scope.
+    Shadowing,
+    /// A call to a known function or class with the wrong number of arguments.
+    WrongArity,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Lint::UnusedVariable,
+        Lint::UnreachableCode,
+        Lint::UndeclaredGlobal,
+        Lint::SelfComparison,
+        Lint::EmptyBlock,
+        Lint::Shadowing,
+        Lint::WrongArity,
+    ];
+
+    /// The name used in config files and `allow(...)` comments.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::UndeclaredGlobal => "undeclared-global",
+            Lint::SelfComparison => "self-comparison",
+            Lint::EmptyBlock => "empty-block",
+            Lint::Shadowing => "shadowing",
+            Lint::WrongArity => "wrong-arity",
+        }
+    }
+
+    /// Lints for code that fails whenever it runs are errors, the others warnings.
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Lint::UndeclaredGlobal | Lint::WrongArity => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .iter()
+            .find(|lint| lint.id() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown lint '{}'.", s))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Allow,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub const VARIANTS: &'static [&'static str] = &["allow", "warning", "error"];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Allow => "Allowed",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Severity::Allow),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!(
+                "Unknown severity '{}'; expected one of: {}.",
+                s,
+                Severity::VARIANTS.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub lint: Lint,
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] {}: {} [{}]",
+            self.span.line,
+            self.severity.label(),
+            self.message,
+            self.lint.id()
+        )
+    }
+}
+
+/// The severity of each lint. A config file has one `<lint> = <severity>` line per lint it
+/// changes, e.g. `shadowing = allow`; `#` starts a comment.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    severities: HashMap<Lint, Severity>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let setting = line
+                .split_once('=')
+                .ok_or_else(|| format!("Line {}: expected '<lint> = <severity>'.", i + 1));
+            let (lint, severity) = setting?;
+            let lint = lint
+                .trim()
+                .parse()
+                .map_err(|e| format!("Line {}: {}", i + 1, e))?;
+            let severity = severity
+                .trim()
+                .parse()
+                .map_err(|e| format!("Line {}: {}", i + 1, e))?;
+            config.set(lint, severity);
+        }
+        Ok(config)
+    }
+
+    pub fn set(&mut self, lint: Lint, severity: Severity) {
+        self.severities.insert(lint, severity);
+    }
+
+    pub fn severity(&self, lint: Lint) -> Severity {
+        self.severities
+            .get(&lint)
+            .copied()
+            .unwrap_or_else(|| lint.default_severity())
+    }
+}
+
+/// Lints a program that resolved without errors. Diagnostics come in source order, without
+/// the ones that are allowed.
+pub fn lint(
+    source: &str,
+    statements: &[Stmt],
+    locals: &Locals,
+    config: &Config,
+) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        source,
+        locals,
+        globals: globals(statements),
+        scopes: vec![],
+        found: vec![],
+    };
+    linter.statements(statements);
+
+    let allowed = allowed_by_comments(source);
+    let mut diagnostics: Vec<Diagnostic> = linter
+        .found
+        .into_iter()
+        .filter_map(|(lint, span, message)| {
+            let severity = if allowed.contains(&(span.line, lint)) {
+                Severity::Allow
+            } else {
+                config.severity(lint)
+            };
+            match severity {
+                Severity::Allow => None,
+                _ => Some(Diagnostic {
+                    lint,
+                    severity,
+                    span,
+                    message,
+                }),
+            }
+        })
+        .collect();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    diagnostics
+}
+
+// lines on which `allow(...)` comments switch lints off.
+fn allowed_by_comments(source: &str) -> HashSet<(usize, Lint)> {
+    let mut allowed = HashSet::new();
+    for token in Scanner::new(source).trivia(true).scan_ignoring_errors() {
+        if let Token::Trivia {
+            span,
+            token: Trivia::Comment,
+        } = token
+        {
+            let text = source[span.start + 2..span.end].trim();
+            let ids = text
+                .strip_prefix(ALLOW_COMMENT)
+                .map(str::trim)
+                .and_then(|text| text.strip_prefix("allow("))
+                .and_then(|text| text.strip_suffix(')'));
+            let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+            let line = if source[line_start..span.start].trim().is_empty() {
+                span.line + 1
+            } else {
+                span.line
+            };
+            for id in ids.into_iter().flat_map(|ids| ids.split(',')) {
+                if let Ok(lint) = id.trim().parse() {
+                    allowed.insert((line, lint));
+                }
+            }
+        }
+    }
+    allowed
+}
+
+// top-level declarations, with the arity of those that are known to be callable with it.
+fn globals<'a>(statements: &[Stmt<'a>]) -> HashMap<&'a str, Option<usize>> {
+    let mut globals: HashMap<&str, Option<usize>> = NATIVES
+        .iter()
+        .map(|(name, arity)| (*name, Some(*arity)))
+        .collect();
+    for statement in statements {
+        let (name, arity) = match &statement.kind {
+            StmtKind::Var { name, .. } => (name, None),
+            StmtKind::Function(function) => (&function.name, Some(function.params.len())),
+            StmtKind::Class {
+                name,
+                superclass,
+                methods,
+            } => (name, class_arity(superclass.as_ref(), methods)),
+            _ => continue,
+        };
+        // a global declared twice may hold either value.
+        let arity = if globals.contains_key(name.lexeme) {
+            None
+        } else {
+            arity
+        };
+        globals.insert(name.lexeme, arity);
+    }
+    globals
+}
+
+// a class takes the arguments of its initializer, which may be inherited.
+fn class_arity(superclass: Option<&Expr>, methods: &[std::rc::Rc<Function>]) -> Option<usize> {
+    match methods.iter().find(|method| method.name.lexeme == "init") {
+        Some(init) => Some(init.params.len()),
+        None if superclass.is_none() => Some(0),
+        None => None,
+    }
+}
+
+// whether running `stmt` always ends in a `return`.
+fn always_returns(stmt: &Stmt) -> bool {
+    match &stmt.kind {
+        StmtKind::Return(_) => true,
+        StmtKind::Block(statements) => statements.iter().any(always_returns),
+        StmtKind::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => always_returns(then_branch) && always_returns(else_branch),
+        _ => false,
+    }
+}
+
+// an expression that has the same value each time it's evaluated in a row.
+fn is_pure(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Literal(_) | ExprKind::Variable(_) | ExprKind::This | ExprKind::Super(_) => true,
+        ExprKind::Grouping(inner) | ExprKind::Unary(_, inner) | ExprKind::Get(inner, _) => {
+            is_pure(inner)
+        }
+        ExprKind::Binary(left, _, right) | ExprKind::Logical(left, _, right) => {
+            is_pure(left) && is_pure(right)
+        }
+        ExprKind::Assign(..) | ExprKind::Call(..) | ExprKind::Set(..) => false,
+    }
+}
+
+struct Binding<'a> {
+    name: &'a str,
+    // "Local variable", "Parameter", ...; `this` and `super` have none and are never reported.
+    kind: Option<&'static str>,
+    span: Span,
+    arity: Option<usize>,
+    used: bool,
+}
+
+struct Linter<'a, 'l> {
+    source: &'l str,
+    locals: &'l Locals,
+    globals: HashMap<&'a str, Option<usize>>,
+    // the resolver's scopes, with what the lints need to know about each local.
+    scopes: Vec<Vec<Binding<'a>>>,
+    found: Vec<(Lint, Span, String)>,
+}
+
+impl<'a, 'l> Linter<'a, 'l> {
+    fn report(&mut self, lint: Lint, span: Span, message: String) {
+        self.found.push((lint, span, message));
+    }
+
+    fn statements(&mut self, statements: &[Stmt<'a>]) {
+        if let Some(i) = statements.iter().position(always_returns) {
+            if let (Some(first), Some(last)) = (statements.get(i + 1), statements.last()) {
+                self.report(
+                    Lint::UnreachableCode,
+                    first.span.to(last.span),
+                    String::from("Unreachable code after 'return'."),
+                );
+            }
+        }
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt<'a>) {
+        match &stmt.kind {
+            StmtKind::Expression(expr) | StmtKind::Print(expr) => self.expression(expr),
+            StmtKind::Var { name, initializer } => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.declare(name.lexeme, "Local variable", name.span, None);
+            }
+            StmtKind::Block(statements) => {
+                let text = &self.source[stmt.span.start..stmt.span.end];
+                if statements.is_empty() && !text.contains("//") {
+                    self.report(Lint::EmptyBlock, stmt.span, String::from("Empty block."));
+                }
+                self.begin_scope();
+                self.statements(statements);
+                self.end_scope();
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.expression(condition);
+                self.statement(body);
+            }
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                self.begin_scope();
+                if let Some(initializer) = initializer {
+                    self.statement(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+                self.statement(body);
+                self.end_scope();
+            }
+            StmtKind::Function(function) => {
+                let arity = Some(function.params.len());
+                self.declare(
+                    function.name.lexeme,
+                    "Local function",
+                    function.name.span,
+                    arity,
+                );
+                self.function(function);
+            }
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            StmtKind::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let arity = class_arity(superclass.as_ref(), methods);
+                self.declare(name.lexeme, "Local class", name.span, arity);
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                    self.begin_scope();
+                    self.implicit("super");
+                }
+                self.begin_scope();
+                self.implicit("this");
+                for method in methods {
+                    self.function(method);
+                }
+                self.end_scope();
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Function<'a>) {
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param.lexeme, "Parameter", param.span, None);
+        }
+        self.statements(&function.body);
+        self.end_scope();
+    }
+
+    fn expression(&mut self, expr: &Expr<'a>) {
+        match &expr.kind {
+            ExprKind::Literal(_) | ExprKind::This | ExprKind::Super(_) => {}
+            ExprKind::Variable(name) => {
+                if let Some(binding) = self.binding(expr, name) {
+                    binding.used = true;
+                }
+            }
+            ExprKind::Assign(name, value) => {
+                self.expression(value);
+                let declared = self.globals.contains_key(name.lexeme);
+                match self.binding(expr, name.lexeme) {
+                    // the value may no longer be the function that was declared.
+                    Some(binding) => binding.arity = None,
+                    None if !declared => self.report(
+                        Lint::UndeclaredGlobal,
+                        expr.span,
+                        format!("Assignment to undeclared global '{}'.", name.lexeme),
+                    ),
+                    None => {}
+                }
+            }
+            ExprKind::Binary(left, operator, right) => {
+                let comparison = !matches!(
+                    operator,
+                    BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Star | BinaryOp::Slash
+                );
+                if comparison && left == right && is_pure(left) {
+                    self.report(
+                        Lint::SelfComparison,
+                        expr.span,
+                        format!(
+                            "Comparison of an expression with itself using '{}'.",
+                            operator
+                        ),
+                    );
+                }
+                self.expression(left);
+                self.expression(right);
+            }
+            ExprKind::Logical(left, _, right) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            ExprKind::Grouping(inner) | ExprKind::Unary(_, inner) => self.expression(inner),
+            ExprKind::Call(callee, arguments) => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+                if let ExprKind::Variable(name) = callee.kind {
+                    if let Some(arity) = self.arity(callee, name) {
+                        if arity != arguments.len() {
+                            self.report(
+                                Lint::WrongArity,
+                                expr.span,
+                                format!(
+                                    "'{}' expects {} argument{} but is called with {}.",
+                                    name,
+                                    arity,
+                                    if arity == 1 { "" } else { "s" },
+                                    arguments.len()
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+            ExprKind::Get(object, _) => self.expression(object),
+            ExprKind::Set(object, _, value) => {
+                self.expression(value);
+                self.expression(object);
+            }
+        }
+    }
+
+    // the local an expression was resolved to.
+    fn binding(&mut self, expr: &Expr, name: &str) -> Option<&mut Binding<'a>> {
+        let depth = *self.locals.get(&expr.id)?;
+        let index = self.scopes.len().checked_sub(depth + 1)?;
+        self.scopes[index]
+            .iter_mut()
+            .rev()
+            .find(|binding| binding.name == name)
+    }
+
+    fn arity(&mut self, expr: &Expr, name: &str) -> Option<usize> {
+        if self.locals.contains_key(&expr.id) {
+            self.binding(expr, name).and_then(|binding| binding.arity)
+        } else {
+            self.globals.get(name).copied().flatten()
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap_or_default();
+        for binding in scope {
+            if let (Some(kind), false) = (binding.kind, binding.used) {
+                if !binding.name.starts_with('_') {
+                    self.report(
+                        Lint::UnusedVariable,
+                        binding.span,
+                        format!("{} '{}' is never used.", kind, binding.name),
+                    );
+                }
+            }
+        }
+    }
+
+    // like the resolver, only declarations in local scopes are tracked.
+    fn declare(&mut self, name: &'a str, kind: &'static str, span: Span, arity: Option<usize>) {
+        if self.scopes.is_empty() {
+            return;
+        }
+        let enclosing = &self.scopes[..self.scopes.len() - 1];
+        let shadowed = enclosing
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|binding| binding.name == name && binding.kind.is_some())
+            .map(|binding| binding.span.line);
+        if let Some(line) = shadowed {
+            self.report(
+                Lint::Shadowing,
+                span,
+                format!("'{}' shadows a local declared on line {}.", name, line),
+            );
+        }
+        self.scopes.last_mut().unwrap().push(Binding {
+            name,
+            kind: Some(kind),
+            span,
+            arity,
+            used: false,
+        });
+    }
+
+    fn implicit(&mut self, name: &'a str) {
+        self.scopes.last_mut().unwrap().push(Binding {
+            name,
+            kind: None,
+            span: Span::default(),
+            arity: None,
+            used: true,
+        });
+    }
+}
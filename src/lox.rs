@@ -1,54 +1,628 @@
-use std::io::{self, Write};
-use text_io::read;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
 
+use crate::cst;
+use crate::dot;
+use crate::formatter;
+use crate::highlight;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::json;
+use crate::lint::{self, Config, Diagnostic, Severity};
+use crate::optimizer;
+use crate::parser::{Parser, Stmt};
+use crate::printer::AstPrinter;
+use crate::repl;
+use crate::resolver::{Locals, Resolver};
 use crate::scanner::Scanner;
+use crate::types::{Span, Token};
+use crate::value::Value;
+use crate::vm::disassembler;
+use crate::vm::gc::GcMode;
+use crate::vm::object::ObjRef;
+use crate::vm::{self, Vm};
+
+/// Why a command failed. Diagnostics for static and runtime errors are printed as they are
+/// found, so the variants only classify the failure, e.g. for choosing an exit code.
+#[derive(Debug)]
+pub enum LoxError {
+    /// The command line was malformed.
+    Usage(String),
+    /// A source file couldn't be read.
+    Io { path: PathBuf, error: io::Error },
+    /// Scanning, parsing or resolving reported errors.
+    Static,
+    /// The program failed while running.
+    Runtime(RuntimeError),
+    /// `rlox fmt --check` found a program that isn't formatted.
+    Unformatted,
+    /// `rlox lint` found problems at `Severity::Error`.
+    Lint,
+}
+
+impl LoxError {
+    /// Exit status following the BSD sysexits convention, as the book's jlox and clox do.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // like `rustfmt --check`, outside of the sysexits range.
+            LoxError::Unformatted | LoxError::Lint => 1,
+            LoxError::Usage(_) => 64,
+            LoxError::Static => 65,
+            LoxError::Io { .. } => 66,
+            LoxError::Runtime(_) => 70,
+        }
+    }
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxError::Usage(message) => write!(f, "{}", message),
+            LoxError::Io { path, error } => write!(f, "Could not read {:?}: {}", path, error),
+            LoxError::Static => write!(f, "Compilation failed."),
+            LoxError::Runtime(error) => write!(f, "{}", error),
+            LoxError::Unformatted => write!(f, "Not formatted; run `rlox fmt` to see the changes."),
+            LoxError::Lint => write!(f, "Lints failed."),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+/// Reads a source file, or stdin when `path` is `-`.
+pub fn read_source(path: &Path) -> Result<String, LoxError> {
+    let mut source = String::new();
+    let result = if path == Path::new("-") {
+        io::stdin().read_to_string(&mut source).map(|_| source)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    result.map_err(|error| LoxError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
 
 pub struct Lox {
     had_error: bool,
+    had_runtime_error: bool,
+    trace: bool,
+    color: bool,
+    backend: Backend,
+    trace_execution: bool,
+    stress_gc: bool,
+    gc: GcMode,
+    gc_stats: bool,
+    profile: bool,
+    optimize: bool,
+    collected: Option<Vec<Report>>,
+}
+
+/// A static error as reported, kept by a `Lox` that collects errors instead of printing them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub line: usize,
+    /// The token the error is at, when the reporter has it.
+    pub span: Option<Span>,
+    /// Where on the line, as printed: `at 'x'`, `at end`, or empty.
+    pub location: String,
+    pub message: String,
+}
+
+/// What runs programs: the tree-walking `interpreter` or the bytecode `vm`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Tree,
+    Vm,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum TokenFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum AstFormat {
+    Lisp,
+    Json,
+    Dot,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum HighlightFormat {
+    Ansi,
+    Html,
+}
+
+impl Backend {
+    pub const VARIANTS: &'static [&'static str] = &["tree", "vm"];
+}
+
+impl TokenFormat {
+    pub const VARIANTS: &'static [&'static str] = &["text", "json"];
+}
+
+impl AstFormat {
+    pub const VARIANTS: &'static [&'static str] = &["lisp", "json", "dot"];
+}
+
+impl HighlightFormat {
+    pub const VARIANTS: &'static [&'static str] = &["ansi", "html"];
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tree" => Ok(Backend::Tree),
+            "vm" => Ok(Backend::Vm),
+            _ => Err(format!("unknown backend '{}'", s)),
+        }
+    }
+}
+
+impl FromStr for TokenFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(TokenFormat::Text),
+            "json" => Ok(TokenFormat::Json),
+            _ => Err(format!("unknown token format '{}'", s)),
+        }
+    }
+}
+
+impl FromStr for AstFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lisp" => Ok(AstFormat::Lisp),
+            "json" => Ok(AstFormat::Json),
+            "dot" => Ok(AstFormat::Dot),
+            _ => Err(format!("unknown ast format '{}'", s)),
+        }
+    }
+}
+
+impl FromStr for HighlightFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ansi" => Ok(HighlightFormat::Ansi),
+            "html" => Ok(HighlightFormat::Html),
+            _ => Err(format!("unknown highlight format '{}'", s)),
+        }
+    }
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Lox {
     pub fn new() -> Lox {
-        Lox { had_error: false }
+        Lox {
+            had_error: false,
+            had_runtime_error: false,
+            trace: false,
+            color: false,
+            backend: Backend::Tree,
+            trace_execution: false,
+            stress_gc: false,
+            gc: GcMode::MarkSweep,
+            gc_stats: false,
+            profile: false,
+            optimize: true,
+            collected: None,
+        }
+    }
+
+    /// Print each statement to stderr before it is executed.
+    pub fn trace(mut self, trace: bool) -> Lox {
+        self.trace = trace;
+        self
+    }
+
+    /// Use ANSI colors in diagnostics.
+    pub fn color(mut self, color: bool) -> Lox {
+        self.color = color;
+        self
+    }
+
+    /// Run programs with `backend`. Only `run_source` uses the VM; the REPL always uses the
+    /// interpreter.
+    pub fn backend(mut self, backend: Backend) -> Lox {
+        self.backend = backend;
+        self
     }
 
-    fn report(&self, line_number: usize, loc: String, message: &str) {
-        println!("[line {}] Error {}: {}", line_number, loc, message);
+    /// Print the VM's stack and each instruction to stderr before it is executed.
+    pub fn trace_execution(mut self, trace: bool) -> Lox {
+        self.trace_execution = trace;
+        self
+    }
+
+    /// Make the VM collect garbage on every allocation.
+    pub fn stress_gc(mut self, stress: bool) -> Lox {
+        self.stress_gc = stress;
+        self
+    }
+
+    /// Collect the VM's garbage in `mode`.
+    pub fn gc(mut self, mode: GcMode) -> Lox {
+        self.gc = mode;
+        self
+    }
+
+    /// Print what the VM's garbage collector did to stderr once a program finishes.
+    pub fn gc_stats(mut self, stats: bool) -> Lox {
+        self.gc_stats = stats;
+        self
+    }
+
+    /// Print the instructions the VM ran most, and the pairs of them, to stderr once a
+    /// program finishes.
+    pub fn profile(mut self, profile: bool) -> Lox {
+        self.profile = profile;
+        self
+    }
+
+    /// Simplify programs with the `optimizer` before running or disassembling them, and
+    /// compile them for the VM with its fused instructions: `Invoke` for method calls and the
+    /// superinstructions. On unless turned off.
+    pub fn optimize(mut self, optimize: bool) -> Lox {
+        self.optimize = optimize;
+        self
+    }
+
+    pub(crate) fn optimizing(&self) -> bool {
+        self.optimize
+    }
+
+    /// Keep static errors for `take_errors` instead of printing them.
+    pub fn collect_errors(mut self) -> Lox {
+        self.collected = Some(vec![]);
+        self
+    }
+
+    /// The errors collected since the last call, and forgets that there were any.
+    pub fn take_errors(&mut self) -> Vec<Report> {
+        self.had_error = false;
+        self.collected
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn report(&mut self, line_number: usize, span: Option<Span>, loc: String, message: &str) {
+        if let Some(collected) = &mut self.collected {
+            collected.push(Report {
+                line: line_number,
+                span,
+                location: loc,
+                message: message.to_string(),
+            });
+            return;
+        }
+        let error = if self.color {
+            "\x1b[1;31mError\x1b[0m"
+        } else {
+            "Error"
+        };
+        if loc.is_empty() {
+            eprintln!("[line {}] {}: {}", line_number, error, message);
+        } else {
+            eprintln!("[line {}] {} {}: {}", line_number, error, loc, message);
+        }
     }
 
     pub fn error(&mut self, line_number: usize, message: &str) {
-        self.report(line_number, String::from(""), message);
+        self.report(line_number, None, String::from(""), message);
         self.had_error = true;
     }
 
-    fn run(&mut self, source: &str) {
+    /// Like `error`, for an error at a known piece of source rather than at a token.
+    pub fn error_in(&mut self, span: Span, message: &str) {
+        self.report(span.line, Some(span), String::from(""), message);
+        self.had_error = true;
+    }
+
+    pub fn error_at(&mut self, token: &Token, message: &str) {
+        let loc = match token {
+            Token::Eof { .. } => String::from("at end"),
+            _ => format!("at '{}'", token),
+        };
+        self.report(token.line(), Some(token.span()), loc, message);
+        self.had_error = true;
+    }
+
+    pub fn error_at_lexeme(&mut self, line_number: usize, lexeme: &str, message: &str) {
+        self.report(line_number, None, format!("at '{}'", lexeme), message);
+        self.had_error = true;
+    }
+
+    pub fn runtime_error(&mut self, error: &RuntimeError) {
+        eprintln!("{}", error);
+        self.had_runtime_error = true;
+    }
+
+    fn static_errors(&self) -> Result<(), LoxError> {
+        if self.had_error {
+            Err(LoxError::Static)
+        } else {
+            Ok(())
+        }
+    }
+
+    // scans, parses and resolves, stopping after the first phase that reports errors.
+    fn compile<'a>(&mut self, source: &'a str) -> Result<(Vec<Stmt<'a>>, Locals), LoxError> {
         let scanner = Scanner::new(source);
         let tokens = scanner.scan_tokens(self);
-        for token in tokens {
-            print!("{:?}", token);
+        let statements = Parser::new(&tokens).parse(self);
+        self.static_errors()?;
+        let locals = Resolver::new().resolve(&statements, self);
+        self.static_errors()?;
+        Ok((statements, locals))
+    }
+
+    // compiles a program for running it.
+    fn compile_to_run<'a>(&mut self, source: &'a str) -> Result<(Vec<Stmt<'a>>, Locals), LoxError> {
+        let (statements, locals) = self.compile(source)?;
+        if self.optimize {
+            Ok((optimizer::optimize(statements), locals))
+        } else {
+            Ok((statements, locals))
+        }
+    }
+
+    /// Runs a program; `args` are available to it through the `argc()` and `argv(i)` natives.
+    pub fn run_source(&mut self, source: &str, args: &[String]) -> Result<(), LoxError> {
+        if self.backend == Backend::Vm {
+            return self.run_vm(source, args);
         }
-        println!();
+        let mut interpreter = self.interpreter(args);
+        self.run_in(&mut interpreter, source)
+    }
+
+    fn run_vm(&mut self, source: &str, args: &[String]) -> Result<(), LoxError> {
+        let mut vm = self.vm(args);
+        let result = self.run_in_vm(&mut vm, source);
+        if self.gc_stats {
+            eprint!("{}", vm.heap().stats());
+        }
+        if let Some(profile) = vm.profile() {
+            eprint!("{}", profile);
+        }
+        result
+    }
+
+    /// A VM set up the way `run_source` uses it.
+    pub fn vm(&self, args: &[String]) -> Vm {
+        let mut vm = Vm::new();
+        vm.set_trace(self.trace_execution);
+        vm.set_stress_gc(self.stress_gc);
+        vm.set_gc_mode(self.gc);
+        vm.set_profiling(self.profile);
+        define_vm_args(&mut vm, args);
+        vm
+    }
+
+    /// Runs a program on an existing VM, which keeps the globals it defines.
+    pub fn run_in_vm(&mut self, vm: &mut Vm, source: &str) -> Result<(), LoxError> {
+        let script = self.compile_vm(source, vm)?;
+        let result = vm.interpret(script);
         io::stdout().flush().unwrap();
+        result.map_err(|error| {
+            self.runtime_error(&error);
+            LoxError::Runtime(error)
+        })
     }
 
-    pub fn run_file(&mut self, path: std::path::PathBuf) -> io::Result<()> {
-        println!("Running {:?}", path);
-        let source = std::fs::read_to_string(&path)?;
-        self.run(&source);
-        if self.had_error {
-            println!("Error during scanning, exit...");
-            std::process::exit(1);
+    /// An interpreter set up the way `run_source` uses it.
+    pub fn interpreter<'a>(&self, args: &'a [String]) -> Interpreter<'a> {
+        let mut interpreter = self.session_interpreter();
+        define_args(&mut interpreter, args);
+        interpreter
+    }
+
+    // an interpreter for the REPL, which has no script arguments: it lacks `argc` and `argv`.
+    pub(crate) fn session_interpreter<'a>(&self) -> Interpreter<'a> {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_trace(self.trace);
+        interpreter
+    }
+
+    /// Runs a program on an existing interpreter, which keeps the globals it defines.
+    pub fn run_in<'a>(
+        &mut self,
+        interpreter: &mut Interpreter<'a>,
+        source: &'a str,
+    ) -> Result<(), LoxError> {
+        let (statements, locals) = self.compile_to_run(source)?;
+        let result = interpreter.interpret(&statements, locals);
+        io::stdout().flush().unwrap();
+        result.map_err(|error| {
+            self.runtime_error(&error);
+            LoxError::Runtime(error)
+        })
+    }
+
+    // compiles a program to bytecode for `vm`.
+    fn compile_vm(&mut self, source: &str, vm: &mut Vm) -> Result<ObjRef, LoxError> {
+        let (statements, _) = self.compile_to_run(source)?;
+        let script = vm::compile(&statements, vm, self);
+        self.static_errors()?;
+        Ok(script)
+    }
+
+    /// Prints the bytecode the VM backend compiles a program to; see `vm::disassembler`.
+    pub fn disassemble(&mut self, source: &str) -> Result<(), LoxError> {
+        let mut vm = Vm::new();
+        let script = self.compile_vm(source, &mut vm)?;
+        print!("{}", disassembler::disassemble(vm.heap(), script));
+        Ok(())
+    }
+
+    /// Scans, parses and resolves a program without running it.
+    pub fn check(&mut self, source: &str) -> Result<(), LoxError> {
+        self.compile(source).map(|_| ())
+    }
+
+    /// Prints the tokens of a program; scan errors are reported but don't stop the dump.
+    pub fn dump_tokens(
+        &mut self,
+        source: &str,
+        format: TokenFormat,
+        trivia: bool,
+    ) -> Result<(), LoxError> {
+        let scanner = Scanner::new(source).trivia(trivia);
+        let tokens = scanner.scan_tokens(self);
+        match format {
+            TokenFormat::Text => {
+                for token in &tokens {
+                    println!("{:?}", token);
+                }
+            }
+            TokenFormat::Json => println!("{}", json::tokens(source, &tokens)),
+        }
+        self.static_errors()
+    }
+
+    /// Prints the lossless syntax tree of a program, one node or token per line. It's printed
+    /// even if there are errors.
+    pub fn dump_cst(&mut self, source: &str) -> Result<(), LoxError> {
+        print!("{:?}", cst::parse(source, self));
+        self.static_errors()
+    }
+
+    /// Prints a program with syntax highlighting. Scan errors are left unhighlighted rather
+    /// than reported.
+    pub fn highlight(&self, source: &str, format: HighlightFormat) {
+        match format {
+            HighlightFormat::Ansi => print!("{}", highlight::ansi(source)),
+            HighlightFormat::Html => print!("{}", highlight::html(source)),
+        }
+    }
+
+    /// Prints the syntax tree of a program, provided it scans and parses without errors.
+    pub fn dump_ast(&mut self, source: &str, format: AstFormat) -> Result<(), LoxError> {
+        let scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens(self);
+        let statements = Parser::new(&tokens).parse(self);
+        self.static_errors()?;
+        match format {
+            AstFormat::Lisp => print!("{}", AstPrinter::Lisp.print_program(&statements)),
+            AstFormat::Json => println!("{}", json::ast(&statements)),
+            AstFormat::Dot => print!("{}", dot::ast(&statements)),
         }
         Ok(())
     }
 
-    pub fn repl(&mut self) {
-        println!("Welcome to Lox!");
-        println!("--------------");
-        loop {
-            let line: String = read!("{}\n");
-            self.run(&line);
-            self.had_error = false;
+    /// A program in canonical source form, comments included; see the `formatter` module.
+    pub fn formatted(&mut self, source: &str) -> Result<String, LoxError> {
+        let scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens(self);
+        let statements = Parser::new(&tokens).parse(self);
+        self.static_errors()?;
+        Ok(formatter::format(source, &statements))
+    }
+
+    /// Prints a program in canonical source form. With `check`, prints nothing and fails with
+    /// `LoxError::Unformatted` unless the program is already formatted.
+    pub fn format(&mut self, source: &str, check: bool) -> Result<(), LoxError> {
+        let formatted = self.formatted(source)?;
+        if !check {
+            print!("{}", formatted);
+        } else if formatted != source {
+            return Err(LoxError::Unformatted);
+        }
+        Ok(())
+    }
+
+    /// The lint diagnostics of a program that compiles; see the `lint` module.
+    pub fn lints(&mut self, source: &str, config: &Config) -> Result<Vec<Diagnostic>, LoxError> {
+        let (statements, locals) = self.compile(source)?;
+        Ok(lint::lint(source, &statements, &locals, config))
+    }
+
+    /// Prints the lint diagnostics of a program to stderr. Fails if any is an error.
+    pub fn lint(&mut self, source: &str, config: &Config) -> Result<(), LoxError> {
+        let diagnostics = self.lints(source, config)?;
+        for diagnostic in &diagnostics {
+            let severity = match (self.color, diagnostic.severity) {
+                (true, Severity::Error) => "\x1b[1;31mError\x1b[0m",
+                (true, _) => "\x1b[1;33mWarning\x1b[0m",
+                (false, severity) => severity.label(),
+            };
+            eprintln!(
+                "[line {}] {}: {} [{}]",
+                diagnostic.span.line,
+                severity,
+                diagnostic.message,
+                diagnostic.lint.id()
+            );
         }
+        if diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            return Err(LoxError::Lint);
+        }
+        Ok(())
+    }
+
+    /// Runs entries typed at a prompt until end of input. Errors are reported and the session
+    /// carries on.
+    pub fn repl(&mut self) {
+        repl::run(self);
     }
+
+    pub(crate) fn uses_color(&self) -> bool {
+        self.color
+    }
+
+    // errors of one REPL entry don't carry over to the next.
+    pub(crate) fn reset_errors(&mut self) {
+        self.had_error = false;
+        self.had_runtime_error = false;
+    }
+}
+
+const ARGUMENT_INDEX_ERROR: &str = "Argument index must be a non-negative integer.";
+
+fn define_args<'a>(interpreter: &mut Interpreter<'a>, args: &'a [String]) {
+    interpreter.define_native("argc", 0, move |_| Ok(Value::Number(args.len() as f64)));
+    interpreter.define_native("argv", 1, move |arguments| match &arguments[0] {
+        Value::Number(index) if *index >= 0.0 && index.fract() == 0.0 => Ok(args
+            .get(*index as usize)
+            .map_or(Value::Nil, |arg| Value::String(Rc::from(arg.as_str())))),
+        _ => Err(String::from(ARGUMENT_INDEX_ERROR)),
+    });
+}
+
+fn define_vm_args(vm: &mut Vm, args: &[String]) {
+    let count = args.len();
+    vm.define_native("argc", 0, move |_, _| {
+        Ok(vm::value::Value::number(count as f64))
+    });
+    let args = args.to_vec();
+    vm.define_native("argv", 1, move |heap, arguments| {
+        match arguments[0].as_number() {
+            Some(index) if index >= 0.0 && index.fract() == 0.0 => Ok(args
+                .get(index as usize)
+                .map_or(vm::value::Value::nil(), |arg| {
+                    vm::value::Value::obj(heap.intern(arg))
+                })),
+            _ => Err(String::from(ARGUMENT_INDEX_ERROR)),
+        }
+    });
 }
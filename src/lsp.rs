@@ -0,0 +1,533 @@
+//! A Language Server Protocol server for Lox, run by `rlox lsp` over stdin and stdout.
+//!
+//! Documents are synchronized in full on every change. Each change publishes the errors of the
+//! scanner, parser and resolver as diagnostics, or the lints of a program without errors.
+//! Requests are answered from a fresh analysis of the document:
+//!
+//! - `textDocument/definition` and `textDocument/references` follow the resolver's bindings
+//!   (see the `symbols` module);
+//! - `textDocument/hover` shows a declaration's signature and what kind of declaration it is;
+//! - `textDocument/documentSymbol` lists classes with their methods, functions and global
+//!   variables;
+//! - `textDocument/semanticTokens/full` classifies the scanner's tokens, with the types in
+//!   `TOKEN_TYPES`;
+//! - `textDocument/formatting` formats like `rlox fmt`.
+//!
+//! `Server` handles decoded messages and can be driven directly; `serve` adds the protocol's
+//! `Content-Length` framing.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+use crate::formatter;
+use crate::highlight::Highlight;
+use crate::lint::{self, Config, Severity};
+use crate::lox::{Lox, Report};
+use crate::parser::{Parser, Stmt};
+use crate::resolver::{Locals, Resolver};
+use crate::scanner::Scanner;
+use crate::symbols::{SymbolKind, Symbols};
+use crate::types::{Literal, Misc, Span, Token};
+
+/// The legend of semantic token types, in the order their indices refer to.
+pub const TOKEN_TYPES: &[&str] = &[
+    "keyword",
+    "string",
+    "number",
+    "operator",
+    "comment",
+    "variable",
+    "parameter",
+    "function",
+    "class",
+    "method",
+    "property",
+];
+
+// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serves one client until it sends `exit` or closes its end. Returns whether the session
+/// ended properly, with a `shutdown` request before the end.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<bool> {
+    let mut server = Server::new();
+    while let Some(body) = read_message(&mut input)? {
+        let replies = match serde_json::from_str(&body) {
+            Ok(message) => server.handle(&message),
+            Err(error) => vec![error_response(Value::Null, PARSE_ERROR, &error.to_string())],
+        };
+        for reply in replies {
+            write_message(&mut output, &reply)?;
+        }
+        if server.exited {
+            break;
+        }
+    }
+    Ok(server.shut_down)
+}
+
+// the body of the next message, or `None` at the end of input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Message without a Content-Length.",
+        )
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": code, "message": message},
+    })
+}
+
+/// The state of one session: the open documents, by URI.
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, String>,
+    shut_down: bool,
+    exited: bool,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server::default()
+    }
+
+    /// Whether the client has sent `exit`; nothing else should be handled after it.
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Handles one message from the client and returns the messages to send back: the
+    /// response to a request, and notifications such as published diagnostics.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return self.notification(method, params),
+        };
+        if self.shut_down {
+            return vec![error_response(
+                id,
+                INVALID_REQUEST,
+                "The server has shut down.",
+            )];
+        }
+        let result = match method {
+            "initialize" => Ok(initialize_result()),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition"
+            | "textDocument/references"
+            | "textDocument/hover"
+            | "textDocument/documentSymbol"
+            | "textDocument/semanticTokens/full"
+            | "textDocument/formatting" => self.document_request(method, params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'.", method))),
+        };
+        vec![match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => error_response(id, code, &message),
+        }]
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "exit" => self.exited = true,
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+                return vec![publish_diagnostics(uri, text)];
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                let text = changes
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                if let (Some(text), Some(document)) = (text, self.documents.get_mut(uri)) {
+                    *document = text.to_string();
+                    return vec![publish_diagnostics(uri, text)];
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": {"uri": uri, "diagnostics": []},
+                })];
+            }
+            // `initialized`, `$/cancelRequest`, ... need no answer.
+            _ => {}
+        }
+        vec![]
+    }
+
+    fn document_request(&self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let source = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document '{}'.", uri)))?;
+        let lines = LineIndex::new(source);
+        let (statements, locals, errors) = analyze(source);
+        let symbols = Symbols::new(&statements, &locals);
+        let offset = lines.offset(&params["position"]);
+        let location = |span: Span| json!({"uri": uri, "range": lines.range(span)});
+        Ok(match method {
+            "textDocument/definition" => match symbols.at(offset) {
+                Some(symbol) => location(symbol.span),
+                None => Value::Null,
+            },
+            "textDocument/references" => match symbols.at(offset) {
+                Some(symbol) => {
+                    let mut spans = symbol.references.clone();
+                    if params["context"]["includeDeclaration"].as_bool() == Some(true) {
+                        spans.push(symbol.span);
+                    }
+                    spans.sort_by_key(|span| span.start);
+                    Value::from(spans.into_iter().map(location).collect::<Vec<_>>())
+                }
+                None => Value::Null,
+            },
+            "textDocument/hover" => match symbols.at(offset) {
+                Some(symbol) => {
+                    let covering = std::iter::once(symbol.span)
+                        .chain(symbol.references.iter().copied())
+                        .find(|span| span.start <= offset && offset <= span.end);
+                    json!({
+                        "contents": {
+                            "kind": "markdown",
+                            "value": format!(
+                                "```lox\n{}\n```\n{}",
+                                symbol.signature,
+                                symbol.description(&symbols)
+                            ),
+                        },
+                        "range": covering.map(|span| lines.range(span)),
+                    })
+                }
+                None => Value::Null,
+            },
+            "textDocument/documentSymbol" => document_symbols(&symbols, &lines),
+            "textDocument/semanticTokens/full" => {
+                json!({"data": semantic_tokens(source, &symbols, &lines)})
+            }
+            _ => {
+                if !errors.is_empty() {
+                    return Ok(Value::Null);
+                }
+                let formatted = formatter::format(source, &statements);
+                if formatted == *source {
+                    json!([])
+                } else {
+                    let whole = Span {
+                        start: 0,
+                        end: source.len(),
+                        line: 1,
+                    };
+                    json!([{"range": lines.range(whole), "newText": formatted}])
+                }
+            }
+        })
+    }
+}
+
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            // full text on every change.
+            "textDocumentSync": 1,
+            "definitionProvider": true,
+            "referencesProvider": true,
+            "hoverProvider": true,
+            "documentSymbolProvider": true,
+            "documentFormattingProvider": true,
+            "semanticTokensProvider": {
+                "legend": {"tokenTypes": TOKEN_TYPES, "tokenModifiers": []},
+                "full": true,
+            },
+        },
+        "serverInfo": {"name": "rlox", "version": env!("CARGO_PKG_VERSION")},
+    })
+}
+
+// the program as far as it parses, its bindings and every error found on the way.
+fn analyze(source: &str) -> (Vec<Stmt<'_>>, Locals, Vec<Report>) {
+    let mut lox = Lox::new().collect_errors();
+    let tokens = Scanner::new(source).scan_tokens(&mut lox);
+    let statements = Parser::new(&tokens).parse(&mut lox);
+    let locals = Resolver::new().resolve(&statements, &mut lox);
+    let errors = lox.take_errors();
+    (statements, locals, errors)
+}
+
+fn publish_diagnostics(uri: &str, source: &str) -> Value {
+    let lines = LineIndex::new(source);
+    let (statements, locals, errors) = analyze(source);
+    let diagnostics: Vec<Value> = if errors.is_empty() {
+        lint::lint(source, &statements, &locals, &Config::default())
+            .iter()
+            .map(|diagnostic| {
+                json!({
+                    "range": lines.range(diagnostic.span),
+                    "severity": if diagnostic.severity == Severity::Error { 1 } else { 2 },
+                    "code": diagnostic.lint.id(),
+                    "source": "rlox",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect()
+    } else {
+        let mut errors: Vec<(Span, &Report)> = errors
+            .iter()
+            .map(|error| (error_span(&lines, error), error))
+            .collect();
+        // scan errors are all reported before the parser's.
+        errors.sort_by_key(|(span, _)| span.start);
+        errors
+            .into_iter()
+            .map(|(span, error)| {
+                let message = match error.location.as_str() {
+                    "" => error.message.clone(),
+                    location => format!("Error {}: {}", location, error.message),
+                };
+                json!({
+                    "range": lines.range(span),
+                    "severity": 1,
+                    "source": "rlox",
+                    "message": message,
+                })
+            })
+            .collect()
+    };
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+// errors reported without a token cover the lexeme they name, or else their whole line.
+fn error_span(lines: &LineIndex, error: &Report) -> Span {
+    if let Some(span) = error.span {
+        return span;
+    }
+    let (start, end) = lines.line_range(error.line);
+    let text = &lines.source[start..end];
+    let lexeme = error
+        .location
+        .strip_prefix("at '")
+        .and_then(|lexeme| lexeme.strip_suffix('\''));
+    let span = |start, end| Span {
+        start,
+        end,
+        line: error.line,
+    };
+    match lexeme.and_then(|lexeme| text.find(lexeme).map(|i| (i, lexeme.len()))) {
+        Some((i, len)) => span(start + i, start + i + len),
+        None => {
+            let indent = text.len() - text.trim_start().len();
+            span(start + indent, start + text.trim_end().len())
+        }
+    }
+}
+
+fn document_symbols(symbols: &Symbols, lines: &LineIndex) -> Value {
+    let symbol_json = |index: usize, children: Vec<Value>| {
+        let symbol = &symbols.symbols[index];
+        // the protocol's SymbolKind numbers.
+        let kind = match symbol.kind {
+            SymbolKind::Class => 5,
+            SymbolKind::Method => 6,
+            SymbolKind::Function => 12,
+            SymbolKind::Variable | SymbolKind::Parameter => 13,
+        };
+        json!({
+            "name": symbol.name,
+            "detail": symbol.signature,
+            "kind": kind,
+            "range": lines.range(symbol.declaration),
+            "selectionRange": lines.range(symbol.span),
+            "children": children,
+        })
+    };
+    let top_level = symbols
+        .symbols
+        .iter()
+        .enumerate()
+        .filter(|(_, symbol)| symbol.global)
+        .map(|(index, _)| {
+            let methods = symbols
+                .symbols
+                .iter()
+                .enumerate()
+                .filter(|(_, symbol)| symbol.container == Some(index))
+                .map(|(method, _)| symbol_json(method, vec![]))
+                .collect();
+            symbol_json(index, methods)
+        });
+    Value::from(top_level.collect::<Vec<_>>())
+}
+
+// five integers per token: line and start relative to the previous token, length, type and
+// modifiers. Tokens spanning lines, such as multi-line strings, are split at line breaks.
+fn semantic_tokens(source: &str, symbols: &Symbols, lines: &LineIndex) -> Vec<u32> {
+    let mut data = vec![];
+    let mut previous = (0, 0);
+    let mut after_dot = false;
+    for token in Scanner::new(source).trivia(true).scan_ignoring_errors() {
+        let span = token.span();
+        let token_type = match &token {
+            Token::Literal {
+                token: Literal::Identifier { .. },
+                ..
+            } => Some(match symbols.at(span.start).map(|symbol| symbol.kind) {
+                Some(SymbolKind::Parameter) => "parameter",
+                Some(SymbolKind::Function) => "function",
+                Some(SymbolKind::Class) => "class",
+                Some(SymbolKind::Method) => "method",
+                _ if after_dot => "property",
+                _ => "variable",
+            }),
+            token => Highlight::of(token).map(|highlight| highlight.class()),
+        };
+        match &token {
+            Token::Trivia { .. } => {}
+            Token::Misc {
+                token: Misc::Dot, ..
+            } => after_dot = true,
+            _ => after_dot = false,
+        }
+        let token_type = match token_type {
+            Some(token_type) => TOKEN_TYPES.iter().position(|t| *t == token_type).unwrap(),
+            None => continue,
+        };
+        let mut start = span.start;
+        for piece in source[span.start..span.end].split('\n') {
+            let (line, character) = lines.position(start);
+            let length = utf16_len(piece.trim_end_matches('\r'));
+            if length > 0 {
+                let delta_line = line - previous.0;
+                let delta_start = if delta_line == 0 {
+                    character - previous.1
+                } else {
+                    character
+                };
+                data.extend(&[delta_line, delta_start, length, token_type as u32, 0]);
+                previous = (line, character);
+            }
+            start += piece.len() + 1;
+        }
+    }
+    data
+}
+
+fn utf16_len(text: &str) -> u32 {
+    text.chars().map(|c| c.len_utf16() as u32).sum()
+}
+
+/// Converts between byte offsets and the protocol's positions: zero-based lines and
+/// characters counted in UTF-16 code units.
+struct LineIndex<'s> {
+    source: &'s str,
+    // byte offset of the start of each line.
+    starts: Vec<usize>,
+}
+
+impl<'s> LineIndex<'s> {
+    fn new(source: &'s str) -> LineIndex<'s> {
+        let mut starts = vec![0];
+        starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        LineIndex { source, starts }
+    }
+
+    fn position(&self, offset: usize) -> (u32, u32) {
+        let line = match self.starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let character = utf16_len(&self.source[self.starts[line]..offset]);
+        (line as u32, character)
+    }
+
+    fn range(&self, span: Span) -> Value {
+        let position = |offset| {
+            let (line, character) = self.position(offset);
+            json!({"line": line, "character": character})
+        };
+        json!({"start": position(span.start), "end": position(span.end)})
+    }
+
+    // the byte offset of a position, clamped to the end of its line.
+    fn offset(&self, position: &Value) -> usize {
+        let line = position["line"].as_u64().unwrap_or_default() as usize;
+        let character = position["character"].as_u64().unwrap_or_default() as u32;
+        let start = match self.starts.get(line) {
+            Some(start) => *start,
+            None => return self.source.len(),
+        };
+        let mut units = 0;
+        for (i, c) in self.source[start..].char_indices() {
+            if units >= character || c == '\n' {
+                return start + i;
+            }
+            units += c.len_utf16() as u32;
+        }
+        self.source.len()
+    }
+
+    // the bytes of a 1-based line, without its line break.
+    fn line_range(&self, line: usize) -> (usize, usize) {
+        let source = self.source;
+        let start = self
+            .starts
+            .get(line.saturating_sub(1))
+            .copied()
+            .unwrap_or(source.len());
+        let end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        (start, end)
+    }
+}
@@ -1,88 +1,283 @@
-mod lox;
-mod parser;
-mod scanner;
-mod types;
+use std::io::{self, IsTerminal};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::thread;
 
-use lox::Lox;
-use parser::Expr;
-use structopt::StructOpt;
-use types::{Literal, Token};
+use rlox::lint::Config;
+use rlox::lox::{read_source, AstFormat, Backend, HighlightFormat, Lox, LoxError, TokenFormat};
+use rlox::lsp;
+use rlox::vm::gc::GcMode;
+use structopt::{clap, StructOpt};
 
-use crate::parser::Parsed;
+const LINT_CONFIG: &str = ".loxlint";
+
+// the tree-walking interpreter recurses on the native stack, some kilobytes for each Lox call
+// in debug builds. This leaves room for as many calls as the vm allows, so that deep recursion
+// fails with "Stack overflow." on both backends rather than crashing.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
 
 #[derive(StructOpt)]
+#[structopt(about = "A Lox interpreter. Without a command or path, starts a REPL.")]
 struct Cli {
-    /// The path to the file to read
+    /// Script to run; shorthand for `rlox run <path>`
     #[structopt(parse(from_os_str))]
-    path: Option<std::path::PathBuf>,
+    path: Option<PathBuf>,
+
+    /// Evaluate code given on the command line instead of a file or command
+    #[structopt(short = "e", long = "eval", conflicts_with = "path")]
+    eval: Option<String>,
+
+    /// Print each statement to stderr before executing it
+    #[structopt(long, global = true)]
+    trace: bool,
+
+    /// Disable colored output (also disabled when NO_COLOR is set or stderr isn't a terminal)
+    #[structopt(long, global = true)]
+    no_color: bool,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Run a script; `-` reads it from stdin
+    Run {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// What runs the script: the tree-walking interpreter or the bytecode vm
+        #[structopt(long, default_value = "tree", possible_values = Backend::VARIANTS)]
+        backend: Backend,
+        /// Print the vm's stack and each instruction to stderr before executing it
+        #[structopt(long)]
+        trace_execution: bool,
+        /// Collect garbage on every allocation in the vm, to test the collector
+        #[structopt(long)]
+        stress_gc: bool,
+        /// How the vm collects garbage [default: mark-sweep]
+        #[structopt(long, possible_values = GcMode::VARIANTS)]
+        gc: Option<GcMode>,
+        /// Print the vm's garbage collection statistics to stderr when the script finishes
+        #[structopt(long)]
+        gc_stats: bool,
+        /// Print the instructions the vm ran most, and the pairs of them, to stderr when the
+        /// script finishes
+        #[structopt(long)]
+        profile: bool,
+        /// Run the script as written, without simplifying it or fusing its vm instructions
+        #[structopt(long)]
+        no_optimize: bool,
+        /// Arguments for the script, available through `argc()` and `argv(i)`
+        args: Vec<String>,
+    },
+    /// Start an interactive session
+    Repl,
+    /// Print the bytecode the vm backend compiles a script to
+    Disasm {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// Compile the script as written, without simplifying it or fusing its instructions
+        #[structopt(long)]
+        no_optimize: bool,
+    },
+    /// Scan, parse and resolve a script without running it
+    Check {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Print the tokens of a script
+    Tokens {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// Output format; the json schema is documented in the `json` module
+        #[structopt(long, default_value = "text", possible_values = TokenFormat::VARIANTS)]
+        format: TokenFormat,
+        /// Include whitespace and comments
+        #[structopt(long)]
+        trivia: bool,
+    },
+    /// Print the lossless syntax tree of a script, including whitespace and comments
+    Cst {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Print a script with syntax highlighting
+    Highlight {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// Output format: ansi escapes for a terminal, or an html fragment
+        #[structopt(long, default_value = "ansi", possible_values = HighlightFormat::VARIANTS)]
+        format: HighlightFormat,
+    },
+    /// Print the syntax tree of a script
+    Ast {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// Output format: lisp, json (schema documented in the `json` module) or graphviz dot
+        #[structopt(long, default_value = "lisp", possible_values = AstFormat::VARIANTS)]
+        format: AstFormat,
+    },
+    /// Report likely mistakes in a script; exits with status 1 if any lint is an error
+    Lint {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// Lint severities, one `<lint> = allow|warning|error` per line [default: ./.loxlint]
+        #[structopt(long, parse(from_os_str))]
+        config: Option<PathBuf>,
+    },
+    /// Serve the Language Server Protocol on stdin and stdout, for editors
+    Lsp,
+    /// Print a script in canonical formatting, keeping its comments
+    Fmt {
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// Print nothing; exit with status 1 if the script isn't formatted
+        #[structopt(long)]
+        check: bool,
+    },
 }
 
 fn main() {
-    let args = Cli::from_args();
-    let mut lox = Lox::new();
+    let cli = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(cli)
+        .expect("the main thread starts");
+    if let Err(panic) = cli.join() {
+        panic::resume_unwind(panic);
+    }
+}
 
-    // playing with expression types...
-    let exprs = Parsed {
-        expressions: vec![
-            Expr::Literal(Token::Literal {
-                line: 0,
-                token: Literal::Identifier {
-                    size: 3,
-                    literal: "gri",
-                },
-            }),
-            Expr::Operator(Token::Operator {
-                line: 0,
-                token: types::Operator::Slash,
-            }),
-            Expr::Unary(
-                Token::Operator {
-                    line: 0,
-                    token: types::Operator::Minus,
-                },
-                Box::new(Expr::Literal(Token::Literal {
-                    line: 0,
-                    token: Literal::Number { literal: 133.7 },
-                })),
-            ),
-            Expr::Literal(Token::Literal {
-                line: 0,
-                token: Literal::Number { literal: 133.7 },
+fn cli() {
+    let args = match Cli::from_args_safe() {
+        Ok(args) => args,
+        Err(error) => match error.kind {
+            clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => error.exit(),
+            _ => exit(LoxError::Usage(error.message)),
+        },
+    };
+    let color =
+        !args.no_color && std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal();
+    let mut lox = Lox::new().trace(args.trace).color(color);
+
+    // clap only lets arguments conflict with each other, not with subcommands.
+    if args.eval.is_some() && args.command.is_some() {
+        exit(LoxError::Usage(String::from(
+            "--eval can't be used with a command",
+        )));
+    }
+    if let Some(code) = args.eval {
+        if let Err(error) = lox.run_source(&code, &[]) {
+            exit(error);
+        }
+        return;
+    }
+    let command = match (args.command, args.path) {
+        (Some(command), _) => command,
+        (None, Some(path)) => Command::Run {
+            path,
+            backend: Backend::Tree,
+            trace_execution: false,
+            stress_gc: false,
+            gc: None,
+            gc_stats: false,
+            profile: false,
+            no_optimize: false,
+            args: vec![],
+        },
+        (None, None) => Command::Repl,
+    };
+    let result = match command {
+        Command::Run {
+            path,
+            backend,
+            trace_execution,
+            stress_gc,
+            gc,
+            gc_stats,
+            profile,
+            no_optimize,
+            args,
+        } => {
+            let vm_flags = [
+                ("--trace-execution", trace_execution),
+                ("--stress-gc", stress_gc),
+                ("--gc", gc.is_some()),
+                ("--gc-stats", gc_stats),
+                ("--profile", profile),
+            ];
+            if let Some((flag, _)) = vm_flags.iter().find(|(_, set)| *set) {
+                if backend != Backend::Vm {
+                    exit(LoxError::Usage(format!("{} needs --backend vm", flag)));
+                }
+            }
+            let mut lox = lox
+                .backend(backend)
+                .trace_execution(trace_execution)
+                .stress_gc(stress_gc)
+                .gc(gc.unwrap_or(GcMode::MarkSweep))
+                .gc_stats(gc_stats)
+                .profile(profile)
+                .optimize(!no_optimize);
+            read_source(&path).and_then(|source| lox.run_source(&source, &args))
+        }
+        Command::Repl => {
+            lox.repl();
+            Ok(())
+        }
+        Command::Disasm { path, no_optimize } => {
+            let mut lox = lox.optimize(!no_optimize);
+            read_source(&path).and_then(|source| lox.disassemble(&source))
+        }
+        Command::Check { path } => read_source(&path).and_then(|source| lox.check(&source)),
+        Command::Tokens {
+            path,
+            format,
+            trivia,
+        } => read_source(&path).and_then(|source| lox.dump_tokens(&source, format, trivia)),
+        Command::Cst { path } => read_source(&path).and_then(|source| lox.dump_cst(&source)),
+        Command::Highlight { path, format } => {
+            read_source(&path).map(|source| lox.highlight(&source, format))
+        }
+        Command::Ast { path, format } => {
+            read_source(&path).and_then(|source| lox.dump_ast(&source, format))
+        }
+        Command::Lint { path, config } => lint_config(config)
+            .and_then(|config| read_source(&path).and_then(|source| lox.lint(&source, &config))),
+        Command::Lsp => match lsp::serve(io::stdin().lock(), io::stdout()) {
+            Ok(true) => Ok(()),
+            // the protocol asks for status 1 if the client exits without shutting down first.
+            Ok(false) => std::process::exit(1),
+            Err(error) => Err(LoxError::Io {
+                path: PathBuf::from("-"),
+                error,
             }),
-            Expr::Binary(
-                Box::new(Expr::Literal(Token::Literal {
-                    line: 0,
-                    token: Literal::Number { literal: 1.7 },
-                })),
-                Token::Operator {
-                    line: 0,
-                    token: types::Operator::Star,
-                },
-                Box::new(Expr::Grouping(Box::new(Expr::Binary(
-                    Box::new(Expr::Literal(Token::Literal {
-                        line: 0,
-                        token: Literal::Number { literal: 1.7 },
-                    })),
-                    Token::Operator {
-                        line: 0,
-                        token: types::Operator::Star,
-                    },
-                    Box::new(Expr::Grouping(Box::new(Expr::Literal(Token::Literal {
-                        line: 0,
-                        token: Literal::Identifier {
-                            size: 3,
-                            literal: "gri",
-                        },
-                    })))),
-                )))),
-            ),
-        ],
+        },
+        Command::Fmt { path, check } => {
+            read_source(&path).and_then(|source| lox.format(&source, check))
+        }
     };
+    if let Err(error) = result {
+        exit(error);
+    }
+}
 
-    println!("{}", exprs);
+// the config named on the command line, or `.loxlint` in the working directory if there is one.
+fn lint_config(path: Option<PathBuf>) -> Result<Config, LoxError> {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new(LINT_CONFIG).exists() => PathBuf::from(LINT_CONFIG),
+        None => return Ok(Config::default()),
+    };
+    let text = read_source(&path)?;
+    Config::parse(&text)
+        .map_err(|message| LoxError::Usage(format!("{}: {}", path.display(), message)))
+}
 
-    match args.path {
-        Some(path) => lox.run_file(path).expect("error while running file"),
-        None => lox.repl(),
+fn exit(error: LoxError) -> ! {
+    match error {
+        // static and runtime errors have already been reported as they were found.
+        LoxError::Usage(_) | LoxError::Io { .. } | LoxError::Unformatted => eprintln!("{}", error),
+        LoxError::Static | LoxError::Runtime(_) | LoxError::Lint => {}
     }
+    std::process::exit(error.exit_code());
 }
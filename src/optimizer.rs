@@ -0,0 +1,305 @@
+//! Simplifies a resolved program before it runs, on either backend, without changing what it
+//! does: it prints the same output and fails with the same runtime errors on the same lines.
+//!
+//! - Operators whose operands are literals are folded into a literal, when they'd succeed:
+//!   `1 + 2 * 3` becomes `7` and `"a" + "b"` becomes `"ab"`, but `1 + "b"` is left to fail at
+//!   runtime. `and` and `or` with a literal on the left become the operand they'd return.
+//! - Double negations are dropped where the value stays the same: `!!x` where `x` is already a
+//!   boolean or only its truthiness is used, as in a condition, and `-(-x)` where `x` is already
+//!   a number.
+//! - `if` statements with a literal condition become the branch that would run, and `while`
+//!   loops whose condition is falsey are removed.
+//!
+//! The pass runs after the resolver, so scoping errors in code it removes are still reported,
+//! and keeps the ids of the expressions it doesn't fold, so the resolver's `Locals` still apply.
+
+use std::borrow::Cow;
+use std::rc::Rc;
+
+use crate::parser::{
+    BinaryOp, Expr, ExprKind, Function, LiteralValue, LogicalOp, Stmt, StmtKind, UnaryOp,
+};
+
+pub fn optimize(statements: Vec<Stmt<'_>>) -> Vec<Stmt<'_>> {
+    statements.into_iter().filter_map(statement).collect()
+}
+
+// the statement simplified, or `None` if it does nothing.
+fn statement(stmt: Stmt<'_>) -> Option<Stmt<'_>> {
+    let span = stmt.span;
+    let kind = match stmt.kind {
+        StmtKind::Expression(expr) => StmtKind::Expression(expression(expr)),
+        StmtKind::Print(expr) => StmtKind::Print(expression(expr)),
+        StmtKind::Var { name, initializer } => StmtKind::Var {
+            name,
+            initializer: initializer.map(expression),
+        },
+        StmtKind::Block(statements) => StmtKind::Block(optimize(statements)),
+        StmtKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let condition = condition_expression(condition);
+            match truthiness(&condition) {
+                Some(true) => return statement(*then_branch),
+                Some(false) => return else_branch.and_then(|branch| statement(*branch)),
+                None => StmtKind::If {
+                    condition,
+                    then_branch: required(*then_branch),
+                    else_branch: else_branch
+                        .and_then(|branch| statement(*branch))
+                        .map(Box::new),
+                },
+            }
+        }
+        StmtKind::While { condition, body } => {
+            let condition = condition_expression(condition);
+            if truthiness(&condition) == Some(false) {
+                return None;
+            }
+            StmtKind::While {
+                condition,
+                body: required(*body),
+            }
+        }
+        StmtKind::For {
+            initializer,
+            condition,
+            increment,
+            body,
+        } => StmtKind::For {
+            initializer: initializer.and_then(|stmt| statement(*stmt)).map(Box::new),
+            condition: condition.map(condition_expression),
+            increment: increment.map(expression),
+            body: required(*body),
+        },
+        StmtKind::Function(function) => StmtKind::Function(Rc::new(self::function(&function))),
+        StmtKind::Return(value) => StmtKind::Return(value.map(expression)),
+        StmtKind::Class {
+            name,
+            superclass,
+            methods,
+        } => StmtKind::Class {
+            name,
+            superclass,
+            methods: methods
+                .iter()
+                .map(|method| Rc::new(function(method)))
+                .collect(),
+        },
+    };
+    Some(Stmt { kind, span })
+}
+
+// a statement that has to stay a statement, such as a loop's body: an empty block if it does
+// nothing.
+fn required(stmt: Stmt<'_>) -> Box<Stmt<'_>> {
+    let span = stmt.span;
+    Box::new(statement(stmt).unwrap_or(Stmt {
+        kind: StmtKind::Block(vec![]),
+        span,
+    }))
+}
+
+fn function<'a>(function: &Function<'a>) -> Function<'a> {
+    Function {
+        name: function.name,
+        params: function.params.clone(),
+        body: optimize(function.body.clone()),
+        span: function.span,
+    }
+}
+
+// an expression whose value is only tested for truthiness.
+fn condition_expression(expr: Expr<'_>) -> Expr<'_> {
+    let mut expr = expression(expr);
+    // `!!x` is as truthy as `x`, whatever `x` is.
+    while negated(&expr, UnaryOp::Bang).is_some_and(|inner| negated(inner, UnaryOp::Bang).is_some())
+    {
+        expr = into_negated(into_negated(expr));
+    }
+    expr
+}
+
+fn expression(expr: Expr<'_>) -> Expr<'_> {
+    let Expr { kind, span, id } = expr;
+    let kind = match kind {
+        ExprKind::Literal(_) | ExprKind::Variable(_) | ExprKind::This | ExprKind::Super(_) => kind,
+        ExprKind::Assign(name, value) => ExprKind::Assign(name, Box::new(expression(*value))),
+        ExprKind::Binary(left, operator, right) => {
+            let (left, right) = (expression(*left), expression(*right));
+            match fold_binary(&left, operator, &right) {
+                Some(value) => ExprKind::Literal(value),
+                None => ExprKind::Binary(Box::new(left), operator, Box::new(right)),
+            }
+        }
+        ExprKind::Logical(left, operator, right) => {
+            let (left, right) = (expression(*left), expression(*right));
+            match (operator, truthiness(&left)) {
+                (LogicalOp::Or, Some(true)) | (LogicalOp::And, Some(false)) => return left,
+                (_, Some(_)) => return right,
+                (_, None) => ExprKind::Logical(Box::new(left), operator, Box::new(right)),
+            }
+        }
+        ExprKind::Grouping(inner) => match expression(*inner) {
+            Expr {
+                kind: ExprKind::Literal(value),
+                ..
+            } => ExprKind::Literal(value),
+            inner => ExprKind::Grouping(Box::new(inner)),
+        },
+        ExprKind::Unary(operator, operand) => {
+            let operand = expression(*operand);
+            let folded = match &operand.kind {
+                ExprKind::Literal(value) => fold_unary(operator, value),
+                _ => None,
+            };
+            // `!!x` is `x` when `x` is a boolean, and `-(-x)` is `x` when it's a number.
+            let kept = match operator {
+                UnaryOp::Bang => Kind::Boolean,
+                UnaryOp::Minus => Kind::Number,
+            };
+            match folded {
+                Some(value) => ExprKind::Literal(value),
+                None if negated(&operand, operator).and_then(kind_of) == Some(kept) => {
+                    return into_negated(operand);
+                }
+                None => ExprKind::Unary(operator, Box::new(operand)),
+            }
+        }
+        ExprKind::Call(callee, arguments) => ExprKind::Call(
+            Box::new(expression(*callee)),
+            arguments.into_iter().map(expression).collect(),
+        ),
+        ExprKind::Get(object, name) => ExprKind::Get(Box::new(expression(*object)), name),
+        ExprKind::Set(object, name, value) => ExprKind::Set(
+            Box::new(expression(*object)),
+            name,
+            Box::new(expression(*value)),
+        ),
+    };
+    Expr { kind, span, id }
+}
+
+// the operand of `expr` if it's a negation with `operator`, in parentheses or not.
+fn negated<'e, 'a>(expr: &'e Expr<'a>, operator: UnaryOp) -> Option<&'e Expr<'a>> {
+    match &expr.kind {
+        ExprKind::Grouping(inner) => negated(inner, operator),
+        ExprKind::Unary(negation, operand) if *negation == operator => Some(operand),
+        _ => None,
+    }
+}
+
+// the operand of a negation that `negated` found.
+fn into_negated(expr: Expr<'_>) -> Expr<'_> {
+    match expr.kind {
+        ExprKind::Grouping(inner) => into_negated(*inner),
+        ExprKind::Unary(_, operand) => *operand,
+        _ => unreachable!("expected a negation"),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Boolean,
+    Number,
+}
+
+// what an expression evaluates to whenever it doesn't fail, if that's always the same kind.
+fn kind_of(expr: &Expr<'_>) -> Option<Kind> {
+    match &expr.kind {
+        ExprKind::Literal(LiteralValue::True | LiteralValue::False) => Some(Kind::Boolean),
+        ExprKind::Literal(LiteralValue::Number(_)) => Some(Kind::Number),
+        ExprKind::Grouping(inner) => kind_of(inner),
+        ExprKind::Unary(UnaryOp::Bang, _) => Some(Kind::Boolean),
+        ExprKind::Unary(UnaryOp::Minus, _) => Some(Kind::Number),
+        ExprKind::Binary(_, operator, _) => match operator {
+            BinaryOp::EqualEqual
+            | BinaryOp::BangEqual
+            | BinaryOp::Less
+            | BinaryOp::LessEqual
+            | BinaryOp::Greater
+            | BinaryOp::GreaterEqual => Some(Kind::Boolean),
+            BinaryOp::Minus | BinaryOp::Star | BinaryOp::Slash => Some(Kind::Number),
+            // numbers or strings.
+            BinaryOp::Plus => None,
+        },
+        _ => None,
+    }
+}
+
+// whether an expression is truthy, if it's a literal.
+fn truthiness(expr: &Expr<'_>) -> Option<bool> {
+    match &expr.kind {
+        ExprKind::Literal(value) => Some(is_truthy(value)),
+        _ => None,
+    }
+}
+
+fn is_truthy(value: &LiteralValue<'_>) -> bool {
+    !matches!(value, LiteralValue::Nil | LiteralValue::False)
+}
+
+fn boolean<'a>(value: bool) -> LiteralValue<'a> {
+    if value {
+        LiteralValue::True
+    } else {
+        LiteralValue::False
+    }
+}
+
+fn fold_unary<'a>(operator: UnaryOp, value: &LiteralValue<'a>) -> Option<LiteralValue<'a>> {
+    match (operator, value) {
+        (UnaryOp::Bang, value) => Some(boolean(!is_truthy(value))),
+        (UnaryOp::Minus, LiteralValue::Number(number)) => Some(LiteralValue::Number(-number)),
+        // fails at runtime.
+        (UnaryOp::Minus, _) => None,
+    }
+}
+
+// the value of a binary operator applied to literals, unless it would fail at runtime.
+fn fold_binary<'a>(
+    left: &Expr<'a>,
+    operator: BinaryOp,
+    right: &Expr<'a>,
+) -> Option<LiteralValue<'a>> {
+    let (left, right) = match (&left.kind, &right.kind) {
+        (ExprKind::Literal(left), ExprKind::Literal(right)) => (left, right),
+        _ => return None,
+    };
+    if let (LiteralValue::String(left), LiteralValue::String(right)) = (left, right) {
+        return match operator {
+            BinaryOp::Plus => Some(LiteralValue::String(Cow::Owned(format!(
+                "{}{}",
+                left, right
+            )))),
+            BinaryOp::EqualEqual => Some(boolean(left == right)),
+            BinaryOp::BangEqual => Some(boolean(left != right)),
+            _ => None,
+        };
+    }
+    let (a, b) = match (left, right) {
+        (LiteralValue::Number(a), LiteralValue::Number(b)) => (*a, *b),
+        // values of different kinds are never equal, and other operators fail.
+        _ => {
+            return match operator {
+                BinaryOp::EqualEqual => Some(boolean(left == right)),
+                BinaryOp::BangEqual => Some(boolean(left != right)),
+                _ => None,
+            };
+        }
+    };
+    Some(match operator {
+        BinaryOp::EqualEqual => boolean(a == b),
+        BinaryOp::BangEqual => boolean(a != b),
+        BinaryOp::Less => boolean(a < b),
+        BinaryOp::LessEqual => boolean(a <= b),
+        BinaryOp::Greater => boolean(a > b),
+        BinaryOp::GreaterEqual => boolean(a >= b),
+        BinaryOp::Plus => LiteralValue::Number(a + b),
+        BinaryOp::Minus => LiteralValue::Number(a - b),
+        BinaryOp::Star => LiteralValue::Number(a * b),
+        BinaryOp::Slash => LiteralValue::Number(a / b),
+    })
+}
@@ -1,44 +1,1006 @@
+use std::borrow::Cow;
 use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::types::Token;
+use crate::lox::Lox;
+use crate::printer::AstPrinter;
+use crate::types::{Grouping, Keyword, Literal, Misc, Operator, Span, Token};
 
-// simple grammar to start with:
-// expr -> literal | binary | grouping | unary
-// literal -> string | number | identifier
-// binary -> expr operator expr
-// grouping -> "(" expr ")"
-// unary -> "-" | "!" expr
-// operator -> "==" | "!=" | "<" | "<=" | ">" | ">=" | "+" | "-" | "*" | "/"
+// program grammar:
+// program -> declaration* EOF
+// declaration -> class_decl | fun_decl | var_decl | statement
+// class_decl -> "class" identifier ( "<" identifier )? "{" function* "}"
+// fun_decl -> "fun" function
+// function -> identifier "(" parameters? ")" block
+// parameters -> identifier ( "," identifier )*
+// var_decl -> "var" identifier ( "=" expression )? ";"
+// statement -> expr_stmt | for_stmt | if_stmt | print_stmt | return_stmt | while_stmt | block
+// expr_stmt -> expression ";"
+// for_stmt -> "for" "(" ( var_decl | expr_stmt | ";" ) expression? ";" expression? ")" statement
+// if_stmt -> "if" "(" expression ")" statement ( "else" statement )?
+// print_stmt -> "print" expression ";"
+// return_stmt -> "return" expression? ";"
+// while_stmt -> "while" "(" expression ")" statement
+// block -> "{" declaration* "}"
+//
+// expression grammar, lowest to highest precedence:
+// expression -> assignment
+// assignment -> ( call "." )? identifier "=" assignment | logic_or
+// logic_or -> logic_and ( "or" logic_and )*
+// logic_and -> equality ( "and" equality )*
+// equality -> comparison ( ( "!=" | "==" ) comparison )*
+// comparison -> term ( ( ">" | ">=" | "<" | "<=" ) term )*
+// term -> factor ( ( "-" | "+" ) factor )*
+// factor -> unary ( ( "/" | "*" ) unary )*
+// unary -> ( "!" | "-" ) unary | call
+// call -> primary ( "(" arguments? ")" | "." identifier )*
+// arguments -> expression ( "," expression )*
+// primary -> "nil" | "true" | "false" | number | string | identifier | "this"
+//          | "(" expression ")" | "super" "." identifier
 
-pub enum Expr<'a> {
-    Literal(Token<'a>),
-    Binary(Box<Expr<'a>>, Token<'a>, Box<Expr<'a>>),
+const MAX_ARGUMENTS: usize = 255;
+
+// Spans and ids are metadata: trees compare equal when they have the same shape,
+// regardless of where in the source they came from.
+
+#[derive(Clone, Debug)]
+pub struct Expr<'a> {
+    pub kind: ExprKind<'a>,
+    pub span: Span,
+    pub id: ExprId,
+}
+
+/// Identifies an expression node, so that later passes can attach information to it in side
+/// tables (e.g. the resolver's scope distances). Ids are unique within the process, which keeps
+/// them unique across separately parsed sources such as REPL lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    pub fn next() -> ExprId {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        ExprId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind<'a> {
+    Literal(LiteralValue<'a>),
+    Variable(&'a str),
+    Assign(Name<'a>, Box<Expr<'a>>),
+    Binary(Box<Expr<'a>>, BinaryOp, Box<Expr<'a>>),
+    Logical(Box<Expr<'a>>, LogicalOp, Box<Expr<'a>>),
     Grouping(Box<Expr<'a>>),
-    Unary(Token<'a>, Box<Expr<'a>>),
-    Operator(Token<'a>),
+    Unary(UnaryOp, Box<Expr<'a>>),
+    Call(Box<Expr<'a>>, Vec<Expr<'a>>),
+    Get(Box<Expr<'a>>, Name<'a>),
+    Set(Box<Expr<'a>>, Name<'a>, Box<Expr<'a>>),
+    This,
+    Super(Name<'a>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LiteralValue<'a> {
+    Nil,
+    True,
+    False,
+    Number(f64),
+    /// Borrowed from the source, unless the optimizer made it by concatenating literals.
+    String(Cow<'a, str>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogicalOp {
+    And,
+    Or,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    Minus,
+    Bang,
+}
+
+/// An identifier in a declaration or property access, with the span of just the identifier.
+#[derive(Clone, Copy, Debug)]
+pub struct Name<'a> {
+    pub lexeme: &'a str,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub struct Stmt<'a> {
+    pub kind: StmtKind<'a>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StmtKind<'a> {
+    Expression(Expr<'a>),
+    Print(Expr<'a>),
+    Var {
+        name: Name<'a>,
+        initializer: Option<Expr<'a>>,
+    },
+    Block(Vec<Stmt<'a>>),
+    If {
+        condition: Expr<'a>,
+        then_branch: Box<Stmt<'a>>,
+        else_branch: Option<Box<Stmt<'a>>>,
+    },
+    While {
+        condition: Expr<'a>,
+        body: Box<Stmt<'a>>,
+    },
+    For {
+        initializer: Option<Box<Stmt<'a>>>,
+        condition: Option<Expr<'a>>,
+        increment: Option<Expr<'a>>,
+        body: Box<Stmt<'a>>,
+    },
+    Function(Rc<Function<'a>>),
+    Return(Option<Expr<'a>>),
+    Class {
+        name: Name<'a>,
+        superclass: Option<Expr<'a>>,
+        methods: Vec<Rc<Function<'a>>>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Function<'a> {
+    pub name: Name<'a>,
+    pub params: Vec<Name<'a>>,
+    pub body: Vec<Stmt<'a>>,
+    /// From the name through the closing brace of the body.
+    pub span: Span,
+}
+
+/// Binding strength of an expression, used by the parser's grammar and by
+/// `AstPrinter` to decide where parentheses are required.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Precedence {
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
 }
 
-pub struct Parsed<'a> {
-    pub expressions: Vec<Expr<'a>>,
+impl<'a> PartialEq for Expr<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
 }
 
-impl<'a> fmt::Display for Parsed<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for e in &self.expressions {
-            write!(f, " ({}) ", e)?;
+impl<'a> PartialEq for Stmt<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl<'a> PartialEq for Function<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.params == other.params && self.body == other.body
+    }
+}
+
+impl<'a> PartialEq for Name<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.lexeme == other.lexeme
+    }
+}
+
+impl<'a> From<ExprKind<'a>> for Expr<'a> {
+    /// Builds an expression that doesn't originate from source text.
+    fn from(kind: ExprKind<'a>) -> Expr<'a> {
+        Expr {
+            id: ExprId::next(),
+            kind,
+            span: Span::default(),
+        }
+    }
+}
+
+impl BinaryOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BinaryOp::EqualEqual => "==",
+            BinaryOp::BangEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Star => "*",
+            BinaryOp::Slash => "/",
+        }
+    }
+
+    pub fn from_operator(operator: &Operator) -> Option<BinaryOp> {
+        match operator {
+            Operator::EqualEqual => Some(BinaryOp::EqualEqual),
+            Operator::BangEqual => Some(BinaryOp::BangEqual),
+            Operator::Less => Some(BinaryOp::Less),
+            Operator::LessEqual => Some(BinaryOp::LessEqual),
+            Operator::Greater => Some(BinaryOp::Greater),
+            Operator::GreaterEqual => Some(BinaryOp::GreaterEqual),
+            Operator::Plus => Some(BinaryOp::Plus),
+            Operator::Minus => Some(BinaryOp::Minus),
+            Operator::Star => Some(BinaryOp::Star),
+            Operator::Slash => Some(BinaryOp::Slash),
+            Operator::Equal | Operator::Bang => None,
+        }
+    }
+
+    pub fn precedence(&self) -> Precedence {
+        match self {
+            BinaryOp::EqualEqual | BinaryOp::BangEqual => Precedence::Equality,
+            BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => {
+                Precedence::Comparison
+            }
+            BinaryOp::Plus | BinaryOp::Minus => Precedence::Term,
+            BinaryOp::Star | BinaryOp::Slash => Precedence::Factor,
+        }
+    }
+}
+
+impl LogicalOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogicalOp::And => "and",
+            LogicalOp::Or => "or",
+        }
+    }
+
+    pub fn precedence(&self) -> Precedence {
+        match self {
+            LogicalOp::And => Precedence::And,
+            LogicalOp::Or => Precedence::Or,
+        }
+    }
+}
+
+impl<'a> Expr<'a> {
+    pub fn precedence(&self) -> Precedence {
+        match &self.kind {
+            ExprKind::Assign(..) | ExprKind::Set(..) => Precedence::Assignment,
+            ExprKind::Logical(_, operator, _) => operator.precedence(),
+            ExprKind::Binary(_, operator, _) => operator.precedence(),
+            ExprKind::Unary(..) => Precedence::Unary,
+            ExprKind::Call(..) | ExprKind::Get(..) => Precedence::Call,
+            ExprKind::Literal(_)
+            | ExprKind::Variable(_)
+            | ExprKind::Grouping(_)
+            | ExprKind::This
+            | ExprKind::Super(_) => Precedence::Primary,
+        }
+    }
+}
+
+impl UnaryOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnaryOp::Minus => "-",
+            UnaryOp::Bang => "!",
+        }
+    }
+
+    pub fn from_operator(operator: &Operator) -> Option<UnaryOp> {
+        match operator {
+            Operator::Minus => Some(UnaryOp::Minus),
+            Operator::Bang => Some(UnaryOp::Bang),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> LiteralValue<'a> {
+    // identifiers are not literal values; they become ExprKind::Variable instead.
+    pub fn from_token(token: &Token<'a>) -> Option<LiteralValue<'a>> {
+        match token {
+            Token::Keyword { token, .. } => match token {
+                Keyword::Nil => Some(LiteralValue::Nil),
+                Keyword::True => Some(LiteralValue::True),
+                Keyword::False => Some(LiteralValue::False),
+                _ => None,
+            },
+            Token::Literal { token, .. } => match token {
+                Literal::Number { literal } => Some(LiteralValue::Number(*literal)),
+                Literal::String { literal, .. } => {
+                    Some(LiteralValue::String(Cow::Borrowed(literal)))
+                }
+                Literal::Identifier { .. } => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError;
+
+pub struct Parser<'t, 'a> {
+    tokens: &'t [Token<'a>],
+    current: usize,
+}
+
+impl<'t, 'a> Parser<'t, 'a> {
+    pub fn new(tokens: &'t [Token<'a>]) -> Parser<'t, 'a> {
+        Parser { tokens, current: 0 }
+    }
+
+    /// Parses a whole program. Errors are reported to `lox`; the parser then skips ahead to
+    /// the next statement, so statements that failed to parse are left out of the result.
+    pub fn parse(&mut self, lox: &mut Lox) -> Vec<Stmt<'a>> {
+        let mut statements = vec![];
+        while !self.at_end() {
+            if let Some(statement) = self.declaration(lox) {
+                statements.push(statement);
+            }
+        }
+        statements
+    }
+
+    /// Parses a single expression spanning all of the tokens. Errors are reported to `lox`.
+    pub fn parse_expression(&mut self, lox: &mut Lox) -> Option<Expr<'a>> {
+        let expr = self.expression(lox).ok()?;
+        if !self.at_end() {
+            lox.error_at(self.peek(), "Expect end of expression.");
+            return None;
+        }
+        Some(expr)
+    }
+
+    fn declaration(&mut self, lox: &mut Lox) -> Option<Stmt<'a>> {
+        let result = if self.match_keyword(Keyword::Class).is_some() {
+            self.class_declaration(lox)
+        } else if let Some(start) = self.match_keyword(Keyword::Fun) {
+            self.function(lox, "function").map(|function| Stmt {
+                span: start.to(function.span),
+                kind: StmtKind::Function(Rc::new(function)),
+            })
+        } else if self.match_keyword(Keyword::Var).is_some() {
+            self.var_declaration(lox)
+        } else {
+            self.statement(lox)
+        };
+        match result {
+            Ok(statement) => Some(statement),
+            Err(ParseError) => {
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn class_declaration(&mut self, lox: &mut Lox) -> Result<Stmt<'a>, ParseError> {
+        let start = self.previous_span();
+        let name = self.consume_identifier(lox, "Expect class name.")?;
+        let superclass = match self.match_operator(Operator::Less) {
+            Some(_) => {
+                let superclass = self.consume_identifier(lox, "Expect superclass name.")?;
+                Some(Expr {
+                    id: ExprId::next(),
+                    kind: ExprKind::Variable(superclass.lexeme),
+                    span: superclass.span,
+                })
+            }
+            None => None,
+        };
+        self.consume_grouping(lox, Grouping::LeftBrace, "Expect '{' before class body.")?;
+        let mut methods = vec![];
+        while !self.check_grouping(Grouping::RightBrace) && !self.at_end() {
+            methods.push(Rc::new(self.function(lox, "method")?));
+        }
+        let end =
+            self.consume_grouping(lox, Grouping::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt {
+            kind: StmtKind::Class {
+                name,
+                superclass,
+                methods,
+            },
+            span: start.to(end),
+        })
+    }
+
+    fn function(&mut self, lox: &mut Lox, kind: &str) -> Result<Function<'a>, ParseError> {
+        let name = self.consume_identifier(lox, &format!("Expect {} name.", kind))?;
+        self.consume_grouping(
+            lox,
+            Grouping::LeftParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+        let mut params = vec![];
+        if !self.check_grouping(Grouping::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    lox.error_at(self.peek(), "Can't have more than 255 parameters.");
+                }
+                params.push(self.consume_identifier(lox, "Expect parameter name.")?);
+                if self.match_misc(Misc::Comma).is_none() {
+                    break;
+                }
+            }
+        }
+        self.consume_grouping(lox, Grouping::RightParen, "Expect ')' after parameters.")?;
+        self.consume_grouping(
+            lox,
+            Grouping::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let (body, end) = self.block(lox)?;
+        Ok(Function {
+            span: name.span.to(end),
+            name,
+            params,
+            body,
+        })
+    }
+
+    fn var_declaration(&mut self, lox: &mut Lox) -> Result<Stmt<'a>, ParseError> {
+        let start = self.previous_span();
+        let name = self.consume_identifier(lox, "Expect variable name.")?;
+        let initializer = match self.match_operator(Operator::Equal) {
+            Some(_) => Some(self.expression(lox)?),
+            None => None,
+        };
+        let end = self.consume_misc(
+            lox,
+            Misc::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(Stmt {
+            kind: StmtKind::Var { name, initializer },
+            span: start.to(end),
+        })
+    }
+
+    fn statement(&mut self, lox: &mut Lox) -> Result<Stmt<'a>, ParseError> {
+        if let Some(start) = self.match_keyword(Keyword::For) {
+            return self.for_statement(lox, start);
+        }
+        if let Some(start) = self.match_keyword(Keyword::If) {
+            return self.if_statement(lox, start);
+        }
+        if let Some(start) = self.match_keyword(Keyword::Print) {
+            let value = self.expression(lox)?;
+            let end = self.consume_misc(lox, Misc::Semicolon, "Expect ';' after value.")?;
+            return Ok(Stmt {
+                kind: StmtKind::Print(value),
+                span: start.to(end),
+            });
+        }
+        if let Some(start) = self.match_keyword(Keyword::Return) {
+            let value = if self.check_misc(Misc::Semicolon) {
+                None
+            } else {
+                Some(self.expression(lox)?)
+            };
+            let end = self.consume_misc(lox, Misc::Semicolon, "Expect ';' after return value.")?;
+            return Ok(Stmt {
+                kind: StmtKind::Return(value),
+                span: start.to(end),
+            });
+        }
+        if let Some(start) = self.match_keyword(Keyword::While) {
+            self.consume_grouping(lox, Grouping::LeftParen, "Expect '(' after 'while'.")?;
+            let condition = self.expression(lox)?;
+            self.consume_grouping(lox, Grouping::RightParen, "Expect ')' after condition.")?;
+            let body = self.statement(lox)?;
+            return Ok(Stmt {
+                span: start.to(body.span),
+                kind: StmtKind::While {
+                    condition,
+                    body: Box::new(body),
+                },
+            });
+        }
+        if let Some(start) = self.match_grouping(Grouping::LeftBrace) {
+            let (statements, end) = self.block(lox)?;
+            return Ok(Stmt {
+                kind: StmtKind::Block(statements),
+                span: start.to(end),
+            });
+        }
+        let expr = self.expression(lox)?;
+        let end = self.consume_misc(lox, Misc::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt {
+            span: expr.span.to(end),
+            kind: StmtKind::Expression(expr),
+        })
+    }
+
+    fn for_statement(&mut self, lox: &mut Lox, start: Span) -> Result<Stmt<'a>, ParseError> {
+        self.consume_grouping(lox, Grouping::LeftParen, "Expect '(' after 'for'.")?;
+        let initializer = if self.match_misc(Misc::Semicolon).is_some() {
+            None
+        } else if self.match_keyword(Keyword::Var).is_some() {
+            Some(Box::new(self.var_declaration(lox)?))
+        } else {
+            let expr = self.expression(lox)?;
+            let end = self.consume_misc(lox, Misc::Semicolon, "Expect ';' after expression.")?;
+            Some(Box::new(Stmt {
+                span: expr.span.to(end),
+                kind: StmtKind::Expression(expr),
+            }))
+        };
+        let condition = if self.check_misc(Misc::Semicolon) {
+            None
+        } else {
+            Some(self.expression(lox)?)
+        };
+        self.consume_misc(lox, Misc::Semicolon, "Expect ';' after loop condition.")?;
+        let increment = if self.check_grouping(Grouping::RightParen) {
+            None
+        } else {
+            Some(self.expression(lox)?)
+        };
+        self.consume_grouping(lox, Grouping::RightParen, "Expect ')' after for clauses.")?;
+        let body = self.statement(lox)?;
+        Ok(Stmt {
+            span: start.to(body.span),
+            kind: StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body: Box::new(body),
+            },
+        })
+    }
+
+    fn if_statement(&mut self, lox: &mut Lox, start: Span) -> Result<Stmt<'a>, ParseError> {
+        self.consume_grouping(lox, Grouping::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression(lox)?;
+        self.consume_grouping(lox, Grouping::RightParen, "Expect ')' after if condition.")?;
+        let then_branch = Box::new(self.statement(lox)?);
+        let else_branch = match self.match_keyword(Keyword::Else) {
+            Some(_) => Some(Box::new(self.statement(lox)?)),
+            None => None,
+        };
+        let end = else_branch
+            .as_ref()
+            .map_or(then_branch.span, |branch| branch.span);
+        Ok(Stmt {
+            kind: StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            },
+            span: start.to(end),
+        })
+    }
+
+    // parses the declarations following an already consumed '{', up to and including the '}'.
+    fn block(&mut self, lox: &mut Lox) -> Result<(Vec<Stmt<'a>>, Span), ParseError> {
+        let mut statements = vec![];
+        while !self.check_grouping(Grouping::RightBrace) && !self.at_end() {
+            if let Some(statement) = self.declaration(lox) {
+                statements.push(statement);
+            }
+        }
+        let end = self.consume_grouping(lox, Grouping::RightBrace, "Expect '}' after block.")?;
+        Ok((statements, end))
+    }
+
+    fn expression(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        self.assignment(lox)
+    }
+
+    fn assignment(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        let expr = self.logic_or(lox)?;
+        if let Token::Operator {
+            token: Operator::Equal,
+            ..
+        } = self.peek()
+        {
+            let equals = self.peek();
+            self.advance();
+            let value = self.assignment(lox)?;
+            let span = expr.span.to(value.span);
+            return match expr.kind {
+                ExprKind::Variable(lexeme) => Ok(Expr {
+                    id: ExprId::next(),
+                    kind: ExprKind::Assign(
+                        Name {
+                            lexeme,
+                            span: expr.span,
+                        },
+                        Box::new(value),
+                    ),
+                    span,
+                }),
+                ExprKind::Get(object, name) => Ok(Expr {
+                    id: ExprId::next(),
+                    kind: ExprKind::Set(object, name, Box::new(value)),
+                    span,
+                }),
+                _ => {
+                    // report, but there is no need to synchronize: the parser isn't confused.
+                    lox.error_at(equals, "Invalid assignment target.");
+                    Ok(expr)
+                }
+            };
+        }
+        Ok(expr)
+    }
+
+    fn logic_or(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        let mut expr = self.logic_and(lox)?;
+        while self.match_keyword(Keyword::Or).is_some() {
+            let right = self.logic_and(lox)?;
+            expr = Expr {
+                id: ExprId::next(),
+                span: expr.span.to(right.span),
+                kind: ExprKind::Logical(Box::new(expr), LogicalOp::Or, Box::new(right)),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn logic_and(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        let mut expr = self.equality(lox)?;
+        while self.match_keyword(Keyword::And).is_some() {
+            let right = self.equality(lox)?;
+            expr = Expr {
+                id: ExprId::next(),
+                span: expr.span.to(right.span),
+                kind: ExprKind::Logical(Box::new(expr), LogicalOp::And, Box::new(right)),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        self.binary(lox, Precedence::Equality, Parser::comparison)
+    }
+
+    fn comparison(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        self.binary(lox, Precedence::Comparison, Parser::term)
+    }
+
+    fn term(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        self.binary(lox, Precedence::Term, Parser::factor)
+    }
+
+    fn factor(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        self.binary(lox, Precedence::Factor, Parser::unary)
+    }
+
+    // all binary levels are left-associative and differ only in their operators and operand rule.
+    fn binary(
+        &mut self,
+        lox: &mut Lox,
+        precedence: Precedence,
+        operand: fn(&mut Self, &mut Lox) -> Result<Expr<'a>, ParseError>,
+    ) -> Result<Expr<'a>, ParseError> {
+        let mut expr = operand(self, lox)?;
+        while let Some(operator) = self.binary_operator(precedence) {
+            self.advance();
+            let right = operand(self, lox)?;
+            expr = Expr {
+                id: ExprId::next(),
+                span: expr.span.to(right.span),
+                kind: ExprKind::Binary(Box::new(expr), operator, Box::new(right)),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        if let Token::Operator { token, span } = self.peek() {
+            if let Some(operator) = UnaryOp::from_operator(token) {
+                self.advance();
+                let right = self.unary(lox)?;
+                return Ok(Expr {
+                    id: ExprId::next(),
+                    span: span.to(right.span),
+                    kind: ExprKind::Unary(operator, Box::new(right)),
+                });
+            }
+        }
+        self.call(lox)
+    }
+
+    fn call(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        let mut expr = self.primary(lox)?;
+        loop {
+            if self.match_grouping(Grouping::LeftParen).is_some() {
+                let mut arguments = vec![];
+                if !self.check_grouping(Grouping::RightParen) {
+                    loop {
+                        if arguments.len() >= MAX_ARGUMENTS {
+                            lox.error_at(self.peek(), "Can't have more than 255 arguments.");
+                        }
+                        arguments.push(self.expression(lox)?);
+                        if self.match_misc(Misc::Comma).is_none() {
+                            break;
+                        }
+                    }
+                }
+                let end = self.consume_grouping(
+                    lox,
+                    Grouping::RightParen,
+                    "Expect ')' after arguments.",
+                )?;
+                expr = Expr {
+                    id: ExprId::next(),
+                    span: expr.span.to(end),
+                    kind: ExprKind::Call(Box::new(expr), arguments),
+                };
+            } else if self.match_misc(Misc::Dot).is_some() {
+                let name = self.consume_identifier(lox, "Expect property name after '.'.")?;
+                expr = Expr {
+                    id: ExprId::next(),
+                    span: expr.span.to(name.span),
+                    kind: ExprKind::Get(Box::new(expr), name),
+                };
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self, lox: &mut Lox) -> Result<Expr<'a>, ParseError> {
+        let token = self.peek();
+        let span = token.span();
+        if let Some(value) = LiteralValue::from_token(token) {
+            self.advance();
+            return Ok(Expr {
+                id: ExprId::next(),
+                kind: ExprKind::Literal(value),
+                span,
+            });
+        }
+        match token {
+            Token::Literal {
+                token: Literal::Identifier { literal, .. },
+                ..
+            } => {
+                self.advance();
+                Ok(Expr {
+                    id: ExprId::next(),
+                    kind: ExprKind::Variable(literal),
+                    span,
+                })
+            }
+            Token::Keyword {
+                token: Keyword::This,
+                ..
+            } => {
+                self.advance();
+                Ok(Expr {
+                    id: ExprId::next(),
+                    kind: ExprKind::This,
+                    span,
+                })
+            }
+            Token::Keyword {
+                token: Keyword::Super,
+                ..
+            } => {
+                self.advance();
+                self.consume_misc(lox, Misc::Dot, "Expect '.' after 'super'.")?;
+                let method = self.consume_identifier(lox, "Expect superclass method name.")?;
+                Ok(Expr {
+                    id: ExprId::next(),
+                    kind: ExprKind::Super(method),
+                    span: span.to(method.span),
+                })
+            }
+            Token::Grouping {
+                token: Grouping::LeftParen,
+                ..
+            } => {
+                self.advance();
+                let expr = self.expression(lox)?;
+                let end = self.consume_grouping(
+                    lox,
+                    Grouping::RightParen,
+                    "Expect ')' after expression.",
+                )?;
+                Ok(Expr {
+                    id: ExprId::next(),
+                    kind: ExprKind::Grouping(Box::new(expr)),
+                    span: span.to(end),
+                })
+            }
+            token => {
+                lox.error_at(token, "Expect expression.");
+                Err(ParseError)
+            }
+        }
+    }
+
+    // skips tokens until a likely statement boundary, so one mistake produces one error.
+    fn synchronize(&mut self) {
+        self.advance();
+        while !self.at_end() {
+            if let Token::Misc {
+                token: Misc::Semicolon,
+                ..
+            } = self.tokens[self.current - 1]
+            {
+                return;
+            }
+            if let Token::Keyword {
+                token:
+                    Keyword::Class
+                    | Keyword::Fun
+                    | Keyword::Var
+                    | Keyword::For
+                    | Keyword::If
+                    | Keyword::While
+                    | Keyword::Print
+                    | Keyword::Return,
+                ..
+            } = self.peek()
+            {
+                return;
+            }
+            self.advance();
+        }
+    }
+
+    fn binary_operator(&self, precedence: Precedence) -> Option<BinaryOp> {
+        match self.peek() {
+            Token::Operator { token, .. } => BinaryOp::from_operator(token)
+                .filter(|operator| operator.precedence() == precedence),
+            _ => None,
+        }
+    }
+
+    fn check_grouping(&self, grouping: Grouping) -> bool {
+        matches!(self.peek(), Token::Grouping { token, .. } if *token == grouping)
+    }
+
+    fn check_misc(&self, misc: Misc) -> bool {
+        matches!(self.peek(), Token::Misc { token, .. } if *token == misc)
+    }
+
+    fn match_grouping(&mut self, grouping: Grouping) -> Option<Span> {
+        self.advance_if(self.check_grouping(grouping))
+    }
+
+    fn match_misc(&mut self, misc: Misc) -> Option<Span> {
+        self.advance_if(self.check_misc(misc))
+    }
+
+    fn match_keyword(&mut self, keyword: Keyword) -> Option<Span> {
+        let matched = matches!(self.peek(), Token::Keyword { token, .. } if *token == keyword);
+        self.advance_if(matched)
+    }
+
+    fn match_operator(&mut self, operator: Operator) -> Option<Span> {
+        let matched = matches!(self.peek(), Token::Operator { token, .. } if *token == operator);
+        self.advance_if(matched)
+    }
+
+    fn consume_grouping(
+        &mut self,
+        lox: &mut Lox,
+        grouping: Grouping,
+        message: &str,
+    ) -> Result<Span, ParseError> {
+        self.match_grouping(grouping)
+            .ok_or_else(|| self.error(lox, message))
+    }
+
+    fn consume_misc(
+        &mut self,
+        lox: &mut Lox,
+        misc: Misc,
+        message: &str,
+    ) -> Result<Span, ParseError> {
+        self.match_misc(misc)
+            .ok_or_else(|| self.error(lox, message))
+    }
+
+    fn consume_identifier(&mut self, lox: &mut Lox, message: &str) -> Result<Name<'a>, ParseError> {
+        match self.peek() {
+            Token::Literal {
+                token: Literal::Identifier { literal, .. },
+                span,
+            } => {
+                self.advance();
+                Ok(Name {
+                    lexeme: literal,
+                    span: *span,
+                })
+            }
+            _ => Err(self.error(lox, message)),
+        }
+    }
+
+    fn error(&self, lox: &mut Lox, message: &str) -> ParseError {
+        lox.error_at(self.peek(), message);
+        ParseError
+    }
+
+    fn advance_if(&mut self, matched: bool) -> Option<Span> {
+        if matched {
+            let span = self.peek().span();
+            self.advance();
+            Some(span)
+        } else {
+            None
+        }
+    }
+
+    fn previous_span(&self) -> Span {
+        self.tokens[self.current.saturating_sub(1)].span()
+    }
+
+    fn peek(&self) -> &'t Token<'a> {
+        &self.tokens[self.current]
+    }
+
+    fn at_end(&self) -> bool {
+        matches!(self.peek(), Token::Eof { .. })
+    }
+
+    fn advance(&mut self) {
+        if !self.at_end() {
+            self.current += 1;
         }
-        Ok(())
     }
 }
 
 impl<'a> fmt::Display for Expr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", AstPrinter::source(self))
+    }
+}
+
+impl<'a> fmt::Display for LiteralValue<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Expr::Literal(token) => write!(f, "{}", token),
-            Expr::Binary(left, token, right) => write!(f, "{} {} {}", left, token, right),
-            Expr::Grouping(expr) => write!(f, "group({})", expr),
-            Expr::Unary(operator, expr) => write!(f, "{} {}", operator, expr),
-            Expr::Operator(operator) => write!(f, "{}", operator),
+            LiteralValue::Nil => write!(f, "nil"),
+            LiteralValue::True => write!(f, "true"),
+            LiteralValue::False => write!(f, "false"),
+            LiteralValue::Number(number) => write!(f, "{}", number),
+            LiteralValue::String(string) => write!(f, "\"{}\"", string),
         }
     }
 }
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Display for LogicalOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
@@ -0,0 +1,453 @@
+use crate::parser::{Expr, ExprKind, Function, Precedence, Stmt, StmtKind};
+
+const INDENT: &str = "  ";
+
+/// Renders syntax trees back to text.
+///
+/// `Lisp` is the fully parenthesized prefix form from the book, e.g. `(* 1 (group (+ 2 3)))`,
+/// which makes the tree structure explicit. `Source` produces valid Lox, inserting only the
+/// parentheses needed to preserve the tree's shape, so that re-parsing the output yields the
+/// same tree.
+#[derive(Clone, Copy, Debug)]
+pub enum AstPrinter {
+    Lisp,
+    Source,
+}
+
+impl AstPrinter {
+    pub fn lisp(expr: &Expr) -> String {
+        AstPrinter::Lisp.print(expr)
+    }
+
+    pub fn source(expr: &Expr) -> String {
+        AstPrinter::Source.print(expr)
+    }
+
+    pub fn print(&self, expr: &Expr) -> String {
+        let mut printer = Printer::new();
+        match self {
+            AstPrinter::Lisp => printer.lisp(expr),
+            AstPrinter::Source => printer.source(expr),
+        }
+        printer.out
+    }
+
+    /// Prints each statement of a program on its own line(s).
+    pub fn print_program(&self, statements: &[Stmt]) -> String {
+        let mut printer = Printer::new();
+        for statement in statements {
+            match self {
+                AstPrinter::Lisp => printer.lisp_stmt(statement),
+                AstPrinter::Source => printer.source_stmt(statement),
+            }
+            printer.out.push('\n');
+        }
+        printer.out
+    }
+}
+
+struct Printer {
+    out: String,
+    indent: usize,
+}
+
+impl Printer {
+    fn new() -> Printer {
+        Printer {
+            out: String::new(),
+            indent: 0,
+        }
+    }
+
+    fn push(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn lisp_list<T>(&mut self, items: &[T], print: fn(&mut Printer, &T)) {
+        for item in items {
+            self.push(" ");
+            print(self, item);
+        }
+    }
+
+    fn lisp(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Literal(value) => self.push(&value.to_string()),
+            ExprKind::Variable(name) => self.push(name),
+            ExprKind::Assign(name, value) => {
+                self.push(&format!("(= {} ", name.lexeme));
+                self.lisp(value);
+                self.push(")");
+            }
+            ExprKind::Binary(left, operator, right) => {
+                self.push(&format!("({} ", operator));
+                self.lisp(left);
+                self.push(" ");
+                self.lisp(right);
+                self.push(")");
+            }
+            ExprKind::Logical(left, operator, right) => {
+                self.push(&format!("({} ", operator));
+                self.lisp(left);
+                self.push(" ");
+                self.lisp(right);
+                self.push(")");
+            }
+            ExprKind::Grouping(expr) => {
+                self.push("(group ");
+                self.lisp(expr);
+                self.push(")");
+            }
+            ExprKind::Unary(operator, expr) => {
+                self.push(&format!("({} ", operator));
+                self.lisp(expr);
+                self.push(")");
+            }
+            ExprKind::Call(callee, arguments) => {
+                self.push("(call ");
+                self.lisp(callee);
+                self.lisp_list(arguments, Printer::lisp);
+                self.push(")");
+            }
+            ExprKind::Get(object, name) => {
+                self.push("(. ");
+                self.lisp(object);
+                self.push(&format!(" {})", name.lexeme));
+            }
+            ExprKind::Set(object, name, value) => {
+                self.push("(= ");
+                self.lisp(object);
+                self.push(&format!(" {} ", name.lexeme));
+                self.lisp(value);
+                self.push(")");
+            }
+            ExprKind::This => self.push("this"),
+            ExprKind::Super(method) => self.push(&format!("(super {})", method.lexeme)),
+        }
+    }
+
+    fn lisp_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Expression(expr) => {
+                self.push("(; ");
+                self.lisp(expr);
+                self.push(")");
+            }
+            StmtKind::Print(expr) => {
+                self.push("(print ");
+                self.lisp(expr);
+                self.push(")");
+            }
+            StmtKind::Var { name, initializer } => {
+                self.push(&format!("(var {}", name.lexeme));
+                if let Some(initializer) = initializer {
+                    self.push(" = ");
+                    self.lisp(initializer);
+                }
+                self.push(")");
+            }
+            StmtKind::Block(statements) => {
+                self.push("(block");
+                self.lisp_list(statements, Printer::lisp_stmt);
+                self.push(")");
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                match else_branch {
+                    Some(_) => self.push("(if-else "),
+                    None => self.push("(if "),
+                }
+                self.lisp(condition);
+                self.push(" ");
+                self.lisp_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.push(" ");
+                    self.lisp_stmt(else_branch);
+                }
+                self.push(")");
+            }
+            StmtKind::While { condition, body } => {
+                self.push("(while ");
+                self.lisp(condition);
+                self.push(" ");
+                self.lisp_stmt(body);
+                self.push(")");
+            }
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                // absent clauses print as "()" to keep the positions unambiguous.
+                self.push("(for ");
+                match initializer {
+                    Some(initializer) => self.lisp_stmt(initializer),
+                    None => self.push("()"),
+                }
+                for clause in &[condition, increment] {
+                    self.push(" ");
+                    match clause {
+                        Some(clause) => self.lisp(clause),
+                        None => self.push("()"),
+                    }
+                }
+                self.push(" ");
+                self.lisp_stmt(body);
+                self.push(")");
+            }
+            StmtKind::Function(function) => self.lisp_function(function),
+            StmtKind::Return(value) => {
+                self.push("(return");
+                if let Some(value) = value {
+                    self.push(" ");
+                    self.lisp(value);
+                }
+                self.push(")");
+            }
+            StmtKind::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                self.push(&format!("(class {}", name.lexeme));
+                if let Some(superclass) = superclass {
+                    self.push(" < ");
+                    self.lisp(superclass);
+                }
+                for method in methods {
+                    self.push(" ");
+                    self.lisp_function(method);
+                }
+                self.push(")");
+            }
+        }
+    }
+
+    fn lisp_function(&mut self, function: &Function) {
+        let params: Vec<&str> = function.params.iter().map(|param| param.lexeme).collect();
+        self.push(&format!(
+            "(fun {}({})",
+            function.name.lexeme,
+            params.join(" ")
+        ));
+        self.lisp_list(&function.body, Printer::lisp_stmt);
+        self.push(")");
+    }
+
+    fn source(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Literal(value) => self.push(&value.to_string()),
+            ExprKind::Variable(name) => self.push(name),
+            ExprKind::Assign(name, value) => {
+                // assignment is right-associative, so the value never needs parentheses.
+                self.push(&format!("{} = ", name.lexeme));
+                self.source(value);
+            }
+            ExprKind::Binary(left, operator, right) => {
+                self.source_infix(left, &operator.to_string(), right, expr.precedence())
+            }
+            ExprKind::Logical(left, operator, right) => {
+                self.source_infix(left, &operator.to_string(), right, expr.precedence())
+            }
+            ExprKind::Grouping(expr) => {
+                self.push("(");
+                self.source(expr);
+                self.push(")");
+            }
+            ExprKind::Unary(operator, operand) => {
+                self.push(&operator.to_string());
+                self.source_operand(operand, operand.precedence() < Precedence::Unary);
+            }
+            ExprKind::Call(callee, arguments) => {
+                self.source_operand(callee, callee.precedence() < Precedence::Call);
+                self.push("(");
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        self.push(", ");
+                    }
+                    self.source(argument);
+                }
+                self.push(")");
+            }
+            ExprKind::Get(object, name) => {
+                self.source_operand(object, object.precedence() < Precedence::Call);
+                self.push(&format!(".{}", name.lexeme));
+            }
+            ExprKind::Set(object, name, value) => {
+                self.source_operand(object, object.precedence() < Precedence::Call);
+                self.push(&format!(".{} = ", name.lexeme));
+                self.source(value);
+            }
+            ExprKind::This => self.push("this"),
+            ExprKind::Super(method) => self.push(&format!("super.{}", method.lexeme)),
+        }
+    }
+
+    // binary and logical operators are left-associative: a left operand of the same precedence
+    // binds correctly on its own, a right operand of the same precedence does not.
+    fn source_infix(&mut self, left: &Expr, operator: &str, right: &Expr, precedence: Precedence) {
+        self.source_operand(left, left.precedence() < precedence);
+        self.push(&format!(" {} ", operator));
+        self.source_operand(right, right.precedence() <= precedence);
+    }
+
+    fn source_operand(&mut self, expr: &Expr, parenthesize: bool) {
+        if parenthesize {
+            self.push("(");
+            self.source(expr);
+            self.push(")");
+        } else {
+            self.source(expr);
+        }
+    }
+
+    fn source_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Expression(expr) => {
+                self.source(expr);
+                self.push(";");
+            }
+            StmtKind::Print(expr) => {
+                self.push("print ");
+                self.source(expr);
+                self.push(";");
+            }
+            StmtKind::Var { name, initializer } => {
+                self.push(&format!("var {}", name.lexeme));
+                if let Some(initializer) = initializer {
+                    self.push(" = ");
+                    self.source(initializer);
+                }
+                self.push(";");
+            }
+            StmtKind::Block(statements) => self.source_block(statements),
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.push("if (");
+                self.source(condition);
+                self.push(") ");
+                // an else-less `if` as the then-branch would steal our `else` when re-parsed.
+                let dangling = else_branch.is_some()
+                    && matches!(
+                        then_branch.kind,
+                        StmtKind::If {
+                            else_branch: None,
+                            ..
+                        }
+                    );
+                if dangling {
+                    self.source_block(std::slice::from_ref(then_branch));
+                } else {
+                    self.source_stmt(then_branch);
+                }
+                if let Some(else_branch) = else_branch {
+                    self.push(" else ");
+                    self.source_stmt(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.push("while (");
+                self.source(condition);
+                self.push(") ");
+                self.source_stmt(body);
+            }
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                self.push("for (");
+                match initializer {
+                    // the initializer statement brings its own semicolon.
+                    Some(initializer) => self.source_stmt(initializer),
+                    None => self.push(";"),
+                }
+                if let Some(condition) = condition {
+                    self.push(" ");
+                    self.source(condition);
+                }
+                self.push(";");
+                if let Some(increment) = increment {
+                    self.push(" ");
+                    self.source(increment);
+                }
+                self.push(") ");
+                self.source_stmt(body);
+            }
+            StmtKind::Function(function) => {
+                self.push("fun ");
+                self.source_function(function);
+            }
+            StmtKind::Return(value) => {
+                self.push("return");
+                if let Some(value) = value {
+                    self.push(" ");
+                    self.source(value);
+                }
+                self.push(";");
+            }
+            StmtKind::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                self.push(&format!("class {} ", name.lexeme));
+                if let Some(superclass) = superclass {
+                    self.push("< ");
+                    self.source(superclass);
+                    self.push(" ");
+                }
+                if methods.is_empty() {
+                    self.push("{}");
+                    return;
+                }
+                self.push("{");
+                self.indent += 1;
+                for method in methods {
+                    self.newline();
+                    self.source_function(method);
+                }
+                self.indent -= 1;
+                self.newline();
+                self.push("}");
+            }
+        }
+    }
+
+    fn source_function(&mut self, function: &Function) {
+        let params: Vec<&str> = function.params.iter().map(|param| param.lexeme).collect();
+        self.push(&format!("{}({}) ", function.name.lexeme, params.join(", ")));
+        self.source_block(&function.body);
+    }
+
+    fn source_block(&mut self, statements: &[Stmt]) {
+        if statements.is_empty() {
+            self.push("{}");
+            return;
+        }
+        self.push("{");
+        self.indent += 1;
+        for statement in statements {
+            self.newline();
+            self.source_stmt(statement);
+        }
+        self.indent -= 1;
+        self.newline();
+        self.push("}");
+    }
+}
@@ -0,0 +1,367 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::{DefaultHistory, History};
+use rustyline::validate::Validator;
+use rustyline::{ColorMode, Config, Context, Editor, Helper};
+use typed_arena::Arena;
+
+use crate::highlight;
+use crate::lox::{read_source, AstFormat, Lox, TokenFormat};
+use crate::scanner::Scanner;
+use crate::types::KEYWORDS;
+use crate::value::Value;
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+const HISTORY_SIZE: usize = 1000;
+
+const HELP: &str = "\
+Enter Lox code to run it. The value of an expression statement is printed and kept in `_`,
+unless it's nil: then `_` keeps the previous value.
+Commands:
+  :tokens <code>  show the tokens the scanner produces for <code>
+  :ast <code>     show the syntax tree of <code>
+  :env            list global variables and their values
+  :load <file>    run a file in this session
+  :reset          forget everything defined so far
+  :time <code>    run <code> and show how long it took
+  :help           show this message";
+
+enum Command<'a> {
+    Run(&'a str),
+    Tokens(&'a str),
+    Ast(&'a str),
+    Env,
+    Load(&'a str),
+    Reset,
+    Time(&'a str),
+    Help,
+}
+
+impl<'a> Command<'a> {
+    fn parse(entry: &'a str) -> Result<Command<'a>, String> {
+        let command = match entry.trim_start().strip_prefix(':') {
+            Some(command) => command,
+            None => return Ok(Command::Run(entry)),
+        };
+        let (name, argument) = match command.find(char::is_whitespace) {
+            Some(i) => (&command[..i], command[i..].trim()),
+            None => (command, ""),
+        };
+        match (name, argument) {
+            ("tokens", code) => Ok(Command::Tokens(code)),
+            ("ast", code) => Ok(Command::Ast(code)),
+            ("env", "") => Ok(Command::Env),
+            ("load", path) if !path.is_empty() => Ok(Command::Load(path)),
+            ("reset", "") => Ok(Command::Reset),
+            ("time", code) => Ok(Command::Time(code)),
+            ("help", "") => Ok(Command::Help),
+            ("env", _) | ("reset", _) | ("help", _) => {
+                Err(format!(":{} doesn't take an argument.", name))
+            }
+            ("load", _) => Err(String::from(":load needs a file to run.")),
+            _ => Err(format!("Unknown command ':{}'. Try :help.", name)),
+        }
+    }
+}
+
+/// Runs interactive sessions on `lox` until the user is done. Each `:reset` starts a new one.
+pub fn run(lox: &mut Lox) {
+    let config = match config(lox.uses_color()) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Could not start the line editor: {}", error);
+            return;
+        }
+    };
+    let mut history = SavedHistory::load(&config);
+    println!("Welcome to Lox! Type :help for a list of commands.");
+    loop {
+        // what a session defines borrows from the entries and files it ran, so they're kept
+        // until the session ends, and the reader completing its globals goes with them.
+        let sources = Arena::new();
+        let mut reader = match LineReader::new(config.clone()) {
+            Ok(reader) => reader,
+            Err(error) => {
+                eprintln!("Could not start the line editor: {}", error);
+                break;
+            }
+        };
+        reader.swap_history(&mut history.lines);
+        let reset = session(lox, &mut reader, &sources);
+        reader.swap_history(&mut history.lines);
+        if !reset {
+            break;
+        }
+    }
+    history.save();
+}
+
+// runs a session until the user is done, or `:reset`s it: then returns true.
+fn session<'s>(lox: &mut Lox, reader: &mut LineReader<'s>, sources: &'s Arena<String>) -> bool {
+    let mut interpreter = lox.session_interpreter();
+    interpreter.set_echo(true);
+    reader.set_globals(interpreter.globals());
+    while let Some(entry) = reader.read_entry() {
+        let entry = sources.alloc(entry).as_str();
+        // errors have been reported as they were found, so results are ignored below.
+        match Command::parse(entry) {
+            Ok(Command::Run(code)) => {
+                let _ = lox.run_in(&mut interpreter, code);
+            }
+            Ok(Command::Tokens(code)) => {
+                let _ = lox.dump_tokens(code, TokenFormat::Text, false);
+            }
+            Ok(Command::Ast(code)) => {
+                let _ = lox.dump_ast(code, AstFormat::Lisp);
+            }
+            Ok(Command::Env) => {
+                for (name, value) in interpreter.globals() {
+                    println!("{} = {:?}", name, value);
+                }
+            }
+            Ok(Command::Load(path)) => match read_source(Path::new(path)) {
+                // a file runs as `rlox run` would run it, without echoing its values.
+                Ok(source) => {
+                    interpreter.set_echo(false);
+                    let _ = lox.run_in(&mut interpreter, sources.alloc(source));
+                    interpreter.set_echo(true);
+                }
+                Err(error) => eprintln!("{}", error),
+            },
+            Ok(Command::Reset) => {
+                lox.reset_errors();
+                return true;
+            }
+            Ok(Command::Time(code)) => {
+                let start = Instant::now();
+                let _ = lox.run_in(&mut interpreter, code);
+                println!("Took {:?}.", start.elapsed());
+            }
+            Ok(Command::Help) => println!("{}", HELP),
+            Err(message) => eprintln!("{}", message),
+        }
+        lox.reset_errors();
+        reader.set_globals(interpreter.globals());
+    }
+    false
+}
+
+/// Completions for the word that ends `line`, and the byte offset at which that word starts.
+/// After a `.` the word is completed with the fields and methods of the instance that the
+/// preceding chain of names refers to, such as `point.x` or `a.b.c`; elsewhere with keywords
+/// and `globals`.
+pub fn complete<'a>(line: &str, globals: &[(&'a str, Value<'a>)]) -> (usize, Vec<String>) {
+    let start = line
+        .rfind(|c: char| !is_identifier_char(c))
+        .map_or(0, |i| i + 1);
+    let (before, prefix) = line.split_at(start);
+    let mut candidates: Vec<String> = match before.strip_suffix('.') {
+        Some(object) => members(object, globals),
+        None => KEYWORDS
+            .keys()
+            .copied()
+            .chain(globals.iter().map(|(name, _)| *name))
+            .map(String::from)
+            .collect(),
+    };
+    candidates.retain(|candidate| candidate.starts_with(prefix));
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// field and method names of the instance that `object`'s trailing `name(.name)*` refers to.
+fn members<'a>(object: &str, globals: &[(&'a str, Value<'a>)]) -> Vec<String> {
+    let start = object
+        .rfind(|c: char| !is_identifier_char(c) && c != '.')
+        .map_or(0, |i| i + 1);
+    let mut names = object[start..].split('.');
+    let first = names.next().unwrap_or_default();
+    let mut value = match globals.iter().find(|(name, _)| *name == first) {
+        Some((_, value)) => value.clone(),
+        None => return vec![],
+    };
+    for name in names {
+        let field = match &value {
+            Value::Instance(instance) => instance.borrow().fields.get(name).cloned(),
+            _ => None,
+        };
+        match field {
+            Some(field) => value = field,
+            None => return vec![],
+        }
+    }
+    let instance = match &value {
+        Value::Instance(instance) => instance.borrow(),
+        _ => return vec![],
+    };
+    let mut members: Vec<String> = instance
+        .fields
+        .keys()
+        .map(|name| name.to_string())
+        .collect();
+    let mut class = Some(&instance.class);
+    while let Some(current) = class {
+        members.extend(current.methods.keys().map(|name| name.to_string()));
+        class = current.superclass.as_ref();
+    }
+    members
+}
+
+// completes from a snapshot of the session's globals, refreshed after every entry.
+#[derive(Default)]
+struct LoxHelper<'s> {
+    globals: Vec<(&'s str, Value<'s>)>,
+}
+
+impl<'s> Completer for LoxHelper<'s> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&line[..pos], &self.globals))
+    }
+}
+
+impl<'s> Hinter for LoxHelper<'s> {
+    type Hint = String;
+}
+
+impl<'s> Highlighter for LoxHelper<'s> {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight::ansi(line))
+    }
+
+    // any keystroke can change how the line scans, e.g. by opening a string.
+    fn highlight_char(&self, _: &str, _: usize, _: CmdKind) -> bool {
+        true
+    }
+}
+
+impl<'s> Validator for LoxHelper<'s> {}
+
+impl<'s> Helper for LoxHelper<'s> {}
+
+// the reader's settings; with `color`, input is syntax highlighted as it's typed.
+fn config(color: bool) -> rustyline::Result<Config> {
+    Ok(Config::builder()
+        .max_history_size(HISTORY_SIZE)?
+        .auto_add_history(false)
+        .color_mode(if color {
+            ColorMode::Enabled
+        } else {
+            ColorMode::Disabled
+        })
+        .build())
+}
+
+// the entries of earlier sessions: those of previous runs are loaded from `$RLOX_HISTORY`, or
+// `~/.rlox_history` when that isn't set, and this run's are saved there when it ends.
+struct SavedHistory {
+    lines: DefaultHistory,
+    path: Option<PathBuf>,
+}
+
+impl SavedHistory {
+    fn load(config: &Config) -> SavedHistory {
+        let mut lines = DefaultHistory::with_config(config);
+        let path = std::env::var_os("RLOX_HISTORY")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".rlox_history"))
+            });
+        if let Some(path) = &path {
+            // there's no history yet on first use.
+            let _ = lines.load(path);
+        }
+        SavedHistory { lines, path }
+    }
+
+    fn save(&mut self) {
+        if let Some(path) = &self.path {
+            if let Err(error) = self.lines.save(path) {
+                eprintln!("Could not save history to {:?}: {}", path, error);
+            }
+        }
+    }
+}
+
+/// Reads REPL entries with line editing, completion and history. An entry spans several lines
+/// when the first ones leave a string, parenthesis or brace open. The globals it completes
+/// borrow from the session they're defined in, for `'s`.
+pub struct LineReader<'s> {
+    editor: Editor<LoxHelper<'s>, DefaultHistory>,
+}
+
+impl<'s> LineReader<'s> {
+    /// A reader with `config` and an empty history.
+    pub fn new(config: Config) -> rustyline::Result<LineReader<'s>> {
+        let mut editor = Editor::with_config(config)?;
+        editor.set_helper(Some(LoxHelper::default()));
+        Ok(LineReader { editor })
+    }
+
+    /// Exchanges the reader's history for `lines`, which hands one session's history on to the
+    /// next session's reader.
+    pub fn swap_history(&mut self, lines: &mut DefaultHistory) {
+        std::mem::swap(self.editor.history_mut(), lines);
+    }
+
+    /// Makes the names of `globals` available for completion.
+    pub fn set_globals(&mut self, globals: Vec<(&'s str, Value<'s>)>) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.globals = globals;
+        }
+    }
+
+    /// The next entry, or `None` once the user is done: at end of input, or on Ctrl-C at the
+    /// main prompt. Ctrl-C at a continuation prompt abandons the entry instead.
+    pub fn read_entry(&mut self) -> Option<String> {
+        let mut entry = match self.editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => return None,
+            Err(error) => {
+                eprintln!("{}", error);
+                return None;
+            }
+        };
+        // commands are a line each, whatever their code leaves open.
+        while !entry.trim_start().starts_with(':') && Scanner::new(&entry).is_incomplete() {
+            match self.editor.readline(CONTINUATION_PROMPT) {
+                Ok(line) => {
+                    entry.push('\n');
+                    entry.push_str(&line);
+                }
+                Err(ReadlineError::Interrupted) => {
+                    entry.clear();
+                    break;
+                }
+                // run what we have; it'll be reported as incomplete.
+                Err(ReadlineError::Eof) => break,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    return None;
+                }
+            }
+        }
+        if !entry.trim().is_empty() {
+            let _ = self.editor.add_history_entry(entry.as_str());
+        }
+        Some(entry)
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+
+use crate::lox::Lox;
+use crate::parser::{Expr, ExprId, ExprKind, Function, Name, Stmt, StmtKind};
+
+/// For each expression that refers to a local variable, how many scopes up from the innermost
+/// one the variable was declared. Expressions missing from the map refer to globals.
+pub type Locals = HashMap<ExprId, usize>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Static pass between parsing and interpretation: binds each variable reference to its
+/// declaration and reports scoping errors such as returning from top-level code.
+pub struct Resolver<'a> {
+    // per scope, whether each declared name has finished initializing.
+    scopes: Vec<HashMap<&'a str, bool>>,
+    locals: Locals,
+    function: FunctionType,
+    class: ClassType,
+}
+
+impl<'a> Default for Resolver<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new() -> Resolver<'a> {
+        Resolver {
+            scopes: vec![],
+            locals: Locals::new(),
+            function: FunctionType::None,
+            class: ClassType::None,
+        }
+    }
+
+    /// Resolves a program. Errors are reported to `lox`.
+    pub fn resolve(mut self, statements: &[Stmt<'a>], lox: &mut Lox) -> Locals {
+        self.statements(statements, lox);
+        self.locals
+    }
+
+    fn statements(&mut self, statements: &[Stmt<'a>], lox: &mut Lox) {
+        for statement in statements {
+            self.statement(statement, lox);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt<'a>, lox: &mut Lox) {
+        match &stmt.kind {
+            StmtKind::Expression(expr) | StmtKind::Print(expr) => self.expression(expr, lox),
+            StmtKind::Var { name, initializer } => {
+                self.declare(name, lox);
+                if let Some(initializer) = initializer {
+                    self.expression(initializer, lox);
+                }
+                self.define(name);
+            }
+            StmtKind::Block(statements) => {
+                self.begin_scope();
+                self.statements(statements, lox);
+                self.end_scope();
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition, lox);
+                self.statement(then_branch, lox);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch, lox);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.expression(condition, lox);
+                self.statement(body, lox);
+            }
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                // the initializer's variable lives in a scope wrapping the whole loop.
+                self.begin_scope();
+                if let Some(initializer) = initializer {
+                    self.statement(initializer, lox);
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition, lox);
+                }
+                if let Some(increment) = increment {
+                    self.expression(increment, lox);
+                }
+                self.statement(body, lox);
+                self.end_scope();
+            }
+            StmtKind::Function(function) => {
+                self.declare(&function.name, lox);
+                self.define(&function.name);
+                self.function(function, FunctionType::Function, lox);
+            }
+            StmtKind::Return(value) => {
+                if self.function == FunctionType::None {
+                    lox.error_at_lexeme(
+                        stmt.span.line,
+                        "return",
+                        "Can't return from top-level code.",
+                    );
+                }
+                if let Some(value) = value {
+                    if self.function == FunctionType::Initializer {
+                        lox.error_at_lexeme(
+                            stmt.span.line,
+                            "return",
+                            "Can't return a value from an initializer.",
+                        );
+                    }
+                    self.expression(value, lox);
+                }
+            }
+            StmtKind::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let enclosing = self.class;
+                self.class = ClassType::Class;
+                self.declare(name, lox);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let ExprKind::Variable(superclass_name) = superclass.kind {
+                        if superclass_name == name.lexeme {
+                            lox.error_at_lexeme(
+                                superclass.span.line,
+                                superclass_name,
+                                "A class can't inherit from itself.",
+                            );
+                        }
+                    }
+                    self.class = ClassType::Subclass;
+                    self.expression(superclass, lox);
+                    self.begin_scope();
+                    self.scopes.last_mut().unwrap().insert("super", true);
+                }
+
+                self.begin_scope();
+                self.scopes.last_mut().unwrap().insert("this", true);
+                for method in methods {
+                    let kind = if method.name.lexeme == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.function(method, kind, lox);
+                }
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+                self.class = enclosing;
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Function<'a>, kind: FunctionType, lox: &mut Lox) {
+        let enclosing = self.function;
+        self.function = kind;
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param, lox);
+            self.define(param);
+        }
+        self.statements(&function.body, lox);
+        self.end_scope();
+        self.function = enclosing;
+    }
+
+    fn expression(&mut self, expr: &Expr<'a>, lox: &mut Lox) {
+        match &expr.kind {
+            ExprKind::Literal(_) => {}
+            ExprKind::Variable(name) => {
+                if let Some(false) = self.scopes.last().and_then(|scope| scope.get(name)) {
+                    lox.error_at_lexeme(
+                        expr.span.line,
+                        name,
+                        "Can't read local variable in its own initializer.",
+                    );
+                }
+                self.resolve_local(expr, name);
+            }
+            ExprKind::Assign(name, value) => {
+                self.expression(value, lox);
+                self.resolve_local(expr, name.lexeme);
+            }
+            ExprKind::Binary(left, _, right) | ExprKind::Logical(left, _, right) => {
+                self.expression(left, lox);
+                self.expression(right, lox);
+            }
+            ExprKind::Grouping(expr) | ExprKind::Unary(_, expr) => self.expression(expr, lox),
+            ExprKind::Call(callee, arguments) => {
+                self.expression(callee, lox);
+                for argument in arguments {
+                    self.expression(argument, lox);
+                }
+            }
+            ExprKind::Get(object, _) => self.expression(object, lox),
+            ExprKind::Set(object, _, value) => {
+                self.expression(value, lox);
+                self.expression(object, lox);
+            }
+            ExprKind::This => {
+                if self.class == ClassType::None {
+                    lox.error_at_lexeme(
+                        expr.span.line,
+                        "this",
+                        "Can't use 'this' outside of a class.",
+                    );
+                    return;
+                }
+                self.resolve_local(expr, "this");
+            }
+            ExprKind::Super(_) => {
+                match self.class {
+                    ClassType::None => lox.error_at_lexeme(
+                        expr.span.line,
+                        "super",
+                        "Can't use 'super' outside of a class.",
+                    ),
+                    ClassType::Class => lox.error_at_lexeme(
+                        expr.span.line,
+                        "super",
+                        "Can't use 'super' in a class with no superclass.",
+                    ),
+                    ClassType::Subclass => {}
+                }
+                self.resolve_local(expr, "super");
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Name<'a>, lox: &mut Lox) {
+        if let Some(scope) = self.scopes.last_mut() {
+            if scope.contains_key(name.lexeme) {
+                lox.error_at_lexeme(
+                    name.span.line,
+                    name.lexeme,
+                    "Already a variable with this name in this scope.",
+                );
+            }
+            scope.insert(name.lexeme, false);
+        }
+    }
+
+    fn define(&mut self, name: &Name<'a>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme, true);
+        }
+    }
+
+    fn resolve_local(&mut self, expr: &Expr<'a>, name: &str) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(name) {
+                self.locals.insert(expr.id, depth);
+                return;
+            }
+        }
+    }
+}
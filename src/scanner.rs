@@ -1,10 +1,26 @@
 use crate::{
     lox::Lox,
-    types::{Grouping, Literal, Misc, Operator, Token, KEYWORDS},
+    types::{Grouping, Literal, Misc, Operator, Span, Token, Trivia, KEYWORDS},
 };
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScanError {
+    UnexpectedCharacter,
+    UnterminatedString,
+}
+
+impl ScanError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ScanError::UnexpectedCharacter => "Unexpected character",
+            ScanError::UnterminatedString => "Unterminated string",
+        }
+    }
+}
+
 pub struct Scanner<'a> {
     pub source: &'a str,
+    trivia: bool,
 }
 
 #[derive(Debug)]
@@ -19,6 +35,14 @@ impl ScanIndex {
     fn at_end(&self, offset: usize) -> bool {
         self.current + offset >= self.source_length
     }
+
+    fn span(&self, length: usize) -> Span {
+        Span {
+            start: self.start,
+            end: self.start + length,
+            line: self.line,
+        }
+    }
 }
 
 enum ScanResult<'a> {
@@ -29,21 +53,65 @@ enum ScanResult<'a> {
     NumberLexeme(usize, Token<'a>),
     Whitespace,
     Newline,
-    Error(&'a str),
+    Error(ScanError),
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str) -> Scanner<'a> {
-        Scanner { source }
+        Scanner {
+            source,
+            trivia: false,
+        }
+    }
+
+    /// Also produce `Token::Trivia` for whitespace and comments, so that the tokens cover the
+    /// whole source apart from characters reported as errors. The parser doesn't accept trivia.
+    pub fn trivia(mut self, trivia: bool) -> Self {
+        self.trivia = trivia;
+        self
+    }
+
+    /// Scans the whole source, reporting errors to `lox`. The returned tokens always end with
+    /// `Token::Eof`.
+    pub fn scan_tokens(&self, lox: &mut Lox) -> Vec<Token<'a>> {
+        self.scan(|span, error| lox.error_in(span, error.message()))
     }
 
-    pub fn scan_tokens(&'a self, lox: &mut Lox) -> Vec<Token<'a>> {
+    /// Scans the whole source, skipping over errors instead of reporting them; for tools such as
+    /// highlighting that have to cope with any input.
+    pub fn scan_ignoring_errors(&self) -> Vec<Token<'a>> {
+        self.scan(|_, _| {})
+    }
+
+    /// Whether the source stops partway through a construct: inside a string, or with more
+    /// opening than closing parentheses or braces. The REPL keeps reading lines until it isn't.
+    pub fn is_incomplete(&self) -> bool {
+        let mut unterminated = false;
+        let tokens = self.scan(|_, error| unterminated |= error == ScanError::UnterminatedString);
+        let mut depth = 0;
+        for token in &tokens {
+            match token {
+                Token::Grouping {
+                    token: Grouping::LeftParen | Grouping::LeftBrace,
+                    ..
+                } => depth += 1,
+                Token::Grouping {
+                    token: Grouping::RightParen | Grouping::RightBrace,
+                    ..
+                } => depth -= 1,
+                _ => {}
+            }
+        }
+        unterminated || depth > 0
+    }
+
+    fn scan(&self, mut on_error: impl FnMut(Span, ScanError)) -> Vec<Token<'a>> {
         let mut tokens = vec![];
         let mut scan_index = ScanIndex {
             start: 0,
             current: 0,
             line: 1,
-            source_length: self.source.chars().count(),
+            source_length: self.source.len(),
         };
         while !scan_index.at_end(0) {
             scan_index.start = scan_index.current;
@@ -57,13 +125,21 @@ impl<'a> Scanner<'a> {
                     tokens.push(token);
                 }
                 ScanResult::Whitespace => {
+                    self.push_whitespace(&mut tokens, &scan_index);
                     scan_index.current += 1;
                 }
                 ScanResult::Newline => {
+                    self.push_whitespace(&mut tokens, &scan_index);
                     scan_index.current += 1;
                     scan_index.line += 1;
                 }
                 ScanResult::CommentLexeme(length) => {
+                    if self.trivia {
+                        tokens.push(Token::Trivia {
+                            span: scan_index.span(length),
+                            token: Trivia::Comment,
+                        });
+                    }
                     scan_index.current += length;
                 }
                 ScanResult::StringLexeme(length, extra_lines, token) => {
@@ -75,25 +151,66 @@ impl<'a> Scanner<'a> {
                     scan_index.current += length;
                     tokens.push(token);
                 }
-                ScanResult::Error(msg) => {
-                    scan_index.current += 1;
-                    lox.error(scan_index.line, msg);
+                ScanResult::Error(ScanError::UnterminatedString) => {
+                    // the rest of the source belongs to the string.
+                    let span = Span {
+                        start: scan_index.start,
+                        end: scan_index.source_length,
+                        line: scan_index.line,
+                    };
+                    on_error(span, ScanError::UnterminatedString);
+                    scan_index.line += self.source[scan_index.current..].matches('\n').count();
+                    scan_index.current = scan_index.source_length;
+                }
+                ScanResult::Error(error) => {
+                    // skip the whole offending character, not just its first byte
+                    scan_index.current += self.source[scan_index.current..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8);
+                    let span = Span {
+                        start: scan_index.start,
+                        end: scan_index.current,
+                        line: scan_index.line,
+                    };
+                    on_error(span, error);
                 }
             };
         }
 
+        scan_index.start = scan_index.current;
         tokens.push(Token::Eof {
-            line: scan_index.line,
+            span: scan_index.span(0),
         });
         tokens
     }
 
-    fn scan_token(&'a self, scan_index: &ScanIndex) -> ScanResult {
-        let c = self.peek_offset(&scan_index, 0);
-        if c.is_none() {
-            return ScanResult::Error("Unexpected EOF");
+    // consecutive whitespace characters make up a single trivia token.
+    fn push_whitespace(&self, tokens: &mut Vec<Token<'a>>, scan_index: &ScanIndex) {
+        if !self.trivia {
+            return;
         }
-        match c.unwrap() {
+        if let Some(Token::Trivia {
+            span,
+            token: Trivia::Whitespace,
+        }) = tokens.last_mut()
+        {
+            if span.end == scan_index.start {
+                span.end += 1;
+                return;
+            }
+        }
+        tokens.push(Token::Trivia {
+            span: scan_index.span(1),
+            token: Trivia::Whitespace,
+        });
+    }
+
+    fn scan_token(&self, scan_index: &ScanIndex) -> ScanResult<'a> {
+        match self
+            .peek_offset(scan_index, 0)
+            .expect("scan_token called at end of source")
+        {
             // whitespace
             ' ' | '\r' | '\t' => ScanResult::Whitespace,
             // newline
@@ -101,44 +218,44 @@ impl<'a> Scanner<'a> {
 
             // single-character lexemes
             '(' => ScanResult::SingleCharLexeme(Token::Grouping {
-                line: scan_index.line,
+                span: scan_index.span(1),
                 token: Grouping::LeftParen,
             }),
             ')' => ScanResult::SingleCharLexeme(Token::Grouping {
-                line: scan_index.line,
+                span: scan_index.span(1),
                 token: Grouping::RightParen,
             }),
             '{' => ScanResult::SingleCharLexeme(Token::Grouping {
-                line: scan_index.line,
+                span: scan_index.span(1),
                 token: Grouping::LeftBrace,
             }),
             '}' => ScanResult::SingleCharLexeme(Token::Grouping {
-                line: scan_index.line,
+                span: scan_index.span(1),
                 token: Grouping::RightBrace,
             }),
 
             ',' => ScanResult::SingleCharLexeme(Token::Misc {
-                line: scan_index.line,
+                span: scan_index.span(1),
                 token: Misc::Comma,
             }),
             '.' => ScanResult::SingleCharLexeme(Token::Misc {
-                line: scan_index.line,
+                span: scan_index.span(1),
                 token: Misc::Dot,
             }),
             ';' => ScanResult::SingleCharLexeme(Token::Misc {
-                line: scan_index.line,
+                span: scan_index.span(1),
                 token: Misc::Semicolon,
             }),
             '-' => ScanResult::SingleCharLexeme(Token::Operator {
-                line: scan_index.line,
+                span: scan_index.span(1),
                 token: Operator::Minus,
             }),
             '+' => ScanResult::SingleCharLexeme(Token::Operator {
-                line: scan_index.line,
+                span: scan_index.span(1),
                 token: Operator::Plus,
             }),
             '*' => ScanResult::SingleCharLexeme(Token::Operator {
-                line: scan_index.line,
+                span: scan_index.span(1),
                 token: Operator::Star,
             }),
             '/' => {
@@ -158,89 +275,105 @@ impl<'a> Scanner<'a> {
                         }
                     }
                     _ => ScanResult::SingleCharLexeme(Token::Operator {
-                        line: scan_index.line,
+                        span: scan_index.span(1),
                         token: Operator::Slash,
                     }),
                 }
             }
             // single or two character lexemes
             '!' => match self.peek_offset(scan_index, 1) {
-                Some('=') => ScanResult::SingleCharLexeme(Token::Operator {
-                    line: scan_index.line,
-                    token: Operator::BangEqual,
-                }),
+                Some('=') => ScanResult::MultiCharLexeme(
+                    2,
+                    Token::Operator {
+                        span: scan_index.span(2),
+                        token: Operator::BangEqual,
+                    },
+                ),
                 _ => ScanResult::SingleCharLexeme(Token::Operator {
-                    line: scan_index.line,
+                    span: scan_index.span(1),
                     token: Operator::Bang,
                 }),
             },
             '=' => match self.peek_offset(scan_index, 1) {
-                Some('=') => ScanResult::SingleCharLexeme(Token::Operator {
-                    line: scan_index.line,
-                    token: Operator::EqualEqual,
-                }),
+                Some('=') => ScanResult::MultiCharLexeme(
+                    2,
+                    Token::Operator {
+                        span: scan_index.span(2),
+                        token: Operator::EqualEqual,
+                    },
+                ),
                 _ => ScanResult::SingleCharLexeme(Token::Operator {
-                    line: scan_index.line,
+                    span: scan_index.span(1),
                     token: Operator::Equal,
                 }),
             },
             '<' => match self.peek_offset(scan_index, 1) {
-                Some('=') => ScanResult::SingleCharLexeme(Token::Operator {
-                    line: scan_index.line,
-                    token: Operator::LessEqual,
-                }),
+                Some('=') => ScanResult::MultiCharLexeme(
+                    2,
+                    Token::Operator {
+                        span: scan_index.span(2),
+                        token: Operator::LessEqual,
+                    },
+                ),
                 _ => ScanResult::SingleCharLexeme(Token::Operator {
-                    line: scan_index.line,
+                    span: scan_index.span(1),
                     token: Operator::Less,
                 }),
             },
             '>' => match self.peek_offset(scan_index, 1) {
-                Some('=') => ScanResult::SingleCharLexeme(Token::Operator {
-                    line: scan_index.line,
-                    token: Operator::GreaterEqual,
-                }),
+                Some('=') => ScanResult::MultiCharLexeme(
+                    2,
+                    Token::Operator {
+                        span: scan_index.span(2),
+                        token: Operator::GreaterEqual,
+                    },
+                ),
                 _ => ScanResult::SingleCharLexeme(Token::Operator {
-                    line: scan_index.line,
+                    span: scan_index.span(1),
                     token: Operator::Greater,
                 }),
             },
 
             // literals
             '"' => self.string(scan_index),
-            c if c.is_digit(10) => self.number(scan_index),
-            c if c.is_alphabetic() => self.identifier_or_reserved(scan_index),
+            c if c.is_ascii_digit() => self.number(scan_index),
+            c if c.is_ascii_alphabetic() || c == '_' => self.identifier_or_reserved(scan_index),
 
-            _ => ScanResult::Error("Unexpected character"),
+            _ => ScanResult::Error(ScanError::UnexpectedCharacter),
         }
     }
 
-    fn number(&self, scan_index: &ScanIndex) -> ScanResult {
-        // determine length of the number
+    fn number(&self, scan_index: &ScanIndex) -> ScanResult<'a> {
+        // determine length of the number: integer part, then an optional fractional part.
+        // a trailing '.' without digits after it is not part of the number (e.g. "1.foo").
         let mut length = 1;
-        loop {
-            match self.peek_offset(scan_index, length) {
-                Some('0') | Some('1') | Some('2') | Some('3') | Some('4') | Some('5')
-                | Some('6') | Some('7') | Some('8') | Some('9') | Some('.') => {}
-                Some(_) | None => {
-                    break ScanResult::NumberLexeme(
-                        length,
-                        Token::Literal {
-                            line: scan_index.line,
-                            token: Literal::Number {
-                                literal: self
-                                    .literal(scan_index, length)
-                                    .parse()
-                                    .expect("invalid number literal"),
-                            },
-                        },
-                    )
-                }
-            }
+        while let Some('0'..='9') = self.peek_offset(scan_index, length) {
             length += 1;
         }
+        if let (Some('.'), Some('0'..='9')) = (
+            self.peek_offset(scan_index, length),
+            self.peek_offset(scan_index, length + 1),
+        ) {
+            length += 1;
+            while let Some('0'..='9') = self.peek_offset(scan_index, length) {
+                length += 1;
+            }
+        }
+        ScanResult::NumberLexeme(
+            length,
+            Token::Literal {
+                span: scan_index.span(length),
+                token: Literal::Number {
+                    literal: self
+                        .literal(scan_index, length)
+                        .parse()
+                        .expect("invalid number literal"),
+                },
+            },
+        )
     }
 
-    fn string(&self, scan_index: &ScanIndex) -> ScanResult {
+    fn string(&self, scan_index: &ScanIndex) -> ScanResult<'a> {
         // determine length of string
         let mut length = 0;
         // for multiline strings
@@ -252,7 +385,7 @@ impl<'a> Scanner<'a> {
                         length,
                         extra_lines,
                         Token::Literal {
-                            line: scan_index.line,
+                            span: scan_index.span(length + 2),
                             token: Literal::String {
                                 size: length,
                                 literal: self.quoted_literal(scan_index, length),
@@ -262,26 +395,26 @@ impl<'a> Scanner<'a> {
                 }
                 Some('\n') => extra_lines += 1,
                 Some(_) => {}
-                None => break ScanResult::Error("Unterminated string"),
+                None => break ScanResult::Error(ScanError::UnterminatedString),
             }
             length += 1;
         }
     }
 
-    fn quoted_literal(&self, scan_index: &ScanIndex, length: usize) -> &str {
+    fn quoted_literal(&self, scan_index: &ScanIndex, length: usize) -> &'a str {
         &self.source[scan_index.start + 1..scan_index.start + length + 1]
     }
 
-    fn literal(&self, scan_index: &ScanIndex, length: usize) -> &str {
+    fn literal(&self, scan_index: &ScanIndex, length: usize) -> &'a str {
         &self.source[scan_index.start..scan_index.start + length]
     }
 
-    fn identifier_or_reserved(&self, scan_index: &ScanIndex) -> ScanResult {
+    fn identifier_or_reserved(&self, scan_index: &ScanIndex) -> ScanResult<'a> {
         // determine length of identifier
         let mut length = 1;
         loop {
             let c = self.peek_offset(scan_index, length);
-            if c.is_none() || !(c.unwrap().is_ascii_alphanumeric() && c.unwrap() != '_') {
+            if c.is_none() || !(c.unwrap().is_ascii_alphanumeric() || c.unwrap() == '_') {
                 break;
             }
             length += 1;
@@ -291,14 +424,14 @@ impl<'a> Scanner<'a> {
             Some(kind) => ScanResult::MultiCharLexeme(
                 length,
                 Token::Keyword {
-                    line: scan_index.line,
+                    span: scan_index.span(length),
                     token: *kind,
                 },
             ),
             None => ScanResult::MultiCharLexeme(
                 length,
                 Token::Literal {
-                    line: scan_index.line,
+                    span: scan_index.span(length),
                     token: Literal::Identifier {
                         size: length,
                         literal: self.literal(scan_index, length),
@@ -308,18 +441,13 @@ impl<'a> Scanner<'a> {
         }
     }
 
+    // the scanner works on bytes: every lexeme boundary is an ASCII character, so multi-byte
+    // UTF-8 sequences can only appear inside strings and comments, where they are skipped over.
     fn peek_offset(&self, scan_index: &ScanIndex, offset: usize) -> Option<char> {
-        return if scan_index.at_end(offset) {
+        if scan_index.at_end(offset) {
             None
         } else {
-            Some(
-                self.source
-                    .chars()
-                    .nth(scan_index.current + offset)
-                    .unwrap_or_else(|| {
-                        panic!("source out of bounds at {}", scan_index.current + offset)
-                    }),
-            )
-        };
+            Some(self.source.as_bytes()[scan_index.current + offset] as char)
+        }
     }
 }
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use crate::parser::{Expr, ExprKind, Function, Stmt, StmtKind};
+use crate::resolver::Locals;
+use crate::types::Span;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymbolKind {
+    Variable,
+    Parameter,
+    Function,
+    Class,
+    Method,
+}
+
+/// A declaration in a program and the places that refer to it.
+#[derive(Clone, Debug)]
+pub struct Symbol<'a> {
+    pub name: &'a str,
+    pub kind: SymbolKind,
+    /// Declared at the top level rather than in a block or function.
+    pub global: bool,
+    /// The declaring name.
+    pub span: Span,
+    /// The whole declaration, e.g. from `fun` to the closing brace.
+    pub declaration: Span,
+    /// The declaration's first line, e.g. `fun add(a, b)` or `class B < A`.
+    pub signature: String,
+    /// For methods, the index of their class.
+    pub container: Option<usize>,
+    /// Reads and assignments of the name that the resolver binds to this declaration.
+    pub references: Vec<Span>,
+}
+
+impl<'a> Symbol<'a> {
+    /// What the symbol is, in words: `global variable`, `parameter`, `method of Point`, ...
+    pub fn description(&self, symbols: &Symbols) -> String {
+        let scope = if self.global { "global" } else { "local" };
+        match self.kind {
+            SymbolKind::Variable => format!("{} variable", scope),
+            SymbolKind::Function if self.global => String::from("function"),
+            SymbolKind::Function => String::from("local function"),
+            SymbolKind::Class if self.global => String::from("class"),
+            SymbolKind::Class => String::from("local class"),
+            SymbolKind::Parameter => String::from("parameter"),
+            SymbolKind::Method => match self.container {
+                Some(class) => format!("method of {}", symbols.symbols[class].name),
+                None => String::from("method"),
+            },
+        }
+    }
+}
+
+/// Every declaration of a program with its references, for editor tooling. References follow
+/// the resolver: locals by scope distance, everything else by top-level name. Property
+/// accesses aren't resolved, so methods have no references.
+pub struct Symbols<'a> {
+    pub symbols: Vec<Symbol<'a>>,
+}
+
+impl<'a> Symbols<'a> {
+    pub fn new(statements: &[Stmt<'a>], locals: &Locals) -> Symbols<'a> {
+        let mut indexer = Indexer {
+            locals,
+            symbols: vec![],
+            globals: HashMap::new(),
+            scopes: vec![],
+        };
+        indexer.statements(statements);
+        Symbols {
+            symbols: indexer.symbols,
+        }
+    }
+
+    /// The symbol whose declaring name or one of whose references covers byte `offset`.
+    pub fn at(&self, offset: usize) -> Option<&Symbol<'a>> {
+        let covers = |span: &Span| span.start <= offset && offset <= span.end;
+        self.symbols
+            .iter()
+            .find(|symbol| covers(&symbol.span) || symbol.references.iter().any(covers))
+    }
+}
+
+struct Indexer<'a, 'l> {
+    locals: &'l Locals,
+    symbols: Vec<Symbol<'a>>,
+    // the first top-level declaration of each name.
+    globals: HashMap<&'a str, usize>,
+    // the resolver's scopes; `this` and `super` are there without a symbol.
+    scopes: Vec<Vec<(&'a str, Option<usize>)>>,
+}
+
+impl<'a, 'l> Indexer<'a, 'l> {
+    fn statements(&mut self, statements: &[Stmt<'a>]) {
+        // functions can refer to globals declared after them.
+        if self.scopes.is_empty() {
+            for statement in statements {
+                if let Some(index) = self.declaration(statement) {
+                    let name = self.symbols[index].name;
+                    self.globals.entry(name).or_insert(index);
+                }
+            }
+        }
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    // adds the symbol a statement declares, without visiting the statement.
+    fn declaration(&mut self, stmt: &Stmt<'a>) -> Option<usize> {
+        let (name, kind, signature) = match &stmt.kind {
+            StmtKind::Var { name, .. } => {
+                (name, SymbolKind::Variable, format!("var {}", name.lexeme))
+            }
+            StmtKind::Function(function) => (
+                &function.name,
+                SymbolKind::Function,
+                format!("fun {}", signature(function)),
+            ),
+            StmtKind::Class {
+                name, superclass, ..
+            } => {
+                let signature = match superclass.as_ref().map(|superclass| &superclass.kind) {
+                    Some(ExprKind::Variable(superclass)) => {
+                        format!("class {} < {}", name.lexeme, superclass)
+                    }
+                    _ => format!("class {}", name.lexeme),
+                };
+                (name, SymbolKind::Class, signature)
+            }
+            _ => return None,
+        };
+        Some(self.add(Symbol {
+            name: name.lexeme,
+            kind,
+            global: self.scopes.is_empty(),
+            span: name.span,
+            declaration: stmt.span,
+            signature,
+            container: None,
+            references: vec![],
+        }))
+    }
+
+    fn add(&mut self, symbol: Symbol<'a>) -> usize {
+        self.symbols.push(symbol);
+        self.symbols.len() - 1
+    }
+
+    // the symbol of a top-level declaration was added before the statements were visited.
+    fn declare(&mut self, stmt: &Stmt<'a>) -> Option<usize> {
+        match self.scopes.last_mut() {
+            Some(_) => {
+                let index = self.declaration(stmt)?;
+                let name = self.symbols[index].name;
+                self.scopes.last_mut().unwrap().push((name, Some(index)));
+                Some(index)
+            }
+            None => self
+                .symbols
+                .iter()
+                .position(|symbol| symbol.global && symbol.declaration == stmt.span),
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt<'a>) {
+        match &stmt.kind {
+            StmtKind::Expression(expr) | StmtKind::Print(expr) => self.expression(expr),
+            StmtKind::Var { initializer, .. } => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.declare(stmt);
+            }
+            StmtKind::Block(statements) => {
+                self.scopes.push(vec![]);
+                self.statements(statements);
+                self.scopes.pop();
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.expression(condition);
+                self.statement(body);
+            }
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                self.scopes.push(vec![]);
+                if let Some(initializer) = initializer {
+                    self.statement(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+                self.statement(body);
+                self.scopes.pop();
+            }
+            StmtKind::Function(function) => {
+                self.declare(stmt);
+                self.function(function);
+            }
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            StmtKind::Class {
+                superclass,
+                methods,
+                ..
+            } => {
+                let class = self.declare(stmt);
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                    self.scopes.push(vec![("super", None)]);
+                }
+                self.scopes.push(vec![("this", None)]);
+                for method in methods {
+                    self.add(Symbol {
+                        name: method.name.lexeme,
+                        kind: SymbolKind::Method,
+                        global: false,
+                        span: method.name.span,
+                        declaration: method.span,
+                        signature: signature(method),
+                        container: class,
+                        references: vec![],
+                    });
+                    self.function(method);
+                }
+                self.scopes.pop();
+                if superclass.is_some() {
+                    self.scopes.pop();
+                }
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Function<'a>) {
+        let mut scope = vec![];
+        for param in &function.params {
+            let index = self.add(Symbol {
+                name: param.lexeme,
+                kind: SymbolKind::Parameter,
+                global: false,
+                span: param.span,
+                declaration: param.span,
+                signature: param.lexeme.to_string(),
+                container: None,
+                references: vec![],
+            });
+            scope.push((param.lexeme, Some(index)));
+        }
+        self.scopes.push(scope);
+        self.statements(&function.body);
+        self.scopes.pop();
+    }
+
+    fn expression(&mut self, expr: &Expr<'a>) {
+        match &expr.kind {
+            ExprKind::Literal(_) | ExprKind::This | ExprKind::Super(_) => {}
+            ExprKind::Variable(name) => self.reference(expr, name, expr.span),
+            ExprKind::Assign(name, value) => {
+                self.expression(value);
+                self.reference(expr, name.lexeme, name.span);
+            }
+            ExprKind::Binary(left, _, right) | ExprKind::Logical(left, _, right) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            ExprKind::Grouping(inner) | ExprKind::Unary(_, inner) => self.expression(inner),
+            ExprKind::Call(callee, arguments) => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            ExprKind::Get(object, _) => self.expression(object),
+            ExprKind::Set(object, _, value) => {
+                self.expression(value);
+                self.expression(object);
+            }
+        }
+    }
+
+    fn reference(&mut self, expr: &Expr, name: &str, span: Span) {
+        let symbol = match self.locals.get(&expr.id) {
+            Some(depth) => self
+                .scopes
+                .len()
+                .checked_sub(depth + 1)
+                .and_then(|index| {
+                    self.scopes[index]
+                        .iter()
+                        .rev()
+                        .find(|(declared, _)| *declared == name)
+                })
+                .and_then(|(_, symbol)| *symbol),
+            None => self.globals.get(name).copied(),
+        };
+        if let Some(symbol) = symbol {
+            self.symbols[symbol].references.push(span);
+        }
+    }
+}
+
+fn signature(function: &Function) -> String {
+    let params: Vec<&str> = function.params.iter().map(|param| param.lexeme).collect();
+    format!("{}({})", function.name.lexeme, params.join(", "))
+}
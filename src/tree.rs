@@ -0,0 +1,189 @@
+use crate::parser::{Expr, ExprKind, Function, LiteralValue, Stmt, StmtKind};
+use crate::types::Span;
+
+/// A uniform view of the syntax tree for tools that render or export it: every node has a
+/// type, a span, scalar attributes and children labelled by the role they play in the parent.
+pub struct Node<'a> {
+    pub kind: &'static str,
+    pub span: Span,
+    pub attributes: Vec<(&'static str, Attribute<'a>)>,
+    pub children: Vec<(&'static str, Node<'a>)>,
+}
+
+pub enum Attribute<'a> {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(&'a str),
+    List(Vec<&'a str>),
+}
+
+impl<'a> Node<'a> {
+    fn new(kind: &'static str, span: Span) -> Node<'a> {
+        Node {
+            kind,
+            span,
+            attributes: vec![],
+            children: vec![],
+        }
+    }
+
+    fn attribute(mut self, name: &'static str, value: Attribute<'a>) -> Node<'a> {
+        self.attributes.push((name, value));
+        self
+    }
+
+    fn child(mut self, role: &'static str, node: Node<'a>) -> Node<'a> {
+        self.children.push((role, node));
+        self
+    }
+
+    fn optional_child(self, role: &'static str, node: Option<Node<'a>>) -> Node<'a> {
+        match node {
+            Some(node) => self.child(role, node),
+            None => self,
+        }
+    }
+
+    fn statements(mut self, role: &'static str, statements: &'a [Stmt<'a>]) -> Node<'a> {
+        for statement in statements {
+            self.children.push((role, Node::from_stmt(statement)));
+        }
+        self
+    }
+
+    pub fn program(statements: &'a [Stmt<'a>]) -> Node<'a> {
+        let span = match (statements.first(), statements.last()) {
+            (Some(first), Some(last)) => first.span.to(last.span),
+            _ => Span::default(),
+        };
+        Node::new("Program", span).statements("statement", statements)
+    }
+
+    pub fn from_stmt(stmt: &'a Stmt<'a>) -> Node<'a> {
+        let span = stmt.span;
+        match &stmt.kind {
+            StmtKind::Expression(expr) => {
+                Node::new("Expression", span).child("expression", Node::from_expr(expr))
+            }
+            StmtKind::Print(expr) => {
+                Node::new("Print", span).child("expression", Node::from_expr(expr))
+            }
+            StmtKind::Var { name, initializer } => Node::new("Var", span)
+                .attribute("name", Attribute::String(name.lexeme))
+                .optional_child("initializer", initializer.as_ref().map(Node::from_expr)),
+            StmtKind::Block(statements) => {
+                Node::new("Block", span).statements("statement", statements)
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => Node::new("If", span)
+                .child("condition", Node::from_expr(condition))
+                .child("then", Node::from_stmt(then_branch))
+                .optional_child("else", else_branch.as_deref().map(Node::from_stmt)),
+            StmtKind::While { condition, body } => Node::new("While", span)
+                .child("condition", Node::from_expr(condition))
+                .child("body", Node::from_stmt(body)),
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => Node::new("For", span)
+                .optional_child("initializer", initializer.as_deref().map(Node::from_stmt))
+                .optional_child("condition", condition.as_ref().map(Node::from_expr))
+                .optional_child("increment", increment.as_ref().map(Node::from_expr))
+                .child("body", Node::from_stmt(body)),
+            // the statement's span also covers the `fun` keyword.
+            StmtKind::Function(function) => Node {
+                span,
+                ..Node::function(function)
+            },
+            StmtKind::Return(value) => Node::new("Return", span)
+                .optional_child("value", value.as_ref().map(Node::from_expr)),
+            StmtKind::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass.as_ref().map(|superclass| &superclass.kind) {
+                    Some(ExprKind::Variable(superclass)) => Attribute::String(superclass),
+                    _ => Attribute::Nil,
+                };
+                let mut node = Node::new("Class", span)
+                    .attribute("name", Attribute::String(name.lexeme))
+                    .attribute("superclass", superclass);
+                for method in methods {
+                    node = node.child("method", Node::function(method));
+                }
+                node
+            }
+        }
+    }
+
+    fn function(function: &'a Function<'a>) -> Node<'a> {
+        Node::new("Function", function.span)
+            .attribute("name", Attribute::String(function.name.lexeme))
+            .attribute(
+                "params",
+                Attribute::List(function.params.iter().map(|param| param.lexeme).collect()),
+            )
+            .statements("body", &function.body)
+    }
+
+    pub fn from_expr(expr: &'a Expr<'a>) -> Node<'a> {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Literal(value) => {
+                let value = match value {
+                    LiteralValue::Nil => Attribute::Nil,
+                    LiteralValue::True => Attribute::Bool(true),
+                    LiteralValue::False => Attribute::Bool(false),
+                    LiteralValue::Number(number) => Attribute::Number(*number),
+                    LiteralValue::String(string) => Attribute::String(string),
+                };
+                Node::new("Literal", span).attribute("value", value)
+            }
+            ExprKind::Variable(name) => {
+                Node::new("Variable", span).attribute("name", Attribute::String(name))
+            }
+            ExprKind::Assign(name, value) => Node::new("Assign", span)
+                .attribute("name", Attribute::String(name.lexeme))
+                .child("value", Node::from_expr(value)),
+            ExprKind::Binary(left, operator, right) => Node::new("Binary", span)
+                .attribute("operator", Attribute::String(operator.as_str()))
+                .child("left", Node::from_expr(left))
+                .child("right", Node::from_expr(right)),
+            ExprKind::Logical(left, operator, right) => Node::new("Logical", span)
+                .attribute("operator", Attribute::String(operator.as_str()))
+                .child("left", Node::from_expr(left))
+                .child("right", Node::from_expr(right)),
+            ExprKind::Grouping(expr) => {
+                Node::new("Grouping", span).child("expression", Node::from_expr(expr))
+            }
+            ExprKind::Unary(operator, operand) => Node::new("Unary", span)
+                .attribute("operator", Attribute::String(operator.as_str()))
+                .child("operand", Node::from_expr(operand)),
+            ExprKind::Call(callee, arguments) => {
+                let mut node = Node::new("Call", span).child("callee", Node::from_expr(callee));
+                for argument in arguments {
+                    node = node.child("argument", Node::from_expr(argument));
+                }
+                node
+            }
+            ExprKind::Get(object, name) => Node::new("Get", span)
+                .attribute("name", Attribute::String(name.lexeme))
+                .child("object", Node::from_expr(object)),
+            ExprKind::Set(object, name, value) => Node::new("Set", span)
+                .attribute("name", Attribute::String(name.lexeme))
+                .child("object", Node::from_expr(object))
+                .child("value", Node::from_expr(value)),
+            ExprKind::This => Node::new("This", span),
+            ExprKind::Super(method) => {
+                Node::new("Super", span).attribute("method", Attribute::String(method.lexeme))
+            }
+        }
+    }
+}
@@ -50,7 +50,7 @@ impl From<&str> for Keyword {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Operator {
     Minus,
     Plus,
@@ -66,7 +66,7 @@ pub enum Operator {
     Bang,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Grouping {
     LeftParen,
     RightParen,
@@ -74,14 +74,14 @@ pub enum Grouping {
     RightBrace,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Misc {
     Comma,
     Dot,
     Semicolon,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Keyword {
     And,
     Class,
@@ -101,6 +101,13 @@ pub enum Keyword {
     While,
 }
 
+/// Source text that means nothing to the parser. The scanner only produces it on request.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trivia {
+    Whitespace,
+    Comment,
+}
+
 #[derive(Debug)]
 pub enum Literal<'a> {
     Identifier { size: usize, literal: &'a str },
@@ -108,14 +115,112 @@ pub enum Literal<'a> {
     Number { literal: f64 },
 }
 
+/// Location of a piece of source text: `start..end` is a byte range into the source,
+/// `line` is the 1-based line on which it starts.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+impl Span {
+    /// Smallest span covering both `self` and `other`.
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+            line: self.line.min(other.line),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Token<'a> {
-    Operator { line: usize, token: Operator },
-    Grouping { line: usize, token: Grouping },
-    Misc { line: usize, token: Misc },
-    Literal { line: usize, token: Literal<'a> },
-    Keyword { line: usize, token: Keyword },
-    Eof { line: usize },
+    Operator { span: Span, token: Operator },
+    Grouping { span: Span, token: Grouping },
+    Misc { span: Span, token: Misc },
+    Literal { span: Span, token: Literal<'a> },
+    Keyword { span: Span, token: Keyword },
+    Trivia { span: Span, token: Trivia },
+    Eof { span: Span },
+}
+
+impl<'a> Token<'a> {
+    pub fn span(&self) -> Span {
+        match self {
+            Token::Operator { span, .. }
+            | Token::Grouping { span, .. }
+            | Token::Misc { span, .. }
+            | Token::Literal { span, .. }
+            | Token::Keyword { span, .. }
+            | Token::Trivia { span, .. }
+            | Token::Eof { span } => *span,
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        self.span().line
+    }
+
+    /// The token type's name from the book, e.g. `LEFT_PAREN`, as used by the json export.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Token::Grouping { token, .. } => match token {
+                Grouping::LeftParen => "LEFT_PAREN",
+                Grouping::RightParen => "RIGHT_PAREN",
+                Grouping::LeftBrace => "LEFT_BRACE",
+                Grouping::RightBrace => "RIGHT_BRACE",
+            },
+            Token::Misc { token, .. } => match token {
+                Misc::Comma => "COMMA",
+                Misc::Dot => "DOT",
+                Misc::Semicolon => "SEMICOLON",
+            },
+            Token::Operator { token, .. } => match token {
+                Operator::Minus => "MINUS",
+                Operator::Plus => "PLUS",
+                Operator::Star => "STAR",
+                Operator::Equal => "EQUAL",
+                Operator::EqualEqual => "EQUAL_EQUAL",
+                Operator::Greater => "GREATER",
+                Operator::Less => "LESS",
+                Operator::Slash => "SLASH",
+                Operator::BangEqual => "BANG_EQUAL",
+                Operator::GreaterEqual => "GREATER_EQUAL",
+                Operator::LessEqual => "LESS_EQUAL",
+                Operator::Bang => "BANG",
+            },
+            Token::Literal { token, .. } => match token {
+                Literal::Identifier { .. } => "IDENTIFIER",
+                Literal::String { .. } => "STRING",
+                Literal::Number { .. } => "NUMBER",
+            },
+            Token::Keyword { token, .. } => match token {
+                Keyword::And => "AND",
+                Keyword::Class => "CLASS",
+                Keyword::Else => "ELSE",
+                Keyword::False => "FALSE",
+                Keyword::Fun => "FUN",
+                Keyword::For => "FOR",
+                Keyword::If => "IF",
+                Keyword::Nil => "NIL",
+                Keyword::Or => "OR",
+                Keyword::Print => "PRINT",
+                Keyword::Return => "RETURN",
+                Keyword::Super => "SUPER",
+                Keyword::This => "THIS",
+                Keyword::True => "TRUE",
+                Keyword::Var => "VAR",
+                Keyword::While => "WHILE",
+            },
+            Token::Trivia { token, .. } => match token {
+                Trivia::Whitespace => "WHITESPACE",
+                Trivia::Comment => "COMMENT",
+            },
+            Token::Eof { .. } => "EOF",
+        }
+    }
 }
 
 impl<'a> fmt::Display for Token<'a> {
@@ -126,7 +231,7 @@ impl<'a> fmt::Display for Token<'a> {
             Token::Misc { token, .. } => write!(f, "{}", token),
             Token::Literal { token, .. } => write!(f, "{}", token),
             Token::Keyword { token, .. } => write!(f, "{}", token),
-            Token::Eof { .. } => Ok(()),
+            Token::Trivia { .. } | Token::Eof { .. } => Ok(()),
         }
     }
 }
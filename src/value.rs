@@ -0,0 +1,125 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::parser::Function;
+
+#[derive(Clone)]
+pub enum Value<'a> {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<LoxFunction<'a>>),
+    Native(Rc<NativeFunction<'a>>),
+    Class(Rc<LoxClass<'a>>),
+    Instance(Rc<RefCell<LoxInstance<'a>>>),
+}
+
+pub struct LoxFunction<'a> {
+    pub declaration: Rc<Function<'a>>,
+    pub closure: Rc<RefCell<Environment<'a>>>,
+    pub is_initializer: bool,
+}
+
+pub type NativeFn<'a> = dyn Fn(&[Value<'a>]) -> Result<Value<'a>, String> + 'a;
+
+pub struct NativeFunction<'a> {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: Box<NativeFn<'a>>,
+}
+
+pub struct LoxClass<'a> {
+    pub name: &'a str,
+    pub superclass: Option<Rc<LoxClass<'a>>>,
+    pub methods: HashMap<&'a str, Rc<LoxFunction<'a>>>,
+}
+
+pub struct LoxInstance<'a> {
+    pub class: Rc<LoxClass<'a>>,
+    pub fields: HashMap<&'a str, Value<'a>>,
+}
+
+impl<'a> Value<'a> {
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+}
+
+impl<'a> PartialEq for Value<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> LoxFunction<'a> {
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    /// A copy of this method whose closure defines `this` as `instance`.
+    pub fn bind(&self, instance: Value<'a>) -> LoxFunction<'a> {
+        let mut environment = Environment::new(Some(Rc::clone(&self.closure)));
+        environment.define("this", instance);
+        LoxFunction {
+            declaration: Rc::clone(&self.declaration),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+impl<'a> LoxClass<'a> {
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction<'a>>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    /// A class is called with the arguments of its initializer, if it has one.
+    pub fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+}
+
+impl<'a> fmt::Display for Value<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Function(function) => write!(f, "<fn {}>", function.declaration.name.lexeme),
+            Value::Native(_) => write!(f, "<native fn>"),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for Value<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(value) => write!(f, "{:?}", value),
+            value => write!(f, "{}", value),
+        }
+    }
+}
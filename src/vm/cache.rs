@@ -0,0 +1,65 @@
+use std::cell::Cell;
+
+use super::object::ObjRef;
+
+// how many classes a cache remembers; sites that see more are megamorphic, and instances of
+// the classes after these are always looked up by name.
+const POLYMORPHIC: usize = 4;
+
+/// Where a property instruction found a property on an instance of some class.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lookup {
+    /// A field, in this bucket of the instance's fields. Instances of a class usually get
+    /// their fields in the same order, from its initializer, so they have them in the same
+    /// buckets; an instance that doesn't is looked up by name.
+    Field(usize),
+    /// A method of the class. Used unless the instance has a field with the method's name.
+    Method(ObjRef),
+}
+
+/// The inline cache of one property instruction in a chunk: what it found for the classes of
+/// the instances it last ran on, so that running on another instance of one of them doesn't
+/// need to look the property up by name. The cache remembers up to four classes, which is
+/// enough for most sites: one class at monomorphic sites, a few at polymorphic ones.
+///
+/// The cache is emptied when the VM's classes change, which they do at each new `epoch`: a
+/// class's methods may have changed, and a new class may have the handle of a freed one.
+/// Fields are only guesses, checked whenever they're used: they must still be in the bucket.
+#[derive(Default)]
+pub struct InlineCache {
+    epoch: Cell<u64>,
+    // filled in order, and only emptied all at once.
+    entries: [Cell<Option<(ObjRef, Lookup)>>; POLYMORPHIC],
+}
+
+impl InlineCache {
+    /// What the instruction found on an instance of `class` last time, if that was in the
+    /// same `epoch`.
+    pub fn lookup(&self, class: ObjRef, epoch: u64) -> Option<Lookup> {
+        if self.epoch.get() != epoch {
+            return None;
+        }
+        self.entries.iter().find_map(|entry| match entry.get() {
+            Some((cached, lookup)) if cached == class => Some(lookup),
+            _ => None,
+        })
+    }
+
+    /// Remembers what the instruction found on an instance of `class`, unless the cache is
+    /// already full of other classes from the same `epoch`.
+    pub fn update(&self, class: ObjRef, lookup: Lookup, epoch: u64) {
+        if self.epoch.get() != epoch {
+            self.epoch.set(epoch);
+            for entry in &self.entries {
+                entry.set(None);
+            }
+        }
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.get().is_none_or(|(cached, _)| cached == class));
+        if let Some(entry) = entry {
+            entry.set(Some((class, lookup)));
+        }
+    }
+}
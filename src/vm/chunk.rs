@@ -0,0 +1,254 @@
+use super::cache::InlineCache;
+use super::value::Value;
+
+/// One-byte instructions, each followed by the operands noted here. `const` operands are
+/// indices into the chunk's constant pool and `cache` ones into its inline caches; jump
+/// offsets and `cache` operands are two bytes, big-endian.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum OpCode {
+    /// `const`: push a constant.
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    /// `slot`: push a local of the current frame.
+    GetLocal,
+    /// `slot`: store the top of the stack in a local, leaving it on the stack.
+    SetLocal,
+    /// `const` (name)
+    GetGlobal,
+    /// `const` (name): define a global as the value popped off the stack.
+    DefineGlobal,
+    /// `const` (name)
+    SetGlobal,
+    /// `index`: push the value of one of the current closure's upvalues.
+    GetUpvalue,
+    /// `index`: store the top of the stack in an upvalue, leaving it on the stack.
+    SetUpvalue,
+    /// `const` (name), `cache`: replace an instance with the value of one of its properties.
+    GetProperty,
+    /// `const` (name), `cache`: pop a value and an instance, set the field, and push the value.
+    SetProperty,
+    /// Fail unless the top of the stack is an instance, as setting one of its fields would: a
+    /// set checks its instance before its value runs.
+    CheckInstance,
+    /// `const` (name): pop a superclass and bind its method to the instance below it.
+    GetSuper,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    /// `offset`: jump forward.
+    Jump,
+    /// `offset`: jump forward if the top of the stack is falsey, leaving it there.
+    JumpIfFalse,
+    /// `offset`: jump backward.
+    Loop,
+    /// `count`: call the value below `count` arguments.
+    Call,
+    /// `const` (name), `cache`: look up a property of the instance on top of the stack for an
+    /// `Invoke`, before its arguments run. Push a method, leaving the instance below it as the
+    /// receiver, or replace the instance with a field and push nil.
+    GetCallee,
+    /// `const` (name): pop a superclass and push its method for an `Invoke`, with the instance
+    /// below it as the receiver.
+    GetSuperCallee,
+    /// `count`: call what a `GetCallee` or `GetSuperCallee` below `count` arguments found.
+    /// Methods are called with the instance as the receiver, without binding them.
+    Invoke,
+    /// `const` (function), then `is_local`, `index` for each upvalue: push a new closure over
+    /// the function, capturing a local of the current frame or one of its upvalues.
+    Closure,
+    /// Move the local on top of the stack into the upvalues that capture it, and pop it.
+    CloseUpvalue,
+    Return,
+    /// `const` (name): push a new class.
+    Class,
+    /// Copy the methods of a superclass into the class above it, and pop the class.
+    Inherit,
+    /// `const` (name): pop a closure and add it to the class below it as a method.
+    Method,
+    // superinstructions: pairs of instructions that run often, as `rlox run --profile` shows,
+    // fused into one.
+    /// `slot`, `const` (name), `cache`: `GetLocal` then `GetProperty`, as in `this.name`.
+    GetLocalProperty,
+    /// `const` (name), `cache`: `SetProperty` then `Pop`, as in `object.name = value;`.
+    SetPropertyPop,
+    /// `const`: `Constant` then `Add`.
+    AddConstant,
+    /// `const`: `Constant` then `Subtract`.
+    SubtractConstant,
+    /// `const`: `Constant` then `Less`.
+    LessConstant,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 47] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::CheckInstance,
+        OpCode::GetSuper,
+        OpCode::Equal,
+        OpCode::NotEqual,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::GetCallee,
+        OpCode::GetSuperCallee,
+        OpCode::Invoke,
+        OpCode::Closure,
+        OpCode::CloseUpvalue,
+        OpCode::Return,
+        OpCode::Class,
+        OpCode::Inherit,
+        OpCode::Method,
+        OpCode::GetLocalProperty,
+        OpCode::SetPropertyPop,
+        OpCode::AddConstant,
+        OpCode::SubtractConstant,
+        OpCode::LessConstant,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL
+            .get(byte as usize)
+            .copied()
+            .filter(|op| *op as u8 == byte)
+    }
+
+    /// The instruction's name in listings, as clox spells it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpCode::Constant => "OP_CONSTANT",
+            OpCode::Nil => "OP_NIL",
+            OpCode::True => "OP_TRUE",
+            OpCode::False => "OP_FALSE",
+            OpCode::Pop => "OP_POP",
+            OpCode::GetLocal => "OP_GET_LOCAL",
+            OpCode::SetLocal => "OP_SET_LOCAL",
+            OpCode::GetGlobal => "OP_GET_GLOBAL",
+            OpCode::DefineGlobal => "OP_DEFINE_GLOBAL",
+            OpCode::SetGlobal => "OP_SET_GLOBAL",
+            OpCode::GetUpvalue => "OP_GET_UPVALUE",
+            OpCode::SetUpvalue => "OP_SET_UPVALUE",
+            OpCode::GetProperty => "OP_GET_PROPERTY",
+            OpCode::SetProperty => "OP_SET_PROPERTY",
+            OpCode::CheckInstance => "OP_CHECK_INSTANCE",
+            OpCode::GetSuper => "OP_GET_SUPER",
+            OpCode::Equal => "OP_EQUAL",
+            OpCode::NotEqual => "OP_NOT_EQUAL",
+            OpCode::Greater => "OP_GREATER",
+            OpCode::GreaterEqual => "OP_GREATER_EQUAL",
+            OpCode::Less => "OP_LESS",
+            OpCode::LessEqual => "OP_LESS_EQUAL",
+            OpCode::Add => "OP_ADD",
+            OpCode::Subtract => "OP_SUBTRACT",
+            OpCode::Multiply => "OP_MULTIPLY",
+            OpCode::Divide => "OP_DIVIDE",
+            OpCode::Not => "OP_NOT",
+            OpCode::Negate => "OP_NEGATE",
+            OpCode::Print => "OP_PRINT",
+            OpCode::Jump => "OP_JUMP",
+            OpCode::JumpIfFalse => "OP_JUMP_IF_FALSE",
+            OpCode::Loop => "OP_LOOP",
+            OpCode::Call => "OP_CALL",
+            OpCode::GetCallee => "OP_GET_CALLEE",
+            OpCode::GetSuperCallee => "OP_GET_SUPER_CALLEE",
+            OpCode::Invoke => "OP_INVOKE",
+            OpCode::Closure => "OP_CLOSURE",
+            OpCode::CloseUpvalue => "OP_CLOSE_UPVALUE",
+            OpCode::Return => "OP_RETURN",
+            OpCode::Class => "OP_CLASS",
+            OpCode::Inherit => "OP_INHERIT",
+            OpCode::Method => "OP_METHOD",
+            OpCode::GetLocalProperty => "OP_GET_LOCAL_PROPERTY",
+            OpCode::SetPropertyPop => "OP_SET_PROPERTY_POP",
+            OpCode::AddConstant => "OP_ADD_CONSTANT",
+            OpCode::SubtractConstant => "OP_SUBTRACT_CONSTANT",
+            OpCode::LessConstant => "OP_LESS_CONSTANT",
+        }
+    }
+}
+
+/// A compiled function body: its bytecode, the constants it refers to, the inline caches of
+/// its property instructions and the source line of each instruction.
+#[derive(Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    pub caches: Vec<InlineCache>,
+    // runs of instructions on the same line: (offset of the run's first byte, line).
+    lines: Vec<(usize, usize)>,
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk::default()
+    }
+
+    pub fn write(&mut self, byte: u8, line: usize) {
+        if self.lines.last().map(|(_, last)| *last) != Some(line) {
+            self.lines.push((self.code.len(), line));
+        }
+        self.code.push(byte);
+    }
+
+    /// Adds a constant and returns its index.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// Adds an empty inline cache and returns its index.
+    pub fn add_cache(&mut self) -> usize {
+        self.caches.push(InlineCache::default());
+        self.caches.len() - 1
+    }
+
+    /// The source line of the instruction byte at `offset`.
+    pub fn line(&self, offset: usize) -> usize {
+        let run = match self
+            .lines
+            .binary_search_by_key(&offset, |(start, _)| *start)
+        {
+            Ok(run) => run,
+            Err(next) => next.saturating_sub(1),
+        };
+        self.lines.get(run).map_or(0, |(_, line)| *line)
+    }
+}
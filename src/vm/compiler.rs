@@ -0,0 +1,703 @@
+use std::rc::Rc;
+
+use super::chunk::{Chunk, OpCode};
+use super::object::{Function, Obj, ObjRef};
+use super::value::Value;
+use super::Vm;
+use crate::lox::Lox;
+use crate::parser::{
+    self, BinaryOp, Expr, ExprKind, LiteralValue, LogicalOp, Name, Stmt, StmtKind, UnaryOp,
+};
+use crate::types::Span;
+
+// operands are single bytes.
+const MAX_SLOTS: usize = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local<'a> {
+    name: &'a str,
+    depth: usize,
+    // whether a closure captures the variable, so that it has to be closed when its scope ends.
+    is_captured: bool,
+}
+
+// a variable of the enclosing function, or one of its upvalues, as the `Closure` instruction
+// captures it.
+#[derive(Clone, Copy, PartialEq)]
+struct Capture {
+    is_local: bool,
+    index: u8,
+}
+
+// the function being compiled, one per level of nesting.
+struct FunctionState<'a> {
+    name: Option<ObjRef>,
+    arity: usize,
+    kind: FunctionKind,
+    chunk: Chunk,
+    // the frame's stack slots: slot 0 holds the callee, or `this` in methods.
+    locals: Vec<Local<'a>>,
+    captures: Vec<Capture>,
+    scope_depth: usize,
+}
+
+impl<'a> FunctionState<'a> {
+    fn new(name: Option<ObjRef>, arity: usize, kind: FunctionKind) -> FunctionState<'a> {
+        let receiver = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+        FunctionState {
+            name,
+            arity,
+            kind,
+            chunk: Chunk::new(),
+            locals: vec![Local {
+                name: receiver,
+                depth: 0,
+                is_captured: false,
+            }],
+            captures: vec![],
+            scope_depth: 0,
+        }
+    }
+}
+
+/// Compiles a resolved program into the function `vm` runs as its top-level script, allocating
+/// its objects on the VM's heap. Errors are reported to `lox`; the function is only meant to be
+/// run if there were none.
+///
+/// Variables are resolved by name the way the resolver does it, as stack slots of the
+/// enclosing function, upvalues captured from outer functions, or globals.
+pub fn compile(statements: &[Stmt], vm: &mut Vm, lox: &mut Lox) -> ObjRef {
+    let mut compiler = Compiler {
+        optimize: lox.optimizing(),
+        vm,
+        lox,
+        functions: vec![FunctionState::new(None, 0, FunctionKind::Script)],
+    };
+    compiler.statements(statements);
+    let line = statements.last().map_or(1, |statement| statement.span.line);
+    compiler.end_function(line).0
+}
+
+struct Compiler<'a, 'h, 'l> {
+    // whether to emit the fused instructions where they apply.
+    optimize: bool,
+    vm: &'h mut Vm,
+    lox: &'l mut Lox,
+    functions: Vec<FunctionState<'a>>,
+}
+
+impl<'a, 'h, 'l> Compiler<'a, 'h, 'l> {
+    fn current(&mut self) -> &mut FunctionState<'a> {
+        self.functions
+            .last_mut()
+            .expect("a function is being compiled")
+    }
+
+    // the functions being compiled and their constants, which collections mustn't free.
+    fn roots(&self) -> Vec<Value> {
+        let mut roots = vec![];
+        for function in &self.functions {
+            roots.extend(function.name.map(Value::obj));
+            roots.extend(&function.chunk.constants);
+        }
+        roots
+    }
+
+    fn alloc(&mut self, obj: Obj) -> ObjRef {
+        if self.vm.heap.should_collect() {
+            let roots = self.roots();
+            self.vm.collect_garbage(&roots, Some(&obj));
+        }
+        self.vm.heap.alloc(obj)
+    }
+
+    fn intern(&mut self, string: &str) -> ObjRef {
+        if self.vm.heap.should_collect() {
+            let roots = self.roots();
+            self.vm.collect_garbage(&roots, None);
+        }
+        self.vm.heap.intern(string)
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current().chunk
+    }
+
+    fn emit(&mut self, byte: u8, line: usize) {
+        self.chunk().write(byte, line);
+    }
+
+    fn emit_op(&mut self, op: OpCode, line: usize) {
+        self.emit(op as u8, line);
+    }
+
+    fn emit_with(&mut self, op: OpCode, operand: u8, line: usize) {
+        self.emit_op(op, line);
+        self.emit(operand, line);
+    }
+
+    // emits a property instruction with its name and an inline cache of its own.
+    fn emit_property(&mut self, op: OpCode, name: &Name, line: usize) {
+        self.emit_op(op, line);
+        self.emit_property_operands(name, line);
+    }
+
+    fn emit_property_operands(&mut self, name: &Name, line: usize) {
+        let constant = self.identifier_constant(name.lexeme, name.span);
+        self.emit(constant, line);
+        let cache = self.chunk().add_cache();
+        if cache > u16::MAX as usize {
+            self.lox
+                .error_in(name.span, "Too many property accesses in one chunk.");
+        }
+        self.emit((cache >> 8) as u8, line);
+        self.emit(cache as u8, line);
+    }
+
+    // emits a forward jump with a placeholder offset, returning where to patch it.
+    fn emit_jump(&mut self, op: OpCode, line: usize) -> usize {
+        self.emit_op(op, line);
+        self.emit(0xff, line);
+        self.emit(0xff, line);
+        self.chunk().code.len() - 2
+    }
+
+    // points a forward jump at the next instruction.
+    fn patch_jump(&mut self, offset: usize, span: Span) {
+        let jump = self.chunk().code.len() - offset - 2;
+        if jump > u16::MAX as usize {
+            self.lox.error_in(span, "Too much code to jump over.");
+        }
+        let code = &mut self.chunk().code;
+        code[offset] = (jump >> 8) as u8;
+        code[offset + 1] = jump as u8;
+    }
+
+    fn emit_loop(&mut self, start: usize, span: Span) {
+        self.emit_op(OpCode::Loop, span.line);
+        let offset = self.chunk().code.len() - start + 2;
+        if offset > u16::MAX as usize {
+            self.lox.error_in(span, "Loop body too large.");
+        }
+        self.emit((offset >> 8) as u8, span.line);
+        self.emit(offset as u8, span.line);
+    }
+
+    fn make_constant(&mut self, value: Value, span: Span) -> u8 {
+        // numbers are the same constant when their bits are: `0` and `-0` are equal, but they
+        // print differently.
+        let same = |constant: &Value| match (constant.as_number(), value.as_number()) {
+            (Some(constant), Some(value)) => constant.to_bits() == value.to_bits(),
+            _ => *constant == value,
+        };
+        let constants = &self.chunk().constants;
+        if let Some(index) = constants.iter().position(same) {
+            return index as u8;
+        }
+        if constants.len() == MAX_SLOTS {
+            self.lox.error_in(span, "Too many constants in one chunk.");
+            return 0;
+        }
+        self.chunk().add_constant(value) as u8
+    }
+
+    fn identifier_constant(&mut self, name: &str, span: Span) -> u8 {
+        let string = self.intern(name);
+        self.make_constant(Value::obj(string), span)
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self, line: usize) {
+        let function = self.current();
+        function.scope_depth -= 1;
+        let depth = function.scope_depth;
+        while self
+            .current()
+            .locals
+            .last()
+            .is_some_and(|local| local.depth > depth)
+        {
+            let local = self.current().locals.pop().expect("a local is in scope");
+            if local.is_captured {
+                self.emit_op(OpCode::CloseUpvalue, line);
+            } else {
+                self.emit_op(OpCode::Pop, line);
+            }
+        }
+    }
+
+    // the value on top of the stack becomes the variable `name` of the current scope.
+    fn add_local(&mut self, name: &'a str, span: Span) {
+        if self.current().locals.len() == MAX_SLOTS {
+            self.lox
+                .error_in(span, "Too many local variables in function.");
+            return;
+        }
+        let function = self.current();
+        let depth = function.scope_depth;
+        function.locals.push(Local {
+            name,
+            depth,
+            is_captured: false,
+        });
+    }
+
+    // declares a variable whose value is about to be pushed: a local in a block or function,
+    // otherwise a global defined by `define_global` once the value is there.
+    fn declare(&mut self, name: &Name<'a>) {
+        if self.current().scope_depth > 0 {
+            self.add_local(name.lexeme, name.span);
+        }
+    }
+
+    fn define_global(&mut self, name: &Name<'a>, line: usize) {
+        if self.current().scope_depth == 0 {
+            let constant = self.identifier_constant(name.lexeme, name.span);
+            self.emit_with(OpCode::DefineGlobal, constant, line);
+        }
+    }
+
+    fn resolve_local(&self, function: usize, name: &str) -> Option<u8> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    // a variable of an enclosing function, captured by `function` and every function between.
+    fn resolve_capture(&mut self, function: usize, name: &str, span: Span) -> Option<u8> {
+        if function == 0 {
+            return None;
+        }
+        if let Some(slot) = self.resolve_local(function - 1, name) {
+            self.functions[function - 1].locals[slot as usize].is_captured = true;
+            return Some(self.add_capture(function, true, slot, span));
+        }
+        let index = self.resolve_capture(function - 1, name, span)?;
+        Some(self.add_capture(function, false, index, span))
+    }
+
+    fn add_capture(&mut self, function: usize, is_local: bool, index: u8, span: Span) -> u8 {
+        let capture = Capture { is_local, index };
+        let captures = &mut self.functions[function].captures;
+        if let Some(existing) = captures.iter().position(|c| *c == capture) {
+            return existing as u8;
+        }
+        if captures.len() == MAX_SLOTS {
+            self.lox
+                .error_in(span, "Too many closure variables in function.");
+            return 0;
+        }
+        captures.push(capture);
+        (captures.len() - 1) as u8
+    }
+
+    fn get_variable(&mut self, name: &str, span: Span, line: usize) {
+        let function = self.functions.len() - 1;
+        if let Some(slot) = self.resolve_local(function, name) {
+            self.emit_with(OpCode::GetLocal, slot, line);
+        } else if let Some(index) = self.resolve_capture(function, name, span) {
+            self.emit_with(OpCode::GetUpvalue, index, line);
+        } else {
+            let constant = self.identifier_constant(name, span);
+            self.emit_with(OpCode::GetGlobal, constant, line);
+        }
+    }
+
+    fn set_variable(&mut self, name: &str, span: Span, line: usize) {
+        let function = self.functions.len() - 1;
+        if let Some(slot) = self.resolve_local(function, name) {
+            self.emit_with(OpCode::SetLocal, slot, line);
+        } else if let Some(index) = self.resolve_capture(function, name, span) {
+            self.emit_with(OpCode::SetUpvalue, index, line);
+        } else {
+            let constant = self.identifier_constant(name, span);
+            self.emit_with(OpCode::SetGlobal, constant, line);
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt<'a>]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt<'a>) {
+        let line = stmt.span.line;
+        match &stmt.kind {
+            StmtKind::Expression(Expr {
+                kind: ExprKind::Set(object, name, value),
+                ..
+            }) if self.optimize => {
+                self.expression(object);
+                self.check_instance(object, value, line);
+                self.expression(value);
+                self.emit_property(OpCode::SetPropertyPop, name, line);
+            }
+            StmtKind::Expression(expr) => {
+                self.expression(expr);
+                self.emit_op(OpCode::Pop, line);
+            }
+            StmtKind::Print(expr) => {
+                self.expression(expr);
+                self.emit_op(OpCode::Print, line);
+            }
+            StmtKind::Var { name, initializer } => {
+                match initializer {
+                    Some(initializer) => self.expression(initializer),
+                    None => self.emit_op(OpCode::Nil, line),
+                }
+                self.declare(name);
+                self.define_global(name, line);
+            }
+            StmtKind::Block(statements) => {
+                self.begin_scope();
+                self.statements(statements);
+                self.end_scope(statements.last().map_or(line, |last| last.span.line));
+            }
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse, line);
+                self.emit_op(OpCode::Pop, line);
+                self.statement(then_branch);
+                let else_jump = self.emit_jump(OpCode::Jump, line);
+                self.patch_jump(then_jump, stmt.span);
+                self.emit_op(OpCode::Pop, line);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+                self.patch_jump(else_jump, stmt.span);
+            }
+            StmtKind::While { condition, body } => {
+                let start = self.chunk().code.len();
+                self.expression(condition);
+                let exit = self.emit_jump(OpCode::JumpIfFalse, line);
+                self.emit_op(OpCode::Pop, line);
+                self.statement(body);
+                self.emit_loop(start, stmt.span);
+                self.patch_jump(exit, stmt.span);
+                self.emit_op(OpCode::Pop, line);
+            }
+            StmtKind::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                // like the interpreter, one scope around the whole loop.
+                self.begin_scope();
+                if let Some(initializer) = initializer {
+                    self.statement(initializer);
+                }
+                let start = self.chunk().code.len();
+                let exit = condition.as_ref().map(|condition| {
+                    self.expression(condition);
+                    let exit = self.emit_jump(OpCode::JumpIfFalse, line);
+                    self.emit_op(OpCode::Pop, line);
+                    exit
+                });
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                    self.emit_op(OpCode::Pop, line);
+                }
+                self.emit_loop(start, stmt.span);
+                if let Some(exit) = exit {
+                    self.patch_jump(exit, stmt.span);
+                    self.emit_op(OpCode::Pop, line);
+                }
+                self.end_scope(line);
+            }
+            StmtKind::Function(function) => {
+                // declared first, so that the function can refer to itself.
+                self.declare(&function.name);
+                self.function(function, FunctionKind::Function);
+                self.define_global(&function.name, line);
+            }
+            StmtKind::Return(value) => {
+                match value {
+                    Some(value) => self.expression(value),
+                    None => self.emit_implicit_return_value(line),
+                }
+                self.emit_op(OpCode::Return, line);
+            }
+            StmtKind::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let constant = self.identifier_constant(name.lexeme, name.span);
+                self.declare(name);
+                self.emit_with(OpCode::Class, constant, line);
+                self.define_global(name, line);
+
+                // methods capture `super` from a scope around them, as in the interpreter.
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                    self.begin_scope();
+                    self.add_local("super", superclass.span);
+                    self.get_variable(name.lexeme, name.span, line);
+                    self.emit_op(OpCode::Inherit, superclass.span.line);
+                }
+                self.get_variable(name.lexeme, name.span, line);
+                for method in methods {
+                    let kind = if method.name.lexeme == "init" {
+                        FunctionKind::Initializer
+                    } else {
+                        FunctionKind::Method
+                    };
+                    self.function(method, kind);
+                    let constant = self.identifier_constant(method.name.lexeme, method.name.span);
+                    self.emit_with(OpCode::Method, constant, method.name.span.line);
+                }
+                self.emit_op(OpCode::Pop, line);
+                if superclass.is_some() {
+                    self.end_scope(line);
+                }
+            }
+        }
+    }
+
+    // what a `return;` returns: nil, or `this` from an initializer.
+    fn emit_implicit_return_value(&mut self, line: usize) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit_with(OpCode::GetLocal, 0, line);
+        } else {
+            self.emit_op(OpCode::Nil, line);
+        }
+    }
+
+    // compiles a function and emits the instruction creating its closure.
+    fn function(&mut self, function: &parser::Function<'a>, kind: FunctionKind) {
+        let name = self.intern(function.name.lexeme);
+        let mut state = FunctionState::new(Some(name), function.params.len(), kind);
+        state.scope_depth = 1;
+        self.functions.push(state);
+        for param in &function.params {
+            self.add_local(param.lexeme, param.span);
+        }
+        self.statements(&function.body);
+
+        let last_line = function
+            .body
+            .last()
+            .map_or(function.span.line, |last| last.span.line);
+        let (function_ref, captures) = self.end_function(last_line);
+        let line = function.name.span.line;
+        let constant = self.make_constant(Value::obj(function_ref), function.span);
+        self.emit_with(OpCode::Closure, constant, line);
+        for capture in captures {
+            self.emit(capture.is_local as u8, line);
+            self.emit(capture.index, line);
+        }
+    }
+
+    // the superinstruction for `left operator right`, when `right` is a literal the constant
+    // pool can hold and the pair runs often enough to have one.
+    fn constant_operation(&self, operator: BinaryOp, right: &Expr) -> Option<OpCode> {
+        if !self.optimize {
+            return None;
+        }
+        match (operator, &right.kind) {
+            (BinaryOp::Plus, ExprKind::Literal(LiteralValue::Number(_)))
+            | (BinaryOp::Plus, ExprKind::Literal(LiteralValue::String(_))) => {
+                Some(OpCode::AddConstant)
+            }
+            (BinaryOp::Minus, ExprKind::Literal(LiteralValue::Number(_))) => {
+                Some(OpCode::SubtractConstant)
+            }
+            (BinaryOp::Less, ExprKind::Literal(LiteralValue::Number(_))) => {
+                Some(OpCode::LessConstant)
+            }
+            _ => None,
+        }
+    }
+
+    // the slot of the local variable `expr` reads, if it reads one of the current function.
+    fn local_slot(&self, expr: &Expr) -> Option<u8> {
+        let name = match &expr.kind {
+            ExprKind::Variable(name) => name,
+            ExprKind::This => "this",
+            _ => return None,
+        };
+        self.resolve_local(self.functions.len() - 1, name)
+    }
+
+    // the constant of a number or string literal.
+    fn literal_constant(&mut self, literal: &LiteralValue, span: Span) -> u8 {
+        let value = match literal {
+            LiteralValue::Number(number) => Value::number(*number),
+            LiteralValue::String(string) => Value::obj(self.intern(string)),
+            _ => unreachable!("only numbers and strings are constants"),
+        };
+        self.make_constant(value, span)
+    }
+
+    // checks that the object of a property set is an instance before its value runs, unless
+    // the check can be left to the set: the object is `this`, or the value can't run code or
+    // fail, so nothing can tell which comes first.
+    fn check_instance(&mut self, object: &Expr, value: &Expr, line: usize) {
+        let unobservable = matches!(object.kind, ExprKind::This)
+            || matches!(value.kind, ExprKind::Literal(_))
+            || self.local_slot(value).is_some();
+        if !(self.optimize && unobservable) {
+            self.emit_op(OpCode::CheckInstance, line);
+        }
+    }
+
+    fn arguments(&mut self, arguments: &[Expr<'a>]) {
+        for argument in arguments {
+            self.expression(argument);
+        }
+    }
+
+    fn end_function(&mut self, line: usize) -> (ObjRef, Vec<Capture>) {
+        self.emit_implicit_return_value(line);
+        self.emit_op(OpCode::Return, line);
+        let state = self.functions.pop().expect("a function is being compiled");
+        let function = Function {
+            name: state.name,
+            arity: state.arity,
+            upvalue_count: state.captures.len(),
+            chunk: Rc::new(state.chunk),
+        };
+        (self.alloc(Obj::Function(function)), state.captures)
+    }
+
+    fn expression(&mut self, expr: &Expr<'a>) {
+        let line = expr.span.line;
+        match &expr.kind {
+            ExprKind::Literal(literal) => match literal {
+                LiteralValue::Nil => self.emit_op(OpCode::Nil, line),
+                LiteralValue::True => self.emit_op(OpCode::True, line),
+                LiteralValue::False => self.emit_op(OpCode::False, line),
+                LiteralValue::Number(_) | LiteralValue::String(_) => {
+                    let constant = self.literal_constant(literal, expr.span);
+                    self.emit_with(OpCode::Constant, constant, line);
+                }
+            },
+            ExprKind::Variable(name) => self.get_variable(name, expr.span, line),
+            ExprKind::Assign(name, value) => {
+                self.expression(value);
+                self.set_variable(name.lexeme, name.span, line);
+            }
+            ExprKind::Binary(left, operator, right) => {
+                if let Some(op) = self.constant_operation(*operator, right) {
+                    self.expression(left);
+                    let constant = match &right.kind {
+                        ExprKind::Literal(literal) => self.literal_constant(literal, right.span),
+                        _ => unreachable!("the right operand is a literal"),
+                    };
+                    self.emit_with(op, constant, line);
+                    return;
+                }
+                self.expression(left);
+                self.expression(right);
+                let op = match operator {
+                    BinaryOp::EqualEqual => OpCode::Equal,
+                    BinaryOp::BangEqual => OpCode::NotEqual,
+                    BinaryOp::Less => OpCode::Less,
+                    BinaryOp::LessEqual => OpCode::LessEqual,
+                    BinaryOp::Greater => OpCode::Greater,
+                    BinaryOp::GreaterEqual => OpCode::GreaterEqual,
+                    BinaryOp::Plus => OpCode::Add,
+                    BinaryOp::Minus => OpCode::Subtract,
+                    BinaryOp::Star => OpCode::Multiply,
+                    BinaryOp::Slash => OpCode::Divide,
+                };
+                self.emit_op(op, line);
+            }
+            ExprKind::Logical(left, operator, right) => {
+                self.expression(left);
+                match operator {
+                    LogicalOp::And => {
+                        let end = self.emit_jump(OpCode::JumpIfFalse, line);
+                        self.emit_op(OpCode::Pop, line);
+                        self.expression(right);
+                        self.patch_jump(end, expr.span);
+                    }
+                    LogicalOp::Or => {
+                        let right_jump = self.emit_jump(OpCode::JumpIfFalse, line);
+                        let end = self.emit_jump(OpCode::Jump, line);
+                        self.patch_jump(right_jump, expr.span);
+                        self.emit_op(OpCode::Pop, line);
+                        self.expression(right);
+                        self.patch_jump(end, expr.span);
+                    }
+                }
+            }
+            ExprKind::Grouping(inner) => self.expression(inner),
+            ExprKind::Unary(operator, operand) => {
+                self.expression(operand);
+                match operator {
+                    UnaryOp::Minus => self.emit_op(OpCode::Negate, line),
+                    UnaryOp::Bang => self.emit_op(OpCode::Not, line),
+                }
+            }
+            // methods are called without binding them to their instance first. They're looked
+            // up before the arguments run, as properties called any other way are.
+            ExprKind::Call(callee, arguments) => match &callee.kind {
+                ExprKind::Get(object, name) if self.optimize => {
+                    self.expression(object);
+                    self.emit_property(OpCode::GetCallee, name, line);
+                    self.arguments(arguments);
+                    self.emit_with(OpCode::Invoke, arguments.len() as u8, line);
+                }
+                ExprKind::Super(method) if self.optimize => {
+                    self.get_variable("this", callee.span, line);
+                    self.get_variable("super", callee.span, line);
+                    let constant = self.identifier_constant(method.lexeme, method.span);
+                    self.emit_with(OpCode::GetSuperCallee, constant, line);
+                    self.arguments(arguments);
+                    self.emit_with(OpCode::Invoke, arguments.len() as u8, line);
+                }
+                _ => {
+                    self.expression(callee);
+                    self.arguments(arguments);
+                    self.emit_with(OpCode::Call, arguments.len() as u8, line);
+                }
+            },
+            ExprKind::Get(object, name) => match self.local_slot(object) {
+                Some(slot) if self.optimize => {
+                    self.emit_with(OpCode::GetLocalProperty, slot, line);
+                    self.emit_property_operands(name, line);
+                }
+                _ => {
+                    self.expression(object);
+                    self.emit_property(OpCode::GetProperty, name, line);
+                }
+            },
+            ExprKind::Set(object, name, value) => {
+                self.expression(object);
+                self.check_instance(object, value, line);
+                self.expression(value);
+                self.emit_property(OpCode::SetProperty, name, line);
+            }
+            ExprKind::This => self.get_variable("this", expr.span, line),
+            ExprKind::Super(method) => {
+                self.get_variable("this", expr.span, line);
+                self.get_variable("super", expr.span, line);
+                let constant = self.identifier_constant(method.lexeme, method.span);
+                self.emit_with(OpCode::GetSuper, constant, line);
+            }
+        }
+    }
+}
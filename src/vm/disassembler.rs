@@ -0,0 +1,168 @@
+use std::fmt::Write;
+
+use super::chunk::{Chunk, OpCode};
+use super::object::{Heap, Obj, ObjRef};
+use super::value::Value;
+
+/// A listing of a compiled function's chunk followed by those of the functions it defines,
+/// in the order they appear in the constant pools. Each instruction is on a line of its own:
+///
+/// ```text
+/// == fib ==
+/// 0000    2 OP_GET_LOCAL        1
+/// 0002    | OP_CONSTANT         0 2
+/// ```
+///
+/// with its offset, its source line (`|` when that's the line of the previous instruction), its
+/// name and its operands. Constants are shown after their index, strings in quotes, and
+/// property instructions end with the index of their inline cache.
+pub fn disassemble(heap: &Heap, function: ObjRef) -> String {
+    let mut out = String::new();
+    let mut pending = vec![function];
+    while let Some(function) = pending.pop() {
+        let function = heap.function(function);
+        let name = match function.name {
+            Some(name) => heap.string(name),
+            None => "<script>",
+        };
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        writeln!(out, "== {} ==", name).unwrap();
+        let mut offset = 0;
+        while offset < function.chunk.code.len() {
+            let (text, next) = instruction(heap, &function.chunk, offset);
+            writeln!(out, "{}", text).unwrap();
+            offset = next;
+        }
+        // pushed in reverse, so that they're listed in the order they're defined.
+        for constant in function.chunk.constants.iter().rev() {
+            if let Some(reference) = constant.as_obj() {
+                if let Obj::Function(_) = heap.get(reference) {
+                    pending.push(reference);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// The listing of the instruction at `offset`, without a trailing newline, and the offset of
+/// the next instruction.
+pub fn instruction(heap: &Heap, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let mut out = format!("{:04} ", offset);
+    let line = chunk.line(offset);
+    if offset > 0 && line == chunk.line(offset - 1) {
+        out.push_str("   | ");
+    } else {
+        write!(out, "{:4} ", line).unwrap();
+    }
+    let op = match OpCode::from_byte(chunk.code[offset]) {
+        Some(op) => op,
+        None => {
+            write!(out, "unknown opcode {}", chunk.code[offset]).unwrap();
+            return (out, offset + 1);
+        }
+    };
+    let byte = |index: usize| chunk.code[offset + index];
+    let name = op.name();
+    let next = match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetSuper
+        | OpCode::GetSuperCallee
+        | OpCode::Class
+        | OpCode::Method
+        | OpCode::AddConstant
+        | OpCode::SubtractConstant
+        | OpCode::LessConstant => {
+            let index = byte(1);
+            let constant = show(heap, chunk.constants[index as usize]);
+            write!(out, "{:<16} {:4} {}", name, index, constant).unwrap();
+            offset + 2
+        }
+        OpCode::GetProperty | OpCode::SetProperty | OpCode::SetPropertyPop | OpCode::GetCallee => {
+            let index = byte(1);
+            let constant = show(heap, chunk.constants[index as usize]);
+            let cache = (byte(2) as usize) << 8 | byte(3) as usize;
+            write!(out, "{:<16} {:4} {} cache {}", name, index, constant, cache).unwrap();
+            offset + 4
+        }
+        OpCode::GetLocalProperty => {
+            let index = byte(2);
+            let constant = show(heap, chunk.constants[index as usize]);
+            let cache = (byte(3) as usize) << 8 | byte(4) as usize;
+            write!(
+                out,
+                "{:<16} {:4} {:4} {} cache {}",
+                name,
+                byte(1),
+                index,
+                constant,
+                cache
+            )
+            .unwrap();
+            offset + 5
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call
+        | OpCode::Invoke => {
+            write!(out, "{:<16} {:4}", name, byte(1)).unwrap();
+            offset + 2
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let jump = (byte(1) as usize) << 8 | byte(2) as usize;
+            let target = if op == OpCode::Loop {
+                offset + 3 - jump
+            } else {
+                offset + 3 + jump
+            };
+            write!(out, "{:<16} {:4} -> {}", name, offset, target).unwrap();
+            offset + 3
+        }
+        OpCode::Closure => {
+            let index = byte(1);
+            let function = chunk.constants[index as usize];
+            let constant = show(heap, function);
+            write!(out, "{:<16} {:4} {}", name, index, constant).unwrap();
+            let captures = function
+                .as_obj()
+                .map_or(0, |function| heap.function(function).upvalue_count);
+            for capture in 0..captures {
+                let at = offset + 2 + capture * 2;
+                let kind = if chunk.code[at] == 1 {
+                    "local"
+                } else {
+                    "upvalue"
+                };
+                write!(
+                    out,
+                    "\n{:04}    |                     {} {}",
+                    at,
+                    kind,
+                    chunk.code[at + 1]
+                )
+                .unwrap();
+            }
+            offset + 2 + captures * 2
+        }
+        _ => {
+            out.push_str(name);
+            offset + 1
+        }
+    };
+    (out, next)
+}
+
+// a value as listings show it: like `print`, but with strings quoted.
+pub(super) fn show(heap: &Heap, value: Value) -> String {
+    match heap.as_string(value) {
+        Some(string) => format!("{:?}", string),
+        None => heap.display(value).to_string(),
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How the heap collects garbage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GcMode {
+    /// Every collection marks and sweeps the whole heap.
+    MarkSweep,
+    /// New objects are allocated in a nursery that minor collections sweep on their own,
+    /// promoting the survivors; the whole heap is only collected once the promoted objects
+    /// have grown enough. Short-lived garbage is then freed without marking long-lived data,
+    /// which shortens most pauses.
+    Generational,
+}
+
+impl GcMode {
+    pub const VARIANTS: &'static [&'static str] = &["mark-sweep", "generational"];
+}
+
+impl FromStr for GcMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mark-sweep" => Ok(GcMode::MarkSweep),
+            "generational" => Ok(GcMode::Generational),
+            _ => Err(format!("unknown gc mode '{}'", s)),
+        }
+    }
+}
+
+// upper bounds of the pause histogram's buckets; the last bucket has none.
+const BUCKETS: [Duration; 5] = [
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+];
+
+/// What the collector did over a run.
+#[derive(Clone, Debug, Default)]
+pub struct GcStats {
+    /// Collections of the nursery only.
+    pub minor_collections: usize,
+    /// Collections of the whole heap.
+    pub major_collections: usize,
+    pub bytes_freed: usize,
+    /// How many pauses fell in each bucket: under 10µs, 100µs, 1ms, 10ms, 100ms, and longer.
+    pub pauses: [usize; 6],
+    pub longest_pause: Duration,
+    pub total_pause: Duration,
+}
+
+impl GcStats {
+    pub fn collections(&self) -> usize {
+        self.minor_collections + self.major_collections
+    }
+
+    pub(crate) fn record(&mut self, major: bool, bytes_freed: usize, pause: Duration) {
+        if major {
+            self.major_collections += 1;
+        } else {
+            self.minor_collections += 1;
+        }
+        self.bytes_freed += bytes_freed;
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| pause < *bound)
+            .unwrap_or(BUCKETS.len());
+        self.pauses[bucket] += 1;
+        self.longest_pause = self.longest_pause.max(pause);
+        self.total_pause += pause;
+    }
+}
+
+/// A report for `rlox run --gc-stats`:
+///
+/// ```text
+/// gc: 12 collections (10 minor, 2 major), 3456789 bytes freed
+/// gc pauses: longest 523µs, total 2.1ms
+///   < 10µs     3
+///   < 100µs    5
+/// ...
+/// ```
+impl fmt::Display for GcStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "gc: {} collections ({} minor, {} major), {} bytes freed",
+            self.collections(),
+            self.minor_collections,
+            self.major_collections,
+            self.bytes_freed
+        )?;
+        writeln!(
+            f,
+            "gc pauses: longest {:?}, total {:?}",
+            self.longest_pause, self.total_pause
+        )?;
+        for (index, count) in self.pauses.iter().enumerate() {
+            let bucket = match BUCKETS.get(index) {
+                Some(bound) => format!("< {:?}", bound),
+                None => format!(">= {:?}", BUCKETS[BUCKETS.len() - 1]),
+            };
+            writeln!(f, "  {:<10} {}", bucket, count)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,807 @@
+//! The bytecode backend: `compiler` turns a resolved program into `chunk`s of instructions,
+//! which `Vm` runs on a value stack. Programs behave as they do in the tree-walking
+//! interpreter, printing the same output and reporting the same runtime errors.
+
+pub mod cache;
+pub mod chunk;
+pub mod compiler;
+pub mod disassembler;
+pub mod gc;
+pub mod object;
+pub mod profile;
+pub mod table;
+pub mod value;
+
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::interpreter::RuntimeError;
+use cache::Lookup;
+use chunk::{Chunk, OpCode};
+use gc::GcMode;
+use object::{BoundMethod, Class, Closure, Heap, Instance, Native, Obj, ObjRef, Upvalue};
+use profile::Profile;
+use table::Table;
+use value::Value;
+
+pub use compiler::compile;
+
+// deeper recursion fails with "Stack overflow." instead of exhausting memory.
+pub(crate) const FRAMES_MAX: usize = 1024;
+
+struct CallFrame {
+    closure: ObjRef,
+    chunk: Rc<Chunk>,
+    ip: usize,
+    // stack index of the frame's slot 0: the callee, or the receiver of a method.
+    base: usize,
+}
+
+pub struct Vm {
+    heap: Heap,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: Table<ObjRef, Value>,
+    // upvalues still pointing at stack slots, with their slots, sorted by slot.
+    open_upvalues: Vec<(usize, ObjRef)>,
+    init_string: ObjRef,
+    trace: bool,
+    profile: Option<Profile>,
+    inline_caching: bool,
+    // bumped whenever a class is created or its methods change, which empties the inline
+    // caches.
+    class_epoch: u64,
+    cache_misses: usize,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        let mut heap = Heap::new();
+        let init_string = heap.intern("init");
+        let mut vm = Vm {
+            heap,
+            stack: vec![],
+            frames: vec![],
+            globals: Table::new(),
+            open_upvalues: vec![],
+            init_string,
+            trace: false,
+            profile: None,
+            inline_caching: true,
+            class_epoch: 0,
+            cache_misses: 0,
+        };
+        vm.define_native("clock", 0, |_, _| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|error| error.to_string())?;
+            Ok(Value::number(now.as_secs_f64()))
+        });
+        vm
+    }
+
+    /// Prints the stack and the next instruction to stderr before executing each instruction.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Counts the instructions the VM runs, and the pairs that run one after the other.
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.profile = if profiling {
+            Some(Profile::default())
+        } else {
+            None
+        };
+    }
+
+    /// What ran since profiling was turned on.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Collects garbage whenever an object is allocated, to shake out objects that aren't
+    /// properly rooted.
+    pub fn set_stress_gc(&mut self, stress: bool) {
+        self.heap.set_stress(stress);
+    }
+
+    /// Changes how the VM collects garbage. Set it before compiling anything for the VM.
+    pub fn set_gc_mode(&mut self, mode: GcMode) {
+        self.heap.set_mode(mode);
+    }
+
+    /// Whether property instructions use their inline caches, which they do by default.
+    /// Without them every property is looked up by name.
+    pub fn set_inline_caching(&mut self, inline_caching: bool) {
+        self.inline_caching = inline_caching;
+    }
+
+    /// How many times a property instruction has looked up a property by name, because its
+    /// inline cache didn't have it or caching is off.
+    pub fn cache_misses(&self) -> usize {
+        self.cache_misses
+    }
+
+    /// The objects of programs compiled for this VM.
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    pub fn define_native(
+        &mut self,
+        name: &'static str,
+        arity: usize,
+        function: impl Fn(&mut Heap, &[Value]) -> Result<Value, String> + 'static,
+    ) {
+        // natives are defined while setting up, so these allocations don't collect.
+        let native = self.heap.alloc(Obj::Native(Native {
+            name,
+            arity,
+            function: Rc::new(function),
+        }));
+        let name = self.heap.intern(name);
+        self.globals.insert(name, Value::obj(native));
+    }
+
+    /// Runs a compiled script.
+    pub fn interpret(&mut self, script: ObjRef) -> Result<(), RuntimeError> {
+        let closure = self.alloc(Obj::Closure(Closure {
+            function: script,
+            upvalues: vec![],
+        }));
+        self.stack.push(Value::obj(closure));
+        let result = self.call(closure, 0).and_then(|()| self.run());
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    fn frame(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("a function is running")
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame();
+        let byte = frame.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_short(&mut self) -> usize {
+        let high = self.read_byte() as usize;
+        let low = self.read_byte() as usize;
+        high << 8 | low
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_byte() as usize;
+        self.frame().chunk.constants[index]
+    }
+
+    fn read_string(&mut self) -> ObjRef {
+        self.read_constant()
+            .as_obj()
+            .expect("name constants are strings")
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn replace_top(&mut self, value: Value) {
+        *self.stack.last_mut().expect("stack underflow") = value;
+    }
+
+    // an error at the instruction being executed.
+    fn error(&self, message: &str) -> RuntimeError {
+        let frame = self.frames.last().expect("a function is running");
+        RuntimeError {
+            line: frame.chunk.line(frame.ip - 1),
+            message: message.to_string(),
+        }
+    }
+
+    fn run(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            if self.trace {
+                self.trace_instruction();
+            }
+            let op = OpCode::from_byte(self.read_byte()).expect("valid instruction");
+            if let Some(profile) = &mut self.profile {
+                profile.record(op);
+            }
+            match op {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(Value::nil()),
+                OpCode::True => self.push(Value::bool(true)),
+                OpCode::False => self.push(Value::bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let base = self.frame().base;
+                    self.push(self.stack[base + slot]);
+                }
+                OpCode::SetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let base = self.frame().base;
+                    self.stack[base + slot] = self.peek(0);
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    match self.globals.get(&name) {
+                        Some(value) => self.push(*value),
+                        None => return Err(self.undefined("variable", name)),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    if !self.globals.contains_key(&name) {
+                        return Err(self.undefined("variable", name));
+                    }
+                    self.globals.insert(name, self.peek(0));
+                }
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let closure = self.frame().closure;
+                    let value = match *self
+                        .heap
+                        .upvalue(self.heap.closure(closure).upvalues[index])
+                    {
+                        Upvalue::Open(slot) => self.stack[slot],
+                        Upvalue::Closed(value) => value,
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let closure = self.frame().closure;
+                    let value = self.peek(0);
+                    let upvalue = self.heap.closure(closure).upvalues[index];
+                    match self.heap.upvalue_mut(upvalue) {
+                        Upvalue::Open(slot) => {
+                            let slot = *slot;
+                            self.stack[slot] = value;
+                        }
+                        Upvalue::Closed(closed) => {
+                            *closed = value;
+                            self.heap.write_barrier(upvalue, value);
+                        }
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let cache = self.read_short();
+                    self.get_property(name, cache)?;
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let cache = self.read_short();
+                    self.set_property(name, cache)?;
+                }
+                OpCode::CheckInstance => {
+                    let heap = &self.heap;
+                    match self.peek(0).as_obj().map(|obj| heap.get(obj)) {
+                        Some(Obj::Instance(_)) => {}
+                        _ => return Err(self.error("Only instances have fields.")),
+                    }
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let superclass = self.pop().as_obj().expect("'super' is a class");
+                    self.bind_method(superclass, name)?;
+                }
+                OpCode::Equal => {
+                    let (a, b) = (self.pop(), self.pop());
+                    self.push(Value::bool(a == b));
+                }
+                OpCode::NotEqual => {
+                    let (a, b) = (self.pop(), self.pop());
+                    self.push(Value::bool(a != b));
+                }
+                // comparisons with NaN are all false, so `a >= b` isn't `!(a < b)`.
+                OpCode::Greater => self.binary(|a, b| Value::bool(a > b))?,
+                OpCode::GreaterEqual => self.binary(|a, b| Value::bool(a >= b))?,
+                OpCode::Less => self.binary(|a, b| Value::bool(a < b))?,
+                OpCode::LessEqual => self.binary(|a, b| Value::bool(a <= b))?,
+                OpCode::Add => self.add()?,
+                OpCode::Subtract => self.binary(|a, b| Value::number(a - b))?,
+                OpCode::Multiply => self.binary(|a, b| Value::number(a * b))?,
+                OpCode::Divide => self.binary(|a, b| Value::number(a / b))?,
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::bool(!value.is_truthy()));
+                }
+                OpCode::Negate => match self.peek(0).as_number() {
+                    Some(number) => {
+                        self.pop();
+                        self.push(Value::number(-number));
+                    }
+                    None => return Err(self.error("Operand must be a number.")),
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    println!("{}", self.heap.display(value));
+                }
+                OpCode::Jump => {
+                    let offset = self.read_short();
+                    self.frame().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_short();
+                    if !self.peek(0).is_truthy() {
+                        self.frame().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = self.read_short();
+                    self.frame().ip -= offset;
+                }
+                OpCode::Call => {
+                    let count = self.read_byte() as usize;
+                    self.call_value(self.peek(count), count)?;
+                }
+                OpCode::GetCallee => {
+                    let name = self.read_string();
+                    let cache = self.read_short();
+                    match self.property(0, name, cache)? {
+                        Lookup::Field(index) => {
+                            let instance = self.peek(0).as_obj().expect("an instance");
+                            let value = self.heap.instance(instance).fields.get_at(index, &name);
+                            let value = *value.expect("the field is in its bucket");
+                            self.replace_top(value);
+                            self.push(Value::nil());
+                        }
+                        Lookup::Method(method) => self.push(Value::obj(method)),
+                    }
+                }
+                OpCode::GetSuperCallee => {
+                    let name = self.read_string();
+                    let superclass = self.pop().as_obj().expect("'super' is a class");
+                    match self.heap.class(superclass).methods.get(&name) {
+                        Some(method) => self.push(Value::obj(*method)),
+                        None => return Err(self.undefined("property", name)),
+                    }
+                }
+                OpCode::Invoke => {
+                    let count = self.read_byte() as usize;
+                    let method = self.stack.remove(self.stack.len() - count - 1);
+                    match method.as_obj() {
+                        Some(method) => self.call(method, count)?,
+                        // a field holding something callable, called like any other value.
+                        None => self.call_value(self.peek(count), count)?,
+                    }
+                }
+                OpCode::Closure => {
+                    let function = self
+                        .read_constant()
+                        .as_obj()
+                        .expect("closure constants are functions");
+                    let count = self.heap.function(function).upvalue_count;
+                    let mut upvalues = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        let (base, enclosing) = (self.frame().base, self.frame().closure);
+                        upvalues.push(if is_local {
+                            self.capture_upvalue(base + index)
+                        } else {
+                            self.heap.closure(enclosing).upvalues[index]
+                        });
+                    }
+                    let closure = self.alloc(Obj::Closure(Closure { function, upvalues }));
+                    self.push(Value::obj(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("a function is running");
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_string();
+                    self.class_epoch += 1;
+                    let class = self.alloc(Obj::Class(Class {
+                        name,
+                        methods: Table::new(),
+                    }));
+                    self.push(Value::obj(class));
+                }
+                OpCode::Inherit => {
+                    let superclass = match self.peek(1).as_obj().map(|obj| self.heap.get(obj)) {
+                        Some(Obj::Class(superclass)) => superclass.methods.clone(),
+                        _ => return Err(self.error("Superclass must be a class.")),
+                    };
+                    self.class_epoch += 1;
+                    if let Some(subclass) = self.pop().as_obj() {
+                        for (&name, &method) in superclass.iter() {
+                            self.heap.class_mut(subclass).methods.insert(name, method);
+                            self.heap.write_barrier(subclass, Value::obj(name));
+                            self.heap.write_barrier(subclass, Value::obj(method));
+                        }
+                    }
+                }
+                OpCode::Method => {
+                    let name = self.read_string();
+                    let method = self.pop();
+                    self.class_epoch += 1;
+                    if let (Some(class), Some(method)) = (self.peek(0).as_obj(), method.as_obj()) {
+                        self.heap.class_mut(class).methods.insert(name, method);
+                        self.heap.write_barrier(class, Value::obj(name));
+                        self.heap.write_barrier(class, Value::obj(method));
+                    }
+                }
+                OpCode::GetLocalProperty => {
+                    let slot = self.read_byte() as usize;
+                    let base = self.frame().base;
+                    self.push(self.stack[base + slot]);
+                    let name = self.read_string();
+                    let cache = self.read_short();
+                    self.get_property(name, cache)?;
+                }
+                OpCode::SetPropertyPop => {
+                    let name = self.read_string();
+                    let cache = self.read_short();
+                    self.set_property(name, cache)?;
+                    self.pop();
+                }
+                OpCode::AddConstant => {
+                    let constant = self.read_constant();
+                    match (self.peek(0).as_number(), constant.as_number()) {
+                        (Some(a), Some(b)) => self.replace_top(Value::number(a + b)),
+                        _ => {
+                            self.push(constant);
+                            self.add()?;
+                        }
+                    }
+                }
+                OpCode::SubtractConstant => {
+                    let constant = self.read_constant();
+                    self.binary_constant(constant, |a, b| Value::number(a - b))?;
+                }
+                OpCode::LessConstant => {
+                    let constant = self.read_constant();
+                    self.binary_constant(constant, |a, b| Value::bool(a < b))?;
+                }
+            }
+        }
+    }
+
+    // allocates an object, collecting garbage first if it's time to.
+    fn alloc(&mut self, obj: Obj) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage(&[], Some(&obj));
+        }
+        self.heap.alloc(obj)
+    }
+
+    fn intern(&mut self, string: &str) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage(&[], None);
+        }
+        self.heap.intern(string)
+    }
+
+    /// Frees the objects that the VM can't reach. `roots` are values that are reachable
+    /// although the VM doesn't hold them, such as the constants of a program being compiled,
+    /// and `pending` an object about to be allocated.
+    pub(crate) fn collect_garbage(&mut self, roots: &[Value], pending: Option<&Obj>) {
+        self.heap.begin_collection();
+        for value in self.stack.iter().chain(roots) {
+            self.heap.mark_value(*value);
+        }
+        for frame in &self.frames {
+            self.heap.mark(frame.closure);
+        }
+        for (_, upvalue) in &self.open_upvalues {
+            self.heap.mark(*upvalue);
+        }
+        for (name, value) in self.globals.iter() {
+            self.heap.mark(*name);
+            self.heap.mark_value(*value);
+        }
+        self.heap.mark(self.init_string);
+        if let Some(obj) = pending {
+            self.heap.mark_references(obj);
+        }
+        self.heap.collect();
+    }
+
+    // the open upvalue for a stack slot, created unless a closure already captured the slot.
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        let index = match self
+            .open_upvalues
+            .binary_search_by_key(&slot, |(open, _)| *open)
+        {
+            Ok(existing) => return self.open_upvalues[existing].1,
+            Err(index) => index,
+        };
+        let upvalue = self.alloc(Obj::Upvalue(Upvalue::Open(slot)));
+        self.open_upvalues.insert(index, (slot, upvalue));
+        upvalue
+    }
+
+    // closes the upvalues of the slots from `first` up, as those go out of scope.
+    fn close_upvalues(&mut self, first: usize) {
+        while let Some(&(slot, upvalue)) = self.open_upvalues.last() {
+            if slot < first {
+                break;
+            }
+            *self.heap.upvalue_mut(upvalue) = Upvalue::Closed(self.stack[slot]);
+            self.heap.write_barrier(upvalue, self.stack[slot]);
+            self.open_upvalues.pop();
+        }
+    }
+
+    fn trace_instruction(&self) {
+        let stack: String = self
+            .stack
+            .iter()
+            .map(|value| format!("[ {} ]", disassembler::show(&self.heap, *value)))
+            .collect();
+        eprintln!("          {}", stack);
+        let frame = self.frames.last().expect("a function is running");
+        eprintln!(
+            "{}",
+            disassembler::instruction(&self.heap, &frame.chunk, frame.ip).0
+        );
+    }
+
+    fn undefined(&self, what: &str, name: ObjRef) -> RuntimeError {
+        let message = format!("Undefined {} '{}'.", what, self.heap.string(name));
+        self.error(&message)
+    }
+
+    fn binary(&mut self, op: impl Fn(f64, f64) -> Value) -> Result<(), RuntimeError> {
+        match (self.peek(1).as_number(), self.peek(0).as_number()) {
+            (Some(a), Some(b)) => {
+                self.pop();
+                self.pop();
+                self.push(op(a, b));
+                Ok(())
+            }
+            _ => Err(self.error("Operands must be numbers.")),
+        }
+    }
+
+    // like `binary`, with a constant rather than the top of the stack as the right operand.
+    fn binary_constant(
+        &mut self,
+        b: Value,
+        op: impl Fn(f64, f64) -> Value,
+    ) -> Result<(), RuntimeError> {
+        match (self.peek(0).as_number(), b.as_number()) {
+            (Some(a), Some(b)) => {
+                self.replace_top(op(a, b));
+                Ok(())
+            }
+            _ => Err(self.error("Operands must be numbers.")),
+        }
+    }
+
+    fn add(&mut self) -> Result<(), RuntimeError> {
+        let (a, b) = (self.peek(1), self.peek(0));
+        let result = match (a.as_number(), b.as_number()) {
+            (Some(a), Some(b)) => Value::number(a + b),
+            _ => match (self.heap.as_string(a), self.heap.as_string(b)) {
+                (Some(a), Some(b)) => {
+                    let concatenated = format!("{}{}", a, b);
+                    Value::obj(self.intern(&concatenated))
+                }
+                _ => {
+                    return Err(self.error("Operands must be two numbers or two strings."));
+                }
+            },
+        };
+        self.pop();
+        self.pop();
+        self.push(result);
+        Ok(())
+    }
+
+    // replaces the instance on top of the stack with the value of its property `name`.
+    fn get_property(&mut self, name: ObjRef, cache: usize) -> Result<(), RuntimeError> {
+        match self.property(0, name, cache)? {
+            Lookup::Field(index) => {
+                let instance = self.peek(0).as_obj().expect("an instance");
+                let value = self.heap.instance(instance).fields.get_at(index, &name);
+                let value = *value.expect("the field is in its bucket");
+                self.replace_top(value);
+            }
+            Lookup::Method(method) => self.bind(method),
+        }
+        Ok(())
+    }
+
+    // pops a value and the instance below it, sets the instance's field `name` to the value,
+    // and pushes the value.
+    fn set_property(&mut self, name: ObjRef, cache: usize) -> Result<(), RuntimeError> {
+        let value = self.peek(0);
+        let reference = self.peek(1).as_obj();
+        let heap = &mut self.heap;
+        let instance = match reference.map(|obj| heap.get_mut(obj)) {
+            Some(Obj::Instance(instance)) => instance,
+            _ => return Err(self.error("Only instances have fields.")),
+        };
+        let frame = self.frames.last().expect("a function is running");
+        let cache = &frame.chunk.caches[cache];
+        let cached = match cache.lookup(instance.class, self.class_epoch) {
+            Some(Lookup::Field(index)) if self.inline_caching => {
+                instance.fields.get_at_mut(index, &name)
+            }
+            _ => None,
+        };
+        match cached {
+            Some(field) => *field = value,
+            None => {
+                self.cache_misses += 1;
+                instance.fields.insert(name, value);
+                if let Some(index) = instance.fields.index_of(&name) {
+                    cache.update(instance.class, Lookup::Field(index), self.class_epoch);
+                }
+            }
+        }
+        if let Some(reference) = reference {
+            self.heap.write_barrier(reference, Value::obj(name));
+            self.heap.write_barrier(reference, value);
+        }
+        self.pop();
+        self.replace_top(value);
+        Ok(())
+    }
+
+    // where the instance `distance` down the stack has its property `name`: in a field, or a
+    // method of its class. The property instruction's inline cache is checked first, and
+    // updated when it doesn't have the property.
+    fn property(
+        &mut self,
+        distance: usize,
+        name: ObjRef,
+        cache: usize,
+    ) -> Result<Lookup, RuntimeError> {
+        let heap = &self.heap;
+        let instance = match self.peek(distance).as_obj().map(|obj| heap.get(obj)) {
+            Some(Obj::Instance(instance)) => instance,
+            _ => return Err(self.error("Only instances have properties.")),
+        };
+        let frame = self.frames.last().expect("a function is running");
+        let cache = &frame.chunk.caches[cache];
+        if self.inline_caching {
+            match cache.lookup(instance.class, self.class_epoch) {
+                Some(Lookup::Field(index)) if instance.fields.get_at(index, &name).is_some() => {
+                    return Ok(Lookup::Field(index));
+                }
+                // fields shadow methods.
+                Some(Lookup::Method(method)) if !instance.fields.contains_key(&name) => {
+                    return Ok(Lookup::Method(method));
+                }
+                _ => {}
+            }
+        }
+        self.cache_misses += 1;
+        let lookup = match instance.fields.index_of(&name) {
+            Some(index) => Lookup::Field(index),
+            None => match heap.class(instance.class).methods.get(&name) {
+                Some(method) => Lookup::Method(*method),
+                None => return Err(self.undefined("property", name)),
+            },
+        };
+        cache.update(instance.class, lookup, self.class_epoch);
+        Ok(lookup)
+    }
+
+    // replaces the instance on top of the stack with `class`'s method `name` bound to it.
+    fn bind_method(&mut self, class: ObjRef, name: ObjRef) -> Result<(), RuntimeError> {
+        match self.heap.class(class).methods.get(&name) {
+            Some(method) => {
+                self.bind(*method);
+                Ok(())
+            }
+            None => Err(self.undefined("property", name)),
+        }
+    }
+
+    // replaces the instance on top of the stack with `method` bound to it.
+    fn bind(&mut self, method: ObjRef) {
+        let receiver = self.pop();
+        let bound = self.alloc(Obj::BoundMethod(BoundMethod { receiver, method }));
+        self.push(Value::obj(bound));
+    }
+
+    fn call_value(&mut self, callee: Value, count: usize) -> Result<(), RuntimeError> {
+        let reference = match callee.as_obj() {
+            Some(reference) => reference,
+            None => return Err(self.error("Can only call functions and classes.")),
+        };
+        let callee_slot = self.stack.len() - count - 1;
+        match self.heap.get(reference) {
+            Obj::Closure(_) => self.call(reference, count),
+            Obj::Native(native) => {
+                let (arity, function) = (native.arity, Rc::clone(&native.function));
+                self.check_arity(arity, count)?;
+                let arguments: Vec<Value> = self.stack[callee_slot + 1..].to_vec();
+                let result = function(&mut self.heap, &arguments);
+                let result = result.map_err(|message| self.error(&message))?;
+                self.stack.truncate(callee_slot);
+                self.push(result);
+                Ok(())
+            }
+            Obj::Class(class) => {
+                let initializer = class.methods.get(&self.init_string).copied();
+                let instance = self.alloc(Obj::Instance(Instance {
+                    class: reference,
+                    fields: Table::new(),
+                }));
+                self.stack[callee_slot] = Value::obj(instance);
+                match initializer {
+                    Some(initializer) => self.call(initializer, count),
+                    None => self.check_arity(0, count),
+                }
+            }
+            Obj::BoundMethod(bound) => {
+                let (receiver, method) = (bound.receiver, bound.method);
+                self.stack[callee_slot] = receiver;
+                self.call(method, count)
+            }
+            _ => Err(self.error("Can only call functions and classes.")),
+        }
+    }
+
+    fn check_arity(&self, arity: usize, count: usize) -> Result<(), RuntimeError> {
+        if arity == count {
+            return Ok(());
+        }
+        let message = format!("Expected {} arguments but got {}.", arity, count);
+        Err(self.error(&message))
+    }
+
+    fn call(&mut self, closure: ObjRef, count: usize) -> Result<(), RuntimeError> {
+        let function = self.heap.function(self.heap.closure(closure).function);
+        let (arity, chunk) = (function.arity, Rc::clone(&function.chunk));
+        self.check_arity(arity, count)?;
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.error("Stack overflow."));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            chunk,
+            ip: 0,
+            base: self.stack.len() - count - 1,
+        });
+        Ok(())
+    }
+}
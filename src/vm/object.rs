@@ -0,0 +1,571 @@
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+use std::time::Instant;
+
+use super::chunk::Chunk;
+use super::gc::{GcMode, GcStats};
+use super::table::{Key, Table};
+use super::value::Value;
+
+/// Handle to an object on the `Heap`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObjRef(pub(super) u32);
+
+pub enum Obj {
+    String(Box<str>),
+    Function(Function),
+    Native(Native),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+}
+
+/// A compiled function. Functions aren't called directly: the `Closure` instruction wraps them
+/// with the variables they capture.
+pub struct Function {
+    /// `None` for the top-level script.
+    pub name: Option<ObjRef>,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Rc<Chunk>,
+}
+
+pub type NativeFn = dyn Fn(&mut Heap, &[Value]) -> Result<Value, String>;
+
+pub struct Native {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: Rc<NativeFn>,
+}
+
+pub struct Closure {
+    pub function: ObjRef,
+    pub upvalues: Vec<ObjRef>,
+}
+
+/// A variable captured by closures. While the variable is in scope it stays on the stack and
+/// the upvalue is open, pointing at its slot; when the scope ends the VM closes the upvalue
+/// by moving the value into it. Closures capturing the same variable share one upvalue.
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct Class {
+    pub name: ObjRef,
+    /// Methods by name, including the inherited ones: `Inherit` copies a superclass's methods
+    /// into the subclass before the subclass's own are added.
+    pub methods: Table<ObjRef, ObjRef>,
+}
+
+pub struct Instance {
+    pub class: ObjRef,
+    pub fields: Table<ObjRef, Value>,
+}
+
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
+
+// the heap size that triggers the first collection, and how much the heap can grow after a
+// collection before the next one: `GROWTH` times what survived.
+const FIRST_COLLECTION: usize = 1024 * 1024;
+const GROWTH: usize = 2;
+// in `GcMode::Generational`, how much can be allocated between minor collections.
+const NURSERY: usize = 256 * 1024;
+
+struct Entry {
+    obj: Obj,
+    marked: bool,
+    // survived a collection; in `GcMode::Generational`, only major collections trace and free
+    // old objects.
+    old: bool,
+    // an old object in `remembered`.
+    remembered: bool,
+}
+
+/// Owner of every object the compiler and the VM create. Strings are interned: each distinct
+/// string is allocated once, so strings compare equal exactly when their handles do.
+///
+/// Memory is reclaimed by a mark-and-sweep collector. The heap doesn't know what's reachable,
+/// so its owners decide when to collect, with `should_collect`, and `mark` their roots before
+/// calling `collect`, which traces everything reachable from them and frees the rest.
+///
+/// In `GcMode::Generational` most collections are minor: they only mark and free the objects
+/// allocated since the last collection, treating older ones as reachable. Old objects that
+/// refer to new ones are roots of those collections, so owners report every reference they
+/// store in an existing object with `write_barrier`.
+pub struct Heap {
+    // freed slots are `None` until they're reused.
+    objects: Vec<Option<Entry>>,
+    free: Vec<u32>,
+    // weak: strings that are only referenced from here are collected and removed.
+    strings: Table<Interned, ()>,
+    // marked objects whose references haven't been marked yet.
+    gray: Vec<ObjRef>,
+    bytes_allocated: usize,
+    next_collection: usize,
+    stress: bool,
+    mode: GcMode,
+    // objects allocated since the last collection, and their size, in `GcMode::Generational`.
+    young: Vec<ObjRef>,
+    young_bytes: usize,
+    // old objects that may refer to young ones.
+    remembered: Vec<ObjRef>,
+    // the collection being marked is a minor one.
+    minor: bool,
+    started: Option<Instant>,
+    stats: GcStats,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap {
+            objects: vec![],
+            free: vec![],
+            strings: Table::new(),
+            gray: vec![],
+            bytes_allocated: 0,
+            next_collection: FIRST_COLLECTION,
+            stress: false,
+            mode: GcMode::MarkSweep,
+            young: vec![],
+            young_bytes: 0,
+            remembered: vec![],
+            minor: false,
+            started: None,
+            stats: GcStats::default(),
+        }
+    }
+
+    /// The number of objects on the heap, garbage included until it's collected.
+    pub fn object_count(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+
+    /// Roughly how much memory the objects on the heap use.
+    pub fn bytes_allocated(&self) -> usize {
+        self.bytes_allocated
+    }
+
+    /// Makes `should_collect` always true, so that objects that aren't properly rooted are
+    /// freed as early as possible.
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    /// Changes how the heap collects, before anything is allocated.
+    pub fn set_mode(&mut self, mode: GcMode) {
+        self.mode = mode;
+    }
+
+    /// What the collections so far did.
+    pub fn stats(&self) -> &GcStats {
+        &self.stats
+    }
+
+    /// Whether the heap has grown enough since the last collection to collect again.
+    pub fn should_collect(&self) -> bool {
+        self.stress
+            || self.bytes_allocated > self.next_collection
+            || (self.mode == GcMode::Generational && self.young_bytes > NURSERY)
+    }
+
+    /// Stores an object. This never collects: owners collect before allocating, marking any
+    /// objects the new one refers to with `mark_references`.
+    pub fn alloc(&mut self, obj: Obj) -> ObjRef {
+        let size = size(&obj);
+        self.bytes_allocated += size;
+        let entry = Some(Entry {
+            obj,
+            marked: false,
+            old: false,
+            remembered: false,
+        });
+        let reference = match self.free.pop() {
+            Some(index) => {
+                self.objects[index as usize] = entry;
+                ObjRef(index)
+            }
+            None => {
+                self.objects.push(entry);
+                ObjRef(self.objects.len() as u32 - 1)
+            }
+        };
+        if self.mode == GcMode::Generational {
+            self.young.push(reference);
+            self.young_bytes += size;
+        }
+        reference
+    }
+
+    /// The string object with the text `string`, allocated the first time it's asked for.
+    pub fn intern(&mut self, string: &str) -> ObjRef {
+        let hash = hash_string(string);
+        let objects = &self.objects;
+        let interned = self.strings.find_key(hash, |interned| {
+            match &objects[interned.string.0 as usize] {
+                Some(Entry {
+                    obj: Obj::String(text),
+                    ..
+                }) => **text == *string,
+                _ => false,
+            }
+        });
+        if let Some(interned) = interned {
+            return interned.string;
+        }
+        let reference = self.alloc(Obj::String(Box::from(string)));
+        let interned = Interned {
+            hash,
+            string: reference,
+        };
+        self.strings.insert(interned, ());
+        reference
+    }
+
+    pub fn get(&self, reference: ObjRef) -> &Obj {
+        &self.entry(reference).obj
+    }
+
+    pub fn get_mut(&mut self, reference: ObjRef) -> &mut Obj {
+        &mut self.entry_mut(reference).obj
+    }
+
+    /// Records that `container` now refers to `value`. Every reference stored in an object
+    /// after it's allocated has to be recorded, so that minor collections find the young
+    /// objects that old ones refer to.
+    pub fn write_barrier(&mut self, container: ObjRef, value: Value) {
+        let value = match value.as_obj() {
+            Some(value) if self.mode == GcMode::Generational => value,
+            _ => return,
+        };
+        if self.entry(value).old {
+            return;
+        }
+        let entry = self.entry_mut(container);
+        if entry.old && !entry.remembered {
+            entry.remembered = true;
+            self.remembered.push(container);
+        }
+    }
+
+    fn entry(&self, reference: ObjRef) -> &Entry {
+        match &self.objects[reference.0 as usize] {
+            Some(entry) => entry,
+            None => unreachable!("use of a collected object"),
+        }
+    }
+
+    fn entry_mut(&mut self, reference: ObjRef) -> &mut Entry {
+        match &mut self.objects[reference.0 as usize] {
+            Some(entry) => entry,
+            None => unreachable!("use of a collected object"),
+        }
+    }
+
+    /// Starts a collection, before its roots are marked. In `GcMode::Generational` it's a
+    /// minor one unless the old objects have outgrown the heap's budget.
+    pub fn begin_collection(&mut self) {
+        self.started = Some(Instant::now());
+        self.minor = self.mode == GcMode::Generational
+            && self.bytes_allocated.saturating_sub(self.young_bytes) <= self.next_collection;
+    }
+
+    /// Marks a root for the next `collect`.
+    pub fn mark(&mut self, reference: ObjRef) {
+        let minor = self.minor;
+        if let Some(entry) = &mut self.objects[reference.0 as usize] {
+            // minor collections treat old objects as reachable without tracing them.
+            if !(entry.marked || minor && entry.old) {
+                entry.marked = true;
+                self.gray.push(reference);
+            }
+        }
+    }
+
+    pub fn mark_value(&mut self, value: Value) {
+        if let Some(reference) = value.as_obj() {
+            self.mark(reference);
+        }
+    }
+
+    /// Marks the objects an object refers to, for objects that are about to be allocated.
+    pub fn mark_references(&mut self, obj: &Obj) {
+        let mut references = vec![];
+        self::references(obj, &mut references);
+        for reference in references {
+            self.mark(reference);
+        }
+    }
+
+    /// Frees every object that isn't reachable from the roots marked since the last collection.
+    /// A minor collection only frees young objects, and promotes the others.
+    pub fn collect(&mut self) {
+        let minor = self.minor;
+        let mut references = vec![];
+        if minor {
+            for reference in &self.remembered {
+                self::references(self.get(*reference), &mut references);
+            }
+            for reference in references.drain(..) {
+                self.mark(reference);
+            }
+        }
+        // tri-color marking: unmarked objects are white, gray ones are marked with references
+        // still to mark, and the rest are black.
+        while let Some(reference) = self.gray.pop() {
+            self::references(self.get(reference), &mut references);
+            for reference in references.drain(..) {
+                self.mark(reference);
+            }
+        }
+
+        let objects = &self.objects;
+        self.strings.retain(|interned, _| {
+            objects[interned.string.0 as usize]
+                .as_ref()
+                .is_some_and(|entry| entry.marked || (minor && entry.old))
+        });
+
+        let before = self.bytes_allocated;
+        if minor {
+            for reference in self.young.drain(..) {
+                let slot = &mut self.objects[reference.0 as usize];
+                match slot {
+                    Some(entry) if entry.marked => {
+                        entry.marked = false;
+                        entry.old = true;
+                    }
+                    Some(entry) => {
+                        self.bytes_allocated =
+                            self.bytes_allocated.saturating_sub(size(&entry.obj));
+                        *slot = None;
+                        self.free.push(reference.0);
+                    }
+                    None => {}
+                }
+            }
+        } else {
+            self.young.clear();
+            self.bytes_allocated = 0;
+            for (index, slot) in self.objects.iter_mut().enumerate() {
+                match slot {
+                    Some(entry) if entry.marked => {
+                        entry.marked = false;
+                        entry.old = true;
+                        self.bytes_allocated += size(&entry.obj);
+                    }
+                    Some(_) => {
+                        *slot = None;
+                        self.free.push(index as u32);
+                    }
+                    None => {}
+                }
+            }
+            self.next_collection = (self.bytes_allocated * GROWTH).max(FIRST_COLLECTION);
+        }
+        // every object left is old, so none refers to a young one.
+        for reference in self.remembered.drain(..) {
+            if let Some(entry) = &mut self.objects[reference.0 as usize] {
+                entry.remembered = false;
+            }
+        }
+        self.young_bytes = 0;
+        self.minor = false;
+
+        let pause = self
+            .started
+            .take()
+            .map_or_else(Default::default, |started| started.elapsed());
+        let freed = before.saturating_sub(self.bytes_allocated);
+        self.stats.record(!minor, freed, pause);
+    }
+
+    // the accessors below are for objects whose type the compiler guarantees.
+
+    pub fn string(&self, reference: ObjRef) -> &str {
+        match self.get(reference) {
+            Obj::String(string) => string,
+            _ => unreachable!("expected a string"),
+        }
+    }
+
+    pub fn function(&self, reference: ObjRef) -> &Function {
+        match self.get(reference) {
+            Obj::Function(function) => function,
+            _ => unreachable!("expected a function"),
+        }
+    }
+
+    pub fn closure(&self, reference: ObjRef) -> &Closure {
+        match self.get(reference) {
+            Obj::Closure(closure) => closure,
+            _ => unreachable!("expected a closure"),
+        }
+    }
+
+    pub fn upvalue(&self, reference: ObjRef) -> &Upvalue {
+        match self.get(reference) {
+            Obj::Upvalue(upvalue) => upvalue,
+            _ => unreachable!("expected an upvalue"),
+        }
+    }
+
+    pub fn upvalue_mut(&mut self, reference: ObjRef) -> &mut Upvalue {
+        match self.get_mut(reference) {
+            Obj::Upvalue(upvalue) => upvalue,
+            _ => unreachable!("expected an upvalue"),
+        }
+    }
+
+    pub fn class(&self, reference: ObjRef) -> &Class {
+        match self.get(reference) {
+            Obj::Class(class) => class,
+            _ => unreachable!("expected a class"),
+        }
+    }
+
+    pub fn class_mut(&mut self, reference: ObjRef) -> &mut Class {
+        match self.get_mut(reference) {
+            Obj::Class(class) => class,
+            _ => unreachable!("expected a class"),
+        }
+    }
+
+    pub fn instance(&self, reference: ObjRef) -> &Instance {
+        match self.get(reference) {
+            Obj::Instance(instance) => instance,
+            _ => unreachable!("expected an instance"),
+        }
+    }
+
+    /// The string a value is if it's one.
+    pub fn as_string(&self, value: Value) -> Option<&str> {
+        match value.as_obj().map(|reference| self.get(reference)) {
+            Some(Obj::String(string)) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// A value as `print` shows it; the same text the tree-walking interpreter prints.
+    pub fn display(&self, value: Value) -> Display<'_> {
+        Display { heap: self, value }
+    }
+}
+
+// an entry of the intern table, which finds strings by the hash of their text.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Interned {
+    hash: u32,
+    string: ObjRef,
+}
+
+impl Key for Interned {
+    fn hash(&self) -> u32 {
+        self.hash
+    }
+}
+
+// FNV-1a, as clox hashes strings.
+fn hash_string(string: &str) -> u32 {
+    let mut hash = 2_166_136_261u32;
+    for byte in string.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(16_777_619);
+    }
+    hash
+}
+
+// the objects an object refers to, which are reachable if it is.
+fn references(obj: &Obj, out: &mut Vec<ObjRef>) {
+    let mut value = |value: &Value| out.extend(value.as_obj());
+    match obj {
+        Obj::String(_) | Obj::Native(_) | Obj::Upvalue(Upvalue::Open(_)) => {}
+        Obj::Upvalue(Upvalue::Closed(closed)) => value(closed),
+        Obj::Function(function) => {
+            function.chunk.constants.iter().for_each(&mut value);
+            out.extend(function.name);
+        }
+        Obj::Closure(closure) => {
+            out.push(closure.function);
+            out.extend(&closure.upvalues);
+        }
+        Obj::Class(class) => {
+            out.push(class.name);
+            for (name, method) in class.methods.iter() {
+                out.extend([*name, *method]);
+            }
+        }
+        Obj::Instance(instance) => {
+            instance.fields.values().for_each(&mut value);
+            out.push(instance.class);
+            out.extend(instance.fields.keys());
+        }
+        Obj::BoundMethod(bound) => {
+            value(&bound.receiver);
+            out.push(bound.method);
+        }
+    }
+}
+
+// roughly the memory an object uses, which decides how often to collect.
+fn size(obj: &Obj) -> usize {
+    let contents = match obj {
+        Obj::String(string) => string.len(),
+        Obj::Function(function) => {
+            function.chunk.code.len() + function.chunk.constants.len() * mem::size_of::<Value>()
+        }
+        Obj::Closure(closure) => closure.upvalues.len() * mem::size_of::<ObjRef>(),
+        Obj::Class(class) => class.methods.capacity() * mem::size_of::<(ObjRef, ObjRef)>(),
+        Obj::Instance(instance) => instance.fields.capacity() * mem::size_of::<(ObjRef, Value)>(),
+        Obj::Native(_) | Obj::Upvalue(_) | Obj::BoundMethod(_) => 0,
+    };
+    mem::size_of::<Entry>() + contents
+}
+
+pub struct Display<'h> {
+    heap: &'h Heap,
+    value: Value,
+}
+
+impl<'h> fmt::Display for Display<'h> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let heap = self.heap;
+        let value = self.value;
+        let reference = match value.as_obj() {
+            Some(reference) => reference,
+            None => match (value.as_bool(), value.as_number()) {
+                (Some(value), _) => return write!(f, "{}", value),
+                (_, Some(value)) => return write!(f, "{}", value),
+                _ => return write!(f, "nil"),
+            },
+        };
+        match heap.get(reference) {
+            Obj::String(string) => write!(f, "{}", string),
+            Obj::Function(function) => match function.name {
+                Some(name) => write!(f, "<fn {}>", heap.string(name)),
+                None => write!(f, "<script>"),
+            },
+            Obj::Native(_) => write!(f, "<native fn>"),
+            Obj::Closure(closure) => write!(f, "{}", heap.display(Value::obj(closure.function))),
+            Obj::Upvalue(_) => write!(f, "upvalue"),
+            Obj::Class(class) => write!(f, "{}", heap.string(class.name)),
+            Obj::Instance(instance) => {
+                let class = heap.class(instance.class);
+                write!(f, "{} instance", heap.string(class.name))
+            }
+            Obj::BoundMethod(bound) => write!(f, "{}", heap.display(Value::obj(bound.method))),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::chunk::OpCode;
+
+// how many of the most run instructions and pairs the report lists.
+const LISTED: usize = 12;
+
+/// How many times the VM ran each instruction, and each pair of instructions that ran one after
+/// the other. Pairs that run often are candidates for superinstructions.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    instructions: HashMap<OpCode, usize>,
+    pairs: HashMap<(OpCode, OpCode), usize>,
+    previous: Option<OpCode>,
+}
+
+impl Profile {
+    pub(crate) fn record(&mut self, op: OpCode) {
+        *self.instructions.entry(op).or_insert(0) += 1;
+        if let Some(previous) = self.previous {
+            *self.pairs.entry((previous, op)).or_insert(0) += 1;
+        }
+        self.previous = Some(op);
+    }
+
+    /// How many instructions ran.
+    pub fn total(&self) -> usize {
+        self.instructions.values().sum()
+    }
+
+    /// How many times `op` ran.
+    pub fn count(&self, op: OpCode) -> usize {
+        self.instructions.get(&op).copied().unwrap_or(0)
+    }
+
+    /// How many times `second` ran right after `first`.
+    pub fn pair_count(&self, first: OpCode, second: OpCode) -> usize {
+        self.pairs.get(&(first, second)).copied().unwrap_or(0)
+    }
+}
+
+// the entries that ran most, most first, ties in name order.
+fn most_run<K>(counts: &HashMap<K, usize>, name: impl Fn(&K) -> String) -> Vec<(String, usize)> {
+    let mut entries: Vec<(String, usize)> = counts
+        .iter()
+        .map(|(key, count)| (name(key), *count))
+        .collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    entries.truncate(LISTED);
+    entries
+}
+
+/// A report for `rlox run --profile`:
+///
+/// ```text
+/// profile: 1234567 instructions
+/// most run instructions:
+///   OP_GET_LOCAL                           345678  28.0%
+/// ...
+/// most run pairs:
+///   OP_GET_LOCAL OP_GET_LOCAL              123456  10.0%
+/// ...
+/// ```
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        writeln!(f, "profile: {} instructions", total)?;
+        let percent = |count: usize| 100.0 * count as f64 / total.max(1) as f64;
+        writeln!(f, "most run instructions:")?;
+        for (name, count) in most_run(&self.instructions, |op| op.name().to_string()) {
+            writeln!(f, "  {:<36} {:>8} {:>5.1}%", name, count, percent(count))?;
+        }
+        writeln!(f, "most run pairs:")?;
+        let pairs = most_run(&self.pairs, |(first, second)| {
+            format!("{} {}", first.name(), second.name())
+        });
+        for (name, count) in pairs {
+            writeln!(f, "  {:<36} {:>8} {:>5.1}%", name, count, percent(count))?;
+        }
+        Ok(())
+    }
+}
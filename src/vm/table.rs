@@ -0,0 +1,244 @@
+use std::mem;
+
+use super::object::ObjRef;
+
+/// A key of a `Table`: something with a hash that's equal when the keys are.
+pub trait Key: Copy + Eq {
+    fn hash(&self) -> u32;
+}
+
+/// Strings are interned, so tables keyed by them compare and hash handles rather than text.
+impl Key for ObjRef {
+    fn hash(&self) -> u32 {
+        // handles are mostly consecutive, so mix their bits before they pick a bucket: this is
+        // the finalizer of MurmurHash3.
+        let mut hash = self.0;
+        hash ^= hash >> 16;
+        hash = hash.wrapping_mul(0x85eb_ca6b);
+        hash ^= hash >> 13;
+        hash = hash.wrapping_mul(0xc2b2_ae35);
+        hash ^ hash >> 16
+    }
+}
+
+// a table is grown once its entries and tombstones would fill more than this fraction of it.
+const MAX_LOAD: (usize, usize) = (3, 4);
+const MIN_CAPACITY: usize = 8;
+
+#[derive(Clone)]
+enum Bucket<K, V> {
+    Empty,
+    // a removed entry, which lookups probe past to the entries that collided with it.
+    Tombstone,
+    Full(K, V),
+}
+
+/// A hash table with open addressing and linear probing, as clox's: entries are stored in one
+/// array, and a key that collides with another goes in the next free bucket. Removing an entry
+/// leaves a tombstone so that the entries after it can still be found; tombstones are dropped
+/// when the table is resized.
+#[derive(Clone)]
+pub struct Table<K, V> {
+    // a power of two long, or empty.
+    buckets: Vec<Bucket<K, V>>,
+    len: usize,
+    tombstones: usize,
+}
+
+impl<K: Key, V> Default for Table<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Key, V> Table<K, V> {
+    pub fn new() -> Table<K, V> {
+        Table {
+            buckets: vec![],
+            len: 0,
+            tombstones: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of buckets, which is what the table's memory grows with.
+    pub fn capacity(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        match self
+            .buckets
+            .get(self.find(key.hash(), |other| other == key)?)
+        {
+            Some(Bucket::Full(_, value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let index = self.find(key.hash(), |other| other == key)?;
+        match &mut self.buckets[index] {
+            Bucket::Full(_, value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// The key with `hash` that `matches` accepts, for finding keys by something other than
+    /// themselves, like an interned string by its text.
+    pub fn find_key(&self, hash: u32, matches: impl Fn(&K) -> bool) -> Option<K> {
+        match self.buckets.get(self.find(hash, matches)?) {
+            Some(Bucket::Full(key, _)) => Some(*key),
+            _ => None,
+        }
+    }
+
+    /// The bucket holding `key`, for looking it up again with `get_at`. The key stays in its
+    /// bucket until it's removed or the table is resized.
+    pub fn index_of(&self, key: &K) -> Option<usize> {
+        let index = self.find(key.hash(), |other| other == key)?;
+        match self.buckets[index] {
+            Bucket::Full(..) => Some(index),
+            _ => None,
+        }
+    }
+
+    /// The value of `key` if it's in bucket `index`, without hashing it: tables that had the
+    /// same keys inserted in the same order have them in the same buckets.
+    pub fn get_at(&self, index: usize, key: &K) -> Option<&V> {
+        match self.buckets.get(index) {
+            Some(Bucket::Full(other, value)) if other == key => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_at_mut(&mut self, index: usize, key: &K) -> Option<&mut V> {
+        match self.buckets.get_mut(index) {
+            Some(Bucket::Full(other, value)) if other == key => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Sets the value of a key, and returns the value it replaces.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if (self.len + self.tombstones + 1) * MAX_LOAD.1 > self.buckets.len() * MAX_LOAD.0 {
+            self.resize();
+        }
+        let index = self
+            .find(key.hash(), |other| *other == key)
+            .expect("the table has free buckets");
+        match mem::replace(&mut self.buckets[index], Bucket::Full(key, value)) {
+            Bucket::Full(_, old) => Some(old),
+            Bucket::Tombstone => {
+                self.tombstones -= 1;
+                self.len += 1;
+                None
+            }
+            Bucket::Empty => {
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    /// Removes a key, leaving a tombstone, and returns its value.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let index = self.find(key.hash(), |other| other == key)?;
+        match mem::replace(&mut self.buckets[index], Bucket::Tombstone) {
+            Bucket::Full(_, value) => {
+                self.len -= 1;
+                self.tombstones += 1;
+                Some(value)
+            }
+            bucket => {
+                self.buckets[index] = bucket;
+                None
+            }
+        }
+    }
+
+    /// Removes the entries `keep` rejects.
+    pub fn retain(&mut self, mut keep: impl FnMut(&K, &mut V) -> bool) {
+        for bucket in &mut self.buckets {
+            if let Bucket::Full(key, value) = bucket {
+                if !keep(key, value) {
+                    *bucket = Bucket::Tombstone;
+                    self.len -= 1;
+                    self.tombstones += 1;
+                }
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.buckets.iter().filter_map(|bucket| match bucket {
+            Bucket::Full(key, value) => Some((key, value)),
+            _ => None,
+        })
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    // the bucket of the key with `hash` that `matches` accepts, or if there's none, the
+    // bucket to insert it in: the first tombstone on the way to an empty bucket, or that
+    // bucket. `None` if the table has no buckets.
+    fn find(&self, hash: u32, matches: impl Fn(&K) -> bool) -> Option<usize> {
+        let mask = self.buckets.len().checked_sub(1)?;
+        let mut index = hash as usize & mask;
+        let mut tombstone = None;
+        loop {
+            match &self.buckets[index] {
+                Bucket::Empty => return Some(tombstone.unwrap_or(index)),
+                Bucket::Tombstone => {
+                    tombstone.get_or_insert(index);
+                }
+                Bucket::Full(key, _) if matches(key) => return Some(index),
+                Bucket::Full(..) => {}
+            }
+            index = (index + 1) & mask;
+        }
+    }
+
+    // rehashes the entries into a table with room for one more, dropping the tombstones. The
+    // table only grows if it's the entries rather than the tombstones that fill it.
+    fn resize(&mut self) {
+        let mut capacity = self.buckets.len().max(MIN_CAPACITY);
+        while (self.len + 1) * MAX_LOAD.1 > capacity * MAX_LOAD.0 {
+            capacity *= 2;
+        }
+        let buckets = (0..capacity).map(|_| Bucket::Empty).collect();
+        let old = mem::replace(&mut self.buckets, buckets);
+        self.len = 0;
+        self.tombstones = 0;
+        for bucket in old {
+            if let Bucket::Full(key, value) = bucket {
+                self.insert(key, value);
+            }
+        }
+    }
+}
+
+impl<K: Key, V> Extend<(K, V)> for Table<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+        for (key, value) in entries {
+            self.insert(key, value);
+        }
+    }
+}
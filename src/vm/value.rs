@@ -0,0 +1,205 @@
+use std::fmt;
+
+use super::object::ObjRef;
+
+/// A value on the VM's stack, in a constant pool or in a variable: `nil`, a boolean, a number
+/// or a heap object. Heap objects are referred to by handle, so values are small and `Copy`.
+///
+/// By default a value is a tagged enum. With the `nan-boxing` feature it's a single `f64`
+/// whose NaN bit patterns, which arithmetic never produces, encode the other kinds. Both
+/// representations have this API; code outside this module doesn't know which one it has.
+#[derive(Clone, Copy)]
+pub struct Value(Repr);
+
+impl Value {
+    pub fn nil() -> Value {
+        Value(Repr::NIL)
+    }
+
+    pub fn bool(value: bool) -> Value {
+        Value(Repr::bool(value))
+    }
+
+    pub fn number(value: f64) -> Value {
+        Value(Repr::number(value))
+    }
+
+    /// Strings are interned, so comparing handles compares strings by content.
+    pub fn obj(reference: ObjRef) -> Value {
+        Value(Repr::obj(reference))
+    }
+
+    pub fn is_nil(&self) -> bool {
+        self.0.is_nil()
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        self.0.as_bool()
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        self.0.as_number()
+    }
+
+    pub fn as_obj(&self) -> Option<ObjRef> {
+        self.0.as_obj()
+    }
+
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !self.is_nil() && self.as_bool() != Some(false)
+    }
+}
+
+/// Numbers are equal as `f64`s are, so `NaN` isn't equal to itself; other values are equal
+/// when they're the same `nil`, boolean or object.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self.as_number(), other.as_number()) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.0 == other.0,
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(value) = self.as_bool() {
+            write!(f, "Bool({})", value)
+        } else if let Some(value) = self.as_number() {
+            write!(f, "Number({:?})", value)
+        } else if let Some(reference) = self.as_obj() {
+            write!(f, "Obj({:?})", reference)
+        } else {
+            write!(f, "Nil")
+        }
+    }
+}
+
+#[cfg(not(feature = "nan-boxing"))]
+use tagged::Repr;
+
+#[cfg(feature = "nan-boxing")]
+use nan_boxed::Repr;
+
+#[cfg(not(feature = "nan-boxing"))]
+mod tagged {
+    use super::ObjRef;
+
+    // numbers are compared by `Value`, so the derived comparison only decides the others.
+    #[derive(Clone, Copy, PartialEq)]
+    pub(super) enum Repr {
+        Nil,
+        Bool(bool),
+        Number(f64),
+        Obj(ObjRef),
+    }
+
+    impl Repr {
+        pub(super) const NIL: Repr = Repr::Nil;
+
+        pub(super) fn bool(value: bool) -> Repr {
+            Repr::Bool(value)
+        }
+
+        pub(super) fn number(value: f64) -> Repr {
+            Repr::Number(value)
+        }
+
+        pub(super) fn obj(reference: ObjRef) -> Repr {
+            Repr::Obj(reference)
+        }
+
+        pub(super) fn is_nil(&self) -> bool {
+            matches!(self, Repr::Nil)
+        }
+
+        pub(super) fn as_bool(&self) -> Option<bool> {
+            match self {
+                Repr::Bool(value) => Some(*value),
+                _ => None,
+            }
+        }
+
+        pub(super) fn as_number(&self) -> Option<f64> {
+            match self {
+                Repr::Number(value) => Some(*value),
+                _ => None,
+            }
+        }
+
+        pub(super) fn as_obj(&self) -> Option<ObjRef> {
+            match self {
+                Repr::Obj(reference) => Some(*reference),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nan-boxing")]
+mod nan_boxed {
+    use super::ObjRef;
+
+    // a quiet NaN with the bit below the quiet bit also set: hardware and `f64::NAN` produce
+    // NaNs without it, and numbers that are NaN are stored as `f64::NAN`, so no number has
+    // these bits. Below them, `nil` and the booleans are small tags; objects also set the sign
+    // bit and keep their handle in the low 32 bits.
+    const QUIET_NAN: u64 = 0x7ffc_0000_0000_0000;
+    const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+    const OBJ: u64 = SIGN_BIT | QUIET_NAN;
+    const TAG_NIL: u64 = 1;
+    const TAG_FALSE: u64 = 2;
+    const TAG_TRUE: u64 = 3;
+
+    #[derive(Clone, Copy, PartialEq)]
+    pub(super) struct Repr(u64);
+
+    impl Repr {
+        pub(super) const NIL: Repr = Repr(QUIET_NAN | TAG_NIL);
+
+        pub(super) fn bool(value: bool) -> Repr {
+            Repr(QUIET_NAN | if value { TAG_TRUE } else { TAG_FALSE })
+        }
+
+        pub(super) fn number(value: f64) -> Repr {
+            if value.is_nan() {
+                Repr(f64::NAN.to_bits())
+            } else {
+                Repr(value.to_bits())
+            }
+        }
+
+        pub(super) fn obj(reference: ObjRef) -> Repr {
+            Repr(OBJ | reference.0 as u64)
+        }
+
+        pub(super) fn is_nil(&self) -> bool {
+            *self == Repr::NIL
+        }
+
+        pub(super) fn as_bool(&self) -> Option<bool> {
+            match self.0 {
+                bits if bits == QUIET_NAN | TAG_TRUE => Some(true),
+                bits if bits == QUIET_NAN | TAG_FALSE => Some(false),
+                _ => None,
+            }
+        }
+
+        pub(super) fn as_number(&self) -> Option<f64> {
+            if self.0 & QUIET_NAN == QUIET_NAN {
+                None
+            } else {
+                Some(f64::from_bits(self.0))
+            }
+        }
+
+        pub(super) fn as_obj(&self) -> Option<ObjRef> {
+            if self.0 & OBJ == OBJ {
+                Some(ObjRef(self.0 as u32))
+            } else {
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn rlox(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn text(bytes: &[u8]) -> &str {
+    std::str::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn eval_runs_the_code_it_is_given() {
+    let output = rlox(&["-e", "var a = 1; print a + 2;"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(text(&output.stdout), "3\n");
+
+    let output = rlox(&["--eval", "print nil + 1;"], "");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        text(&output.stderr),
+        "Operands must be two numbers or two strings.\n[line 1]\n"
+    );
+}
+
+#[test]
+fn eval_conflicts_with_a_path_and_with_commands() {
+    for args in [
+        &["-e", "print 1;", "script.lox"][..],
+        &["-e", "print 1;", "check", "script.lox"],
+        &["--eval", "print 1;", "run", "-"],
+    ] {
+        let output = rlox(args, "print 2;");
+        assert_eq!(output.status.code(), Some(64), "{:?}", args);
+        assert!(output.stdout.is_empty(), "{:?}", args);
+    }
+}
+
+#[test]
+fn run_reads_the_script_from_stdin_given_a_dash() {
+    let output = rlox(
+        &["run", "-", "first", "second"],
+        "print argc();\nprint argv(1);\n",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(text(&output.stdout), "2\nsecond\n");
+}
+
+#[test]
+fn trace_prints_each_statement_before_running_it() {
+    let source = "var a = 1;\nwhile (a < 3) {\n  a = a + 1;\n}\nprint a;\n";
+    let output = rlox(&["--trace", "run", "-"], source);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(text(&output.stdout), "3\n");
+    assert_eq!(
+        text(&output.stderr),
+        "\
+[line 1] var a = 1;
+[line 2] while (a < 3) ...
+[line 2] { ... }
+[line 3] a = a + 1;
+[line 2] { ... }
+[line 3] a = a + 1;
+[line 5] print a;
+"
+    );
+    // the flag is global, so it may also come after `-e`'s code.
+    let output = rlox(&["-e", "print 1;", "--trace"], "");
+    assert_eq!(text(&output.stderr), "[line 1] print 1;\n");
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// every fixture runs on each backend, which must print what the fixture's comments say and
+// report the same errors as the others. The vm also runs with a collection on every allocation,
+// so that objects the collector frees too early are used while the program still needs them,
+// with each of its collectors.
+const BACKENDS: &[&[&str]] = &[
+    &["--backend", "tree"],
+    &["--backend", "vm"],
+    &["--backend", "vm", "--stress-gc"],
+    &["--backend", "vm", "--stress-gc", "--gc", "generational"],
+];
+
+// fixture programs state what they print and how they exit in comments, as in the book's test
+// suite: `// expect: <line>` for each line of standard output and `// expect exit: <code>` for a
+// non-zero exit status.
+struct Expectation {
+    output: Vec<String>,
+    exit_code: i32,
+}
+
+impl Expectation {
+    fn parse(source: &str) -> Expectation {
+        let mut expectation = Expectation {
+            output: vec![],
+            exit_code: 0,
+        };
+        for line in source.lines() {
+            if let Some(code) = annotation(line, "// expect exit: ") {
+                expectation.exit_code = code.parse().expect("exit code");
+            } else if let Some(output) = annotation(line, "// expect: ") {
+                expectation.output.push(output.to_string());
+            }
+        }
+        expectation
+    }
+}
+
+fn annotation<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    line.find(marker)
+        .map(|i| line[i + marker.len()..].trim_end())
+}
+
+fn fixtures() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut paths: Vec<PathBuf> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lox"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn run(backend: &[&str], path: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("run")
+        .args(backend)
+        .arg(path)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn fixtures_print_and_exit_as_annotated_on_every_backend() {
+    let mut failures = vec![];
+    for path in fixtures() {
+        let expectation = Expectation::parse(&fs::read_to_string(&path).unwrap());
+        for backend in BACKENDS {
+            let output = run(backend, &path);
+            let stdout: Vec<String> = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(String::from)
+                .collect();
+            if stdout != expectation.output {
+                failures.push(format!(
+                    "{} with {:?}: expected output {:?}, got {:?}",
+                    path.display(),
+                    backend,
+                    expectation.output,
+                    stdout
+                ));
+            }
+            if output.status.code() != Some(expectation.exit_code) {
+                failures.push(format!(
+                    "{} with {:?}: expected exit code {}, got {:?}\n{}",
+                    path.display(),
+                    backend,
+                    expectation.exit_code,
+                    output.status.code(),
+                    String::from_utf8_lossy(&output.stderr)
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn backends_report_the_same_errors() {
+    let mut failures = vec![];
+    for path in fixtures() {
+        let reference = run(BACKENDS[0], &path);
+        for backend in &BACKENDS[1..] {
+            let output = run(backend, &path);
+            if output.stderr != reference.stderr {
+                failures.push(format!(
+                    "{}: {:?} reported {:?}, {:?} reported {:?}",
+                    path.display(),
+                    BACKENDS[0],
+                    String::from_utf8_lossy(&reference.stderr),
+                    backend,
+                    String::from_utf8_lossy(&output.stderr)
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+// the optimizer mustn't change what a program does: its output, errors and exit status.
+#[test]
+fn optimizing_keeps_behaviour_the_same() {
+    let mut failures = vec![];
+    for path in fixtures() {
+        for backend in &BACKENDS[..2] {
+            let optimized = run(backend, &path);
+            let unoptimized = run(&[*backend, &["--no-optimize"]].concat(), &path);
+            if (optimized.status, &optimized.stdout, &optimized.stderr)
+                != (unoptimized.status, &unoptimized.stdout, &unoptimized.stderr)
+            {
+                failures.push(format!(
+                    "{} with {:?}: optimized {:?}, unoptimized {:?}",
+                    path.display(),
+                    backend,
+                    optimized,
+                    unoptimized
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn unknown_backends_are_usage_errors() {
+    let path = fixtures().remove(0);
+    assert_eq!(run(&["--backend", "jit"], &path).status.code(), Some(64));
+    assert_eq!(run(&["--stress-gc"], &path).status.code(), Some(64));
+    assert_eq!(run(&["--profile"], &path).status.code(), Some(64));
+    assert_eq!(
+        run(&["--gc", "generational"], &path).status.code(),
+        Some(64)
+    );
+    let unknown = run(&["--backend", "vm", "--gc", "copying"], &path);
+    assert_eq!(unknown.status.code(), Some(64));
+}
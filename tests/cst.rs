@@ -0,0 +1,95 @@
+use proptest::prelude::*;
+
+use rlox::cst::{self, GreenNode, GreenToken, SyntaxNode};
+use rlox::lox::Lox;
+
+fn parse(source: &str) -> SyntaxNode {
+    cst::parse(source, &mut Lox::new())
+}
+
+// kinds of the nodes on the path down to the token at `offset`.
+fn path_to(root: &SyntaxNode, offset: usize) -> Vec<&'static str> {
+    let token = root.token_at(offset).unwrap();
+    let mut path = vec![token.kind()];
+    let mut node = Some(token.parent());
+    while let Some(current) = node {
+        path.push(current.kind());
+        node = current.parent();
+    }
+    path.reverse();
+    path
+}
+
+proptest! {
+    #[test]
+    fn any_input_round_trips(source in "[ -~\n\t]{0,80}") {
+        let root = parse(&source);
+        prop_assert_eq!(root.text(), source.as_str());
+        let tokens: String = root.tokens().iter().map(|token| token.text().to_string()).collect();
+        prop_assert_eq!(tokens, source);
+    }
+
+    #[test]
+    fn lox_like_input_round_trips(
+        source in r#"(var|fun|class|if|else|while|for|return|print|this|super|[a-z]{1,3}|[0-9]{1,3}|"[a-z ]*"?|// [a-z]*\n|[(){},.;=<>!+*/-]|\s){0,40}"#
+    ) {
+        prop_assert_eq!(parse(&source).text(), source);
+    }
+}
+
+#[test]
+fn trivia_belongs_to_the_innermost_node_around_it() {
+    let source = "// leading\nfun add(a, b) {\n  return a + b; // sum\n}\n";
+    let root = parse(source);
+    assert_eq!(root.text(), source);
+    assert_eq!(path_to(&root, 0), vec!["Program", "COMMENT"]);
+    assert_eq!(path_to(&root, 11), vec!["Program", "Function", "FUN"]);
+    let plus = source.find('+').unwrap();
+    assert_eq!(
+        path_to(&root, plus),
+        vec!["Program", "Function", "Return", "Binary", "PLUS"]
+    );
+    let comment = source.find("// sum").unwrap();
+    assert_eq!(
+        path_to(&root, comment),
+        vec!["Program", "Function", "COMMENT"]
+    );
+}
+
+#[test]
+fn rejected_characters_are_error_tokens() {
+    let source = "print 1 @ 2;\nvar s = \"unterminated\n";
+    let root = parse(source);
+    assert_eq!(root.text(), source);
+    let errors: Vec<String> = root
+        .tokens()
+        .iter()
+        .filter(|token| token.kind() == "ERROR")
+        .map(|token| token.text().to_string())
+        .collect();
+    assert_eq!(errors, vec!["@", "\"unterminated\n"]);
+}
+
+#[test]
+fn edits_keep_everything_else() {
+    let source = "// config\nvar answer = 41; // TODO\nprint answer;\n";
+    let root = parse(source);
+    let offset = source.find("41").unwrap();
+    let literal = root.token_at(offset).unwrap().parent();
+    assert_eq!(literal.kind(), "Literal");
+    assert_eq!(literal.range(), offset..offset + 2);
+
+    let replacement = GreenNode::new(
+        "Literal",
+        vec![cst::GreenElement::Token(GreenToken::new("NUMBER", "42"))],
+    );
+    let edited = SyntaxNode::new_root(literal.replace_with(replacement));
+    assert_eq!(
+        edited.text(),
+        "// config\nvar answer = 42; // TODO\nprint answer;\n"
+    );
+    // the untouched statement is shared with the old tree, not copied.
+    let old = root.children()[1].green().clone();
+    let new = edited.children()[1].green().clone();
+    assert!(std::rc::Rc::ptr_eq(&old, &new));
+}
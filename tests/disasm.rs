@@ -0,0 +1,242 @@
+use std::fs;
+use std::process::{Command, Output};
+
+use rlox::vm::chunk::OpCode;
+
+fn rlox(args: &[&str], source: &str) -> Output {
+    let directory = std::env::temp_dir().join(format!("rlox-disasm-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join(format!("{}.lox", args.join("-").replace('/', "")));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .arg(&path)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    output
+}
+
+#[test]
+fn opcodes_round_trip_through_bytes() {
+    let mut names = vec![];
+    for byte in 0..=u8::MAX {
+        if let Some(op) = OpCode::from_byte(byte) {
+            assert_eq!(op as u8, byte);
+            names.push(op.name());
+        }
+    }
+    assert_eq!(
+        OpCode::from_byte(OpCode::Method as u8),
+        Some(OpCode::Method)
+    );
+    let count = names.len();
+    names.sort_unstable();
+    names.dedup();
+    assert_eq!(names.len(), count, "opcode names are unique");
+}
+
+#[test]
+fn disasm_lists_every_function_with_offsets_lines_and_operands() {
+    let source = "\
+fun add(a, b) {
+  return a + b;
+}
+print add(1, \"two\");
+";
+    let output = rlox(&["disasm"], source);
+    assert_eq!(output.status.code(), Some(0));
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        listing,
+        "\
+== <script> ==
+0000    1 OP_CLOSURE          0 <fn add>
+0002    | OP_DEFINE_GLOBAL    1 \"add\"
+0004    4 OP_GET_GLOBAL       1 \"add\"
+0006    | OP_CONSTANT         2 1
+0008    | OP_CONSTANT         3 \"two\"
+0010    | OP_CALL             2
+0012    | OP_PRINT
+0013    | OP_NIL
+0014    | OP_RETURN
+
+== add ==
+0000    2 OP_GET_LOCAL        1
+0002    | OP_GET_LOCAL        2
+0004    | OP_ADD
+0005    | OP_RETURN
+0006    | OP_NIL
+0007    | OP_RETURN
+"
+    );
+}
+
+#[test]
+fn disasm_shows_jump_targets_and_captures() {
+    let source = "\
+class A {
+  m() {
+    fun f() { return this; }
+    while (true) {}
+    return f;
+  }
+}
+";
+    let listing = String::from_utf8(rlox(&["disasm"], source).stdout).unwrap();
+    assert!(listing.contains("== m ==\n0000    3 OP_CLOSURE          0 <fn f>\n0002    |                     local 0\n"), "{}", listing);
+    assert!(listing.contains("0004    4 OP_TRUE\n0005    | OP_JUMP_IF_FALSE    5 -> 12\n0008    | OP_POP\n0009    | OP_LOOP             9 -> 4\n"), "{}", listing);
+    assert!(
+        listing.contains("== f ==\n0000    3 OP_GET_UPVALUE      0\n"),
+        "{}",
+        listing
+    );
+}
+
+#[test]
+fn disasm_shows_upvalues_being_set_and_closed() {
+    let source = "\
+{
+  var a = 1;
+  fun set() { a = 2; }
+}
+";
+    let listing = String::from_utf8(rlox(&["disasm"], source).stdout).unwrap();
+    assert!(
+        listing.contains(
+            "0002    3 OP_CLOSURE          1 <fn set>\n0004    |                     local 1\n"
+        ),
+        "{}",
+        listing
+    );
+    assert!(
+        listing.contains("0006    | OP_POP\n0007    | OP_CLOSE_UPVALUE\n"),
+        "{}",
+        listing
+    );
+    assert!(
+        listing.contains(
+            "== set ==\n0000    3 OP_CONSTANT         0 2\n0002    | OP_SET_UPVALUE      0\n"
+        ),
+        "{}",
+        listing
+    );
+}
+
+#[test]
+fn disasm_reports_static_errors() {
+    let output = rlox(&["disasm"], "print ;");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn trace_execution_prints_the_stack_before_each_instruction() {
+    let output = rlox(
+        &[
+            "run",
+            "--backend",
+            "vm",
+            "--trace-execution",
+            "--no-optimize",
+        ],
+        "print 1 + 2;",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+    let trace = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        trace,
+        "          [ <script> ]
+0000    1 OP_CONSTANT         0 1
+          [ <script> ][ 1 ]
+0002    | OP_CONSTANT         1 2
+          [ <script> ][ 1 ][ 2 ]
+0004    | OP_ADD
+          [ <script> ][ 3 ]
+0005    | OP_PRINT
+          [ <script> ]
+0006    | OP_NIL
+          [ <script> ][ nil ]
+0007    | OP_RETURN
+"
+    );
+}
+
+#[test]
+fn trace_execution_needs_the_vm_backend() {
+    let output = rlox(&["run", "--trace-execution"], "print 1;");
+    assert_eq!(output.status.code(), Some(64));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn methods_are_invoked_and_common_pairs_fused() {
+    let source = "\
+class Counter {
+  increment(by) {
+    this.count = this.count + by;
+    return this.count < 10;
+  }
+}
+Counter().increment(1 - 2);
+";
+    let listing = String::from_utf8(rlox(&["disasm"], source).stdout).unwrap();
+    assert!(
+        listing.contains(
+            "OP_GET_CALLEE       2 \"increment\" cache 0
+0019    | OP_CONSTANT         3 -1
+0021    | OP_INVOKE           1
+"
+        ),
+        "{}",
+        listing
+    );
+    assert!(
+        listing.contains(
+            "== increment ==
+0000    3 OP_GET_LOCAL        0
+0002    | OP_GET_LOCAL_PROPERTY    0    0 \"count\" cache 0
+0007    | OP_GET_LOCAL        1
+0009    | OP_ADD
+0010    | OP_SET_PROPERTY_POP    0 \"count\" cache 1
+0014    4 OP_GET_LOCAL_PROPERTY    0    0 \"count\" cache 2
+0019    | OP_LESS_CONSTANT    1 10
+"
+        ),
+        "{}",
+        listing
+    );
+
+    let plain = String::from_utf8(rlox(&["disasm", "--no-optimize"], source).stdout).unwrap();
+    for fused in [
+        "OP_INVOKE",
+        "OP_GET_LOCAL_PROPERTY",
+        "OP_SET_PROPERTY_POP",
+        "OP_LESS_CONSTANT",
+    ] {
+        assert!(!plain.contains(fused), "{}", plain);
+    }
+    assert!(plain.contains("OP_GET_PROPERTY"), "{}", plain);
+}
+
+#[test]
+fn profile_reports_the_most_run_instructions_and_pairs() {
+    let output = rlox(
+        &["run", "--backend", "vm", "--profile"],
+        "for (var i = 0; i < 100; i = i + 1) {}",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let report = String::from_utf8(output.stderr).unwrap();
+    assert!(report.starts_with("profile: "), "{}", report);
+    // the loop's condition runs once more than its body.
+    let pairs = report.split("most run pairs:\n").nth(1).unwrap();
+    let most_run: Vec<&str> = pairs.lines().next().unwrap().split_whitespace().collect();
+    assert_eq!(
+        most_run[..3],
+        ["OP_GET_LOCAL", "OP_LESS_CONSTANT", "101"],
+        "{}",
+        report
+    );
+}
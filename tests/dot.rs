@@ -0,0 +1,89 @@
+use rlox::dot;
+use rlox::lox::Lox;
+use rlox::parser::Parser;
+use rlox::scanner::Scanner;
+
+fn dot(source: &str) -> String {
+    let mut lox = Lox::new();
+    let tokens = Scanner::new(source).scan_tokens(&mut lox);
+    let statements = Parser::new(&tokens).parse(&mut lox);
+    dot::ast(&statements)
+}
+
+// the graph without the header that sets its fonts and shapes.
+fn body(graph: &str) -> String {
+    assert!(
+        graph.starts_with("digraph ast {\n  ordering=out;\n"),
+        "{}",
+        graph
+    );
+    assert!(graph.ends_with("}\n"), "{}", graph);
+    graph
+        .lines()
+        .skip(4)
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[test]
+fn expressions_label_their_operators_and_operands() {
+    assert_eq!(
+        body(&dot("print -(a + 2) * b;")),
+        r#"  n0 [label="Program"];
+  n1 [label="Print"];
+  n2 [label="Binary\n*"];
+  n3 [label="Unary\n-"];
+  n4 [label="Grouping"];
+  n5 [label="Binary\n+"];
+  n6 [label="Variable\na"];
+  n5 -> n6 [label="left"];
+  n7 [label="Literal\n2"];
+  n5 -> n7 [label="right"];
+  n4 -> n5 [label="expression"];
+  n3 -> n4 [label="operand"];
+  n2 -> n3 [label="left"];
+  n8 [label="Variable\nb"];
+  n2 -> n8 [label="right"];
+  n1 -> n2 [label="expression"];
+  n0 -> n1 [label="statement"];
+}
+"#
+    );
+}
+
+#[test]
+fn statements_label_their_names_and_branches() {
+    assert_eq!(
+        body(&dot("fun f(x, y) { if (x) return nil; else print y.z; }")),
+        r#"  n0 [label="Program"];
+  n1 [label="Function\nf\n(x, y)"];
+  n2 [label="If"];
+  n3 [label="Variable\nx"];
+  n2 -> n3 [label="condition"];
+  n4 [label="Return"];
+  n5 [label="Literal\nnil"];
+  n4 -> n5 [label="value"];
+  n2 -> n4 [label="then"];
+  n6 [label="Print"];
+  n7 [label="Get\nz"];
+  n8 [label="Variable\ny"];
+  n7 -> n8 [label="object"];
+  n6 -> n7 [label="expression"];
+  n2 -> n6 [label="else"];
+  n1 -> n2 [label="body"];
+  n0 -> n1 [label="statement"];
+}
+"#
+    );
+}
+
+#[test]
+fn string_literals_are_quoted_and_escaped() {
+    // Lox strings have no escapes, so the backslash is the string's own.
+    let graph = dot(r#"print "C:\dir";"#);
+    assert!(
+        graph.contains(r#"  n2 [label="Literal\n\"C:\\dir\""];"#),
+        "{}",
+        graph
+    );
+}
@@ -0,0 +1,47 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn rlox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn usage_errors_exit_with_64() {
+    assert_eq!(rlox(&["--no-such-flag"]).status.code(), Some(64));
+    assert_eq!(
+        rlox(&["tokens", "--format", "xml", "-"]).status.code(),
+        Some(64)
+    );
+}
+
+#[test]
+fn unreadable_files_exit_with_66() {
+    let output = rlox(&["run", fixture("does-not-exist.lox").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(66));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does-not-exist.lox"));
+}
+
+#[test]
+fn help_exits_successfully() {
+    assert_eq!(rlox(&["--help"]).status.code(), Some(0));
+}
+
+#[test]
+fn check_reports_static_errors_without_running() {
+    let output = rlox(&["check", fixture("runtime_error.lox").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    let output = rlox(&["check", fixture("parse_error.lox").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(65));
+}
@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use rlox::lox::Lox;
+use rlox::parser::Parser;
+use rlox::printer::AstPrinter;
+use rlox::scanner::Scanner;
+use rlox::types::{Token, Trivia};
+
+// every program the tests know about that parses: the fmt corpus and the run fixtures.
+fn corpus() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let mut paths = vec![];
+    for directory in &["fmt", "fixtures"] {
+        for entry in fs::read_dir(root.join(directory)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "lox") {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    paths
+        .into_iter()
+        .filter(|path| format(&fs::read_to_string(path).unwrap()).is_some())
+        .collect()
+}
+
+fn format(source: &str) -> Option<String> {
+    Lox::new().formatted(source).ok()
+}
+
+fn ast(source: &str) -> String {
+    let mut lox = Lox::new();
+    let tokens = Scanner::new(source).scan_tokens(&mut lox);
+    AstPrinter::Lisp.print_program(&Parser::new(&tokens).parse(&mut lox))
+}
+
+fn comments(source: &str) -> Vec<String> {
+    Scanner::new(source)
+        .trivia(true)
+        .scan_ignoring_errors()
+        .iter()
+        .filter_map(|token| match token {
+            Token::Trivia {
+                span,
+                token: Trivia::Comment,
+            } => Some(source[span.start..span.end].to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    let corpus = corpus();
+    assert!(corpus.len() > 2);
+    for path in corpus {
+        let formatted = format(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            format(&formatted).as_deref(),
+            Some(formatted.as_str()),
+            "{:?}",
+            path
+        );
+    }
+}
+
+#[test]
+fn formatting_keeps_the_program_and_its_comments() {
+    for path in corpus() {
+        let source = fs::read_to_string(&path).unwrap();
+        let formatted = format(&source).unwrap();
+        assert_eq!(ast(&formatted), ast(&source), "{:?}", path);
+        assert_eq!(comments(&formatted), comments(&source), "{:?}", path);
+    }
+}
+
+#[test]
+fn formatted_lines_fit_the_width_where_they_can() {
+    let formatted = format(&fs::read_to_string("tests/fmt/messy.lox").unwrap()).unwrap();
+    assert_eq!(
+        formatted,
+        fs::read_to_string("tests/fmt/messy.expected").unwrap()
+    );
+    assert!(formatted
+        .lines()
+        .all(|line| line.chars().count() <= rlox::formatter::WIDTH));
+}
+
+#[test]
+fn comment_only_bodies_keep_their_closing_braces() {
+    let formatted =
+        format(&fs::read_to_string("tests/fmt/comment_only_bodies.lox").unwrap()).unwrap();
+    assert_eq!(
+        formatted,
+        fs::read_to_string("tests/fmt/comment_only_bodies.expected").unwrap()
+    );
+    assert_eq!(format(&formatted).as_deref(), Some(formatted.as_str()));
+}
+
+#[test]
+fn programs_with_errors_are_not_formatted() {
+    assert_eq!(format("print (1;"), None);
+}
+
+#[test]
+fn check_fails_only_on_unformatted_files() {
+    let check = |path: &str| {
+        Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .args(["fmt", "--check", path])
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap()
+    };
+    let unformatted = check("tests/fmt/messy.lox");
+    assert_eq!(unformatted.status.code(), Some(1));
+    assert!(unformatted.stdout.is_empty());
+    let formatted = check("tests/fmt/messy.expected");
+    assert_eq!(formatted.status.code(), Some(0));
+    assert!(formatted.stdout.is_empty());
+}
@@ -0,0 +1,134 @@
+use rlox::lox::Lox;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+use rlox::vm::gc::GcMode;
+use rlox::vm::{self, Vm};
+
+// runs a program on `vm`, which is left with the heap the program ends with.
+fn run(vm: &mut Vm, source: &str) {
+    let mut lox = Lox::new();
+    let tokens = Scanner::new(source).scan_tokens(&mut lox);
+    let statements = Parser::new(&tokens).parse(&mut lox);
+    Resolver::new().resolve(&statements, &mut lox);
+    let script = vm::compile(&statements, vm, &mut lox);
+    vm.interpret(script).unwrap();
+}
+
+const CHURN: &str = "
+class Pair {
+  init(left, right) {
+    this.left = left;
+    this.right = right;
+    this.method = this.sum;
+  }
+  sum() {
+    return this.left + this.right;
+  }
+}
+var total = 0;
+for (var i = 0; i < 5000; i = i + 1) {
+  var pair = Pair(i, \"\" + \"garbage\");
+  pair.self = pair;
+  fun capture() {
+    return pair;
+  }
+  total = total + pair.left;
+}
+";
+
+#[test]
+fn unreachable_objects_are_freed_as_the_heap_grows() {
+    let mut vm = Vm::new();
+    run(&mut vm, CHURN);
+    // each iteration makes an instance, a bound method, an upvalue and a closure, so there'd
+    // be over 20000 objects without collections.
+    assert!(
+        vm.heap().object_count() < 10000,
+        "{} objects",
+        vm.heap().object_count()
+    );
+}
+
+#[test]
+fn stress_mode_keeps_only_reachable_objects() {
+    let mut vm = Vm::new();
+    vm.set_stress_gc(true);
+    run(&mut vm, CHURN);
+    // the script, the class and its methods, the natives and their names, and the last pair.
+    assert!(
+        vm.heap().object_count() < 50,
+        "{} objects",
+        vm.heap().object_count()
+    );
+}
+
+#[test]
+fn collected_strings_leave_the_intern_table() {
+    let mut vm = Vm::new();
+    vm.set_stress_gc(true);
+    run(
+        &mut vm,
+        "var kept = \"a\" + \"b\"; { var dropped = \"c\" + \"d\"; } var again = \"c\" + \"d\";",
+    );
+    // a failed check reads an undefined variable, which fails the run.
+    run(
+        &mut vm,
+        "if (kept != \"ab\" or again != \"cd\") print mismatch;",
+    );
+}
+
+#[test]
+fn generational_collections_are_mostly_minor() {
+    let mut vm = Vm::new();
+    vm.set_gc_mode(GcMode::Generational);
+    run(&mut vm, CHURN);
+    let stats = vm.heap().stats();
+    assert!(stats.minor_collections > 0, "{}", stats);
+    assert!(stats.bytes_freed > 0, "{}", stats);
+    assert_eq!(stats.pauses.iter().sum::<usize>(), stats.collections());
+    assert!(
+        vm.heap().object_count() < 10000,
+        "{} objects",
+        vm.heap().object_count()
+    );
+}
+
+#[test]
+fn mark_sweep_collections_are_all_major() {
+    let mut vm = Vm::new();
+    vm.set_stress_gc(true);
+    run(&mut vm, "var a = \"a\" + \"b\";");
+    let stats = vm.heap().stats();
+    assert!(stats.major_collections > 0, "{}", stats);
+    assert_eq!(stats.minor_collections, 0, "{}", stats);
+}
+
+#[test]
+fn young_objects_stored_in_old_ones_survive_minor_collections() {
+    let mut vm = Vm::new();
+    vm.set_gc_mode(GcMode::Generational);
+    vm.set_stress_gc(true);
+    // every allocation collects, so the box, the class and the closed upvalue are old by the
+    // time the new strings and closures are stored in them.
+    run(
+        &mut vm,
+        "
+class Box {}
+var box = Box();
+fun counter() {
+  var name = \"\";
+  fun rename() { name = name + \"n\"; return name; }
+  return rename;
+}
+var rename = counter();
+rename();
+box.field = \"a\" + \"b\";
+class Sub < Box { method() { return \"e\" + \"f\"; } }
+var sub = Sub();
+var junk = \"x\" + \"y\";
+junk = Box();
+if (box.field != \"ab\" or rename() != \"nn\" or sub.method() != \"ef\") print mismatch;
+",
+    );
+}
@@ -0,0 +1,30 @@
+use rlox::highlight::{self, Highlight};
+
+#[test]
+fn pieces_cover_the_whole_source() {
+    let source = "// greet\nfun hi(name) {\n  print \"hi \" + name; @\n}\nvar s = \"open";
+    let pieces = highlight::pieces(source);
+    let text: String = pieces.iter().map(|(text, _)| *text).collect();
+    assert_eq!(text, source);
+    assert_eq!(pieces[0], ("// greet", Some(Highlight::Comment)));
+    assert!(pieces.contains(&("fun", Some(Highlight::Keyword))));
+    assert!(pieces.contains(&("\"hi \"", Some(Highlight::String))));
+    assert!(pieces.contains(&("@", None)));
+}
+
+#[test]
+fn html_escapes_and_classifies() {
+    assert_eq!(
+        highlight::html("print 1 < \"&\";"),
+        concat!(
+            "<pre class=\"lox\"><span class=\"keyword\">print</span> ",
+            "<span class=\"number\">1</span> <span class=\"operator\">&lt;</span> ",
+            "<span class=\"string\">&quot;&amp;&quot;</span>;</pre>\n"
+        )
+    );
+}
+
+#[test]
+fn ansi_resets_after_each_highlight() {
+    assert_eq!(highlight::ansi("nil;"), "\x1b[35mnil\x1b[0m;");
+}
@@ -0,0 +1,110 @@
+use rlox::lox::Lox;
+use rlox::parser::Parser;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+use rlox::vm::{self, Vm};
+
+// the property lookups that missed their inline caches while running a program.
+fn misses(source: &str, inline_caching: bool) -> usize {
+    let mut lox = Lox::new();
+    let tokens = Scanner::new(source).scan_tokens(&mut lox);
+    let statements = Parser::new(&tokens).parse(&mut lox);
+    Resolver::new().resolve(&statements, &mut lox);
+    let mut vm = Vm::new();
+    vm.set_inline_caching(inline_caching);
+    let script = vm::compile(&statements, &mut vm, &mut lox);
+    vm.interpret(script).unwrap();
+    vm.cache_misses()
+}
+
+// a loop of a thousand iterations, each getting a field, setting it and calling a method on
+// an instance of the next of `classes` classes, at the same three sites.
+fn shapes(classes: usize) -> String {
+    let mut source = String::new();
+    for class in 0..classes {
+        source.push_str(&format!(
+            "class Shape{0} {{ init() {{ this.size = {0}; }} area() {{ return this.size; }} }}\n\
+             var shape{0} = Shape{0}();\n",
+            class
+        ));
+    }
+    source.push_str("fun pick(k) {\n");
+    for class in 0..classes {
+        source.push_str(&format!("  if (k == {0}) return shape{0};\n", class));
+    }
+    source.push_str(&format!(
+        "}}
+var k = 0;
+var total = 0;
+for (var i = 0; i < 1000; i = i + 1) {{
+  var shape = pick(k);
+  shape.size = shape.size;
+  total = total + shape.area();
+  k = k + 1;
+  if (k == {}) k = 0;
+}}
+",
+        classes
+    ));
+    source
+}
+
+#[test]
+fn monomorphic_sites_look_properties_up_once() {
+    let source = "
+class Counter {
+  init() { this.count = 0; }
+  increment() { this.count = this.count + 1; }
+}
+var counter = Counter();
+for (var i = 0; i < 1000; i = i + 1) counter.increment();
+";
+    // the set in `init`, and each of the four sites in the loop and `increment` once.
+    assert_eq!(misses(source, true), 4);
+    assert_eq!(misses(source, false), 3001);
+}
+
+#[test]
+fn polymorphic_sites_cache_each_class() {
+    // each class misses once at each of the three sites in the loop, and at the sites in its
+    // initializer and `area`.
+    assert_eq!(misses(&shapes(1), true), 5);
+    assert_eq!(misses(&shapes(4), true), 20);
+}
+
+#[test]
+fn megamorphic_sites_look_up_the_classes_they_cant_cache() {
+    // a third of the iterations are on the two classes after the first four.
+    let misses = misses(&shapes(6), true);
+    assert!((1000..1100).contains(&misses), "{} misses", misses);
+}
+
+#[test]
+fn new_classes_invalidate_cached_methods() {
+    let source = "
+class Base { name() { return \"base\"; } }
+fun name(object) { return object.name(); }
+for (var i = 0; i < 100; i = i + 1) {
+  class Derived < Base {}
+  name(Derived());
+}
+for (var i = 0; i < 100; i = i + 1) name(Base());
+";
+    // each `Derived` is a new class, and creating it empties the cache: the first loop misses
+    // every time, and the second once.
+    assert_eq!(misses(source, true), 101);
+}
+
+#[test]
+fn fields_shadow_cached_methods() {
+    let source = "
+class Box { value() { return 1; } }
+var box = Box();
+for (var i = 0; i < 10; i = i + 1) {
+  if (i == 5) box.value = 2;
+  box.value;
+}
+";
+    // the method is cached, then the field, once it's set and found by name.
+    assert_eq!(misses(source, true), 3);
+}
@@ -0,0 +1,248 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde_json::{json, Value};
+
+fn rlox(args: &[&str], source: &str) -> Value {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .arg("-")
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+fn fields(object: &Value) -> BTreeSet<&str> {
+    object
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect()
+}
+
+// the source text a span covers, checking that the span has the documented fields.
+fn spanned<'a>(source: &'a str, span: &Value) -> &'a str {
+    assert_eq!(fields(span), BTreeSet::from(["start", "end", "line"]));
+    assert!(span["line"].as_u64().unwrap() >= 1);
+    let (start, end) = (
+        span["start"].as_u64().unwrap(),
+        span["end"].as_u64().unwrap(),
+    );
+    &source[start as usize..end as usize]
+}
+
+fn tokens(source: &str, trivia: bool) -> Vec<Value> {
+    let mut args = vec!["tokens", "--format", "json"];
+    if trivia {
+        args.push("--trivia");
+    }
+    let document = rlox(&args, source);
+    assert_eq!(document["version"], json!(1));
+    let tokens = document["tokens"].as_array().unwrap().clone();
+    for token in &tokens {
+        assert_eq!(token["lexeme"], json!(spanned(source, &token["span"])));
+        let kind = token["kind"].as_str().unwrap();
+        let mut expected = BTreeSet::from(["kind", "lexeme", "span"]);
+        if kind == "NUMBER" || kind == "STRING" {
+            expected.insert("value");
+        }
+        assert_eq!(fields(token), expected, "{}", token);
+    }
+    tokens
+}
+
+#[test]
+fn tokens_have_a_kind_lexeme_span_and_literal_value() {
+    let source = "var x = 1.5 + \"s\"; // note\nprint x >= 2;";
+    let tokens = tokens(source, false);
+    let kinds: Vec<&str> = tokens.iter().map(|t| t["kind"].as_str().unwrap()).collect();
+    assert_eq!(
+        kinds,
+        [
+            "VAR",
+            "IDENTIFIER",
+            "EQUAL",
+            "NUMBER",
+            "PLUS",
+            "STRING",
+            "SEMICOLON",
+            "PRINT",
+            "IDENTIFIER",
+            "GREATER_EQUAL",
+            "NUMBER",
+            "SEMICOLON",
+            "EOF",
+        ]
+    );
+    assert_eq!(tokens[3]["value"], json!(1.5));
+    assert_eq!(tokens[5]["lexeme"], json!("\"s\""));
+    assert_eq!(tokens[5]["value"], json!("s"));
+    assert_eq!(
+        tokens[7]["span"],
+        json!({"start": 27, "end": 32, "line": 2})
+    );
+    assert_eq!(tokens[12]["lexeme"], json!(""));
+}
+
+#[test]
+fn trivia_tokens_cover_the_rest_of_the_source() {
+    let source = "var x = 1; // note\n\n  print x;\n";
+    let tokens = tokens(source, true);
+    let text: String = tokens
+        .iter()
+        .map(|token| token["lexeme"].as_str().unwrap())
+        .collect();
+    assert_eq!(text, source);
+    let trivia: Vec<(&str, &str)> = tokens
+        .iter()
+        .map(|t| (t["kind"].as_str().unwrap(), t["lexeme"].as_str().unwrap()))
+        .filter(|(kind, _)| *kind == "WHITESPACE" || *kind == "COMMENT")
+        .collect();
+    assert!(trivia.contains(&("COMMENT", "// note")), "{:?}", trivia);
+    assert!(trivia.contains(&("WHITESPACE", "\n\n  ")), "{:?}", trivia);
+}
+
+// every node type in the `json` module's table: its attributes, and the roles its children
+// may have.
+const SCHEMA: &[(&str, &[&str], &[&str])] = &[
+    ("Program", &[], &["statement"]),
+    ("Expression", &[], &["expression"]),
+    ("Print", &[], &["expression"]),
+    ("Var", &["name"], &["initializer"]),
+    ("Block", &[], &["statement"]),
+    ("If", &[], &["condition", "then", "else"]),
+    ("While", &[], &["condition", "body"]),
+    (
+        "For",
+        &[],
+        &["initializer", "condition", "increment", "body"],
+    ),
+    ("Function", &["name", "params"], &["body"]),
+    ("Return", &[], &["value"]),
+    ("Class", &["name", "superclass"], &["method"]),
+    ("Literal", &["value"], &[]),
+    ("Variable", &["name"], &[]),
+    ("Assign", &["name"], &["value"]),
+    ("Binary", &["operator"], &["left", "right"]),
+    ("Logical", &["operator"], &["left", "right"]),
+    ("Grouping", &[], &["expression"]),
+    ("Unary", &["operator"], &["operand"]),
+    ("Call", &[], &["callee", "argument"]),
+    ("Get", &["name"], &["object"]),
+    ("Set", &["name"], &["object", "value"]),
+    ("This", &[], &[]),
+    ("Super", &["method"], &[]),
+];
+
+const EVERY_NODE: &str = "\
+var a = 1;
+var u;
+print -a;
+{ a = !true; }
+if (a < 2 and nil) print \"s\"; else print a;
+while (false) a;
+for (var i = 0; i < 1; i = i + 1) (i);
+for (;;) print 1;
+fun f(x, y) { return x; }
+fun g() { return; }
+class B { m() {} }
+class C < B { m() { this.p = super.m; return f(a, 2).q; } }
+";
+
+// the nodes of a tree, parents before their children, by type.
+fn nodes<'a>(node: &'a Value, by_type: &mut BTreeMap<&'a str, Vec<&'a Value>>) {
+    by_type
+        .entry(node["type"].as_str().unwrap())
+        .or_default()
+        .push(node);
+    for child in node["children"].as_array().unwrap() {
+        nodes(&child["node"], by_type);
+    }
+}
+
+#[test]
+fn ast_nodes_follow_the_documented_schema() {
+    let document = rlox(&["ast", "--format", "json"], EVERY_NODE);
+    assert_eq!(document["version"], json!(1));
+    assert_eq!(document["ast"]["type"], json!("Program"));
+    let mut by_type = BTreeMap::new();
+    nodes(&document["ast"], &mut by_type);
+    let documented: BTreeSet<&str> = SCHEMA.iter().map(|(kind, _, _)| *kind).collect();
+    assert_eq!(by_type.keys().copied().collect::<BTreeSet<_>>(), documented);
+    for (kind, attributes, roles) in SCHEMA {
+        for node in &by_type[kind] {
+            spanned(EVERY_NODE, &node["span"]);
+            let mut expected: BTreeSet<&str> = attributes.iter().copied().collect();
+            expected.extend(["type", "span", "children"]);
+            assert_eq!(fields(node), expected, "{}", node);
+            for child in node["children"].as_array().unwrap() {
+                assert_eq!(fields(child), BTreeSet::from(["role", "node"]));
+                assert!(roles.contains(&child["role"].as_str().unwrap()), "{}", node);
+            }
+        }
+    }
+}
+
+#[test]
+fn ast_attributes_and_roles_have_the_documented_values() {
+    let document = rlox(&["ast", "--format", "json"], EVERY_NODE);
+    let mut by_type = BTreeMap::new();
+    nodes(&document["ast"], &mut by_type);
+    let values = |kind: &str, attribute: &str| -> Vec<Value> {
+        by_type[kind]
+            .iter()
+            .map(|node| node[attribute].clone())
+            .collect()
+    };
+    let roles = |node: &Value| -> Vec<String> {
+        node["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|child| child["role"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(values("Var", "name"), [json!("a"), json!("u"), json!("i")]);
+    assert_eq!(roles(by_type["Var"][1]), Vec::<String>::new());
+    assert_eq!(
+        values("Literal", "value")[..5],
+        [json!(1.0), json!(true), json!(2.0), Value::Null, json!("s")]
+    );
+    assert_eq!(values("Unary", "operator"), [json!("-"), json!("!")]);
+    assert_eq!(values("Logical", "operator"), [json!("and")]);
+    assert!(values("Binary", "operator").contains(&json!("<")));
+    assert_eq!(roles(by_type["If"][0]), ["condition", "then", "else"]);
+    assert_eq!(
+        roles(by_type["For"][0]),
+        ["initializer", "condition", "increment", "body"]
+    );
+    assert_eq!(roles(by_type["For"][1]), ["body"]);
+    assert_eq!(roles(by_type["Return"][1]), Vec::<String>::new());
+    assert_eq!(
+        values("Function", "params")[..2],
+        [json!(["x", "y"]), json!([])]
+    );
+    assert_eq!(values("Class", "superclass"), [Value::Null, json!("B")]);
+    assert_eq!(roles(by_type["Class"][1]), ["method"]);
+    assert_eq!(
+        roles(by_type["Call"][0]),
+        ["callee", "argument", "argument"]
+    );
+    assert_eq!(values("Get", "name"), [json!("q")]);
+    assert_eq!(values("Set", "name"), [json!("p")]);
+    assert_eq!(roles(by_type["Set"][0]), ["object", "value"]);
+    assert_eq!(values("Super", "method"), [json!("m")]);
+}
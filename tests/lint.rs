@@ -0,0 +1,157 @@
+use std::process::Command;
+
+use rlox::lint::{Config, Lint, Severity};
+use rlox::lox::Lox;
+
+fn lints_with(source: &str, config: &Config) -> Vec<(Lint, usize)> {
+    Lox::new()
+        .lints(source, config)
+        .unwrap()
+        .iter()
+        .map(|diagnostic| (diagnostic.lint, diagnostic.span.line))
+        .collect()
+}
+
+fn lints(source: &str) -> Vec<(Lint, usize)> {
+    lints_with(source, &Config::default())
+}
+
+#[test]
+fn each_lint_flags_its_pattern() {
+    let cases = [
+        (
+            "fun f(a, _b) { var c = 1; return a; }\nf(1, 2);",
+            vec![(Lint::UnusedVariable, 1)],
+        ),
+        (
+            "fun f() {\n  return 1;\n  print 2;\n}\nf();",
+            vec![(Lint::UnreachableCode, 3)],
+        ),
+        (
+            "fun f(x) {\n  if (x) return 1; else { return 2; }\n  print 3;\n}\nf(1);",
+            vec![(Lint::UnreachableCode, 3)],
+        ),
+        ("var a;\na = 1;\nb = 2;", vec![(Lint::UndeclaredGlobal, 3)]),
+        (
+            "var x = 1;\nprint x == x;\nprint x.y <= x.y;\nprint x == x + 0;",
+            vec![(Lint::SelfComparison, 2), (Lint::SelfComparison, 3)],
+        ),
+        (
+            "if (true) {}\nwhile (false) {\n  // waiting\n}",
+            vec![(Lint::EmptyBlock, 1)],
+        ),
+        (
+            "{\n  var a = 1;\n  fun f(a) { return a; }\n  print f(a);\n}\nvar g = 1;\n{ var g = 2; print g; }",
+            vec![(Lint::Shadowing, 3)],
+        ),
+        (
+            "fun two(a, b) { return a + b; }\nclass C { init(x) { this.x = x; } }\nclass D {}\ntwo(1);\nC();\nD(1);\nclock(1);",
+            vec![
+                (Lint::WrongArity, 4),
+                (Lint::WrongArity, 5),
+                (Lint::WrongArity, 6),
+                (Lint::WrongArity, 7),
+            ],
+        ),
+    ];
+    for (source, expected) in cases.iter() {
+        assert_eq!(&lints(source), expected, "{}", source);
+    }
+}
+
+#[test]
+fn calls_are_only_checked_against_known_arities() {
+    // reassigned or redeclared names, and inherited initializers, could be anything.
+    let source = "fun f(a) { return a; }\nvar g = f;\ng(1, 2);\nfun h() {}\nvar h;\nh(1);\n\
+                  class A { init(a) { this.a = a; } }\nclass B < A {}\nB(1);\n\
+                  {\n  fun l() {}\n  l = f;\n  l(1);\n}";
+    assert_eq!(lints(source), vec![]);
+}
+
+#[test]
+fn diagnostics_have_ids_severities_and_spans() {
+    let source = "var a = 1;\nprint a == a;\nb = 2;";
+    let diagnostics = Lox::new().lints(source, &Config::default()).unwrap();
+    let summary: Vec<(&str, Severity, &str)> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let span = diagnostic.span;
+            (
+                diagnostic.lint.id(),
+                diagnostic.severity,
+                &source[span.start..span.end],
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("self-comparison", Severity::Warning, "a == a"),
+            ("undeclared-global", Severity::Error, "b = 2"),
+        ]
+    );
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "[line 2] Warning: Comparison of an expression with itself using '=='. [self-comparison]"
+    );
+}
+
+#[test]
+fn comments_allow_lints_on_their_line_or_the_next() {
+    let source = "{\n  // lox-lint: allow(unused-variable, empty-block)\n  var a = 1;\n  var b = 2; // lox-lint: allow(unused-variable)\n  var c = 3;\n}";
+    assert_eq!(lints(source), vec![(Lint::UnusedVariable, 5)]);
+}
+
+#[test]
+fn config_sets_severities() {
+    let config =
+        Config::parse("# quieter\nunused-variable = allow\nempty-block = error\n").unwrap();
+    let source = "{ var a; }\nif (true) {}";
+    let diagnostics = Lox::new().lints(source, &config).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].lint, Lint::EmptyBlock);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+
+    assert!(Config::parse("unused = allow").is_err());
+    assert!(Config::parse("shadowing = loud").is_err());
+    assert!(Config::parse("shadowing").is_err());
+}
+
+#[test]
+fn lint_exits_non_zero_only_for_errors() {
+    let dir = std::env::temp_dir().join(format!("rlox-lint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("script.lox");
+    std::fs::write(&script, "{ var unused; }\nfun f() {}\nf(1);\n").unwrap();
+    let config = dir.join("lints");
+    std::fs::write(&config, "wrong-arity = warning\n").unwrap();
+    let lint = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .arg("lint")
+            .arg(&script)
+            .args(args)
+            .env("NO_COLOR", "1")
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+
+    let default = lint(&[]);
+    assert_eq!(default.status.code(), Some(1));
+    let stderr = String::from_utf8(default.stderr).unwrap();
+    assert_eq!(
+        stderr.lines().collect::<Vec<_>>(),
+        vec![
+            "[line 1] Warning: Local variable 'unused' is never used. [unused-variable]",
+            "[line 3] Error: 'f' expects 0 arguments but is called with 1. [wrong-arity]",
+        ]
+    );
+    assert_eq!(lint(&["--config", "lints"]).status.code(), Some(0));
+
+    // `.loxlint` in the working directory is picked up without `--config`.
+    std::fs::write(dir.join(".loxlint"), "wrong-arity = allow\n").unwrap();
+    assert_eq!(lint(&[]).status.code(), Some(0));
+    std::fs::write(dir.join(".loxlint"), "wrong-arity = sometimes\n").unwrap();
+    assert_eq!(lint(&[]).status.code(), Some(64));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
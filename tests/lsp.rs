@@ -0,0 +1,396 @@
+use serde_json::{json, Value};
+
+use rlox::lsp;
+
+const URI: &str = "file:///test.lox";
+
+// a scripted client: messages are queued, then played to the server in one session over
+// in-memory streams, with the protocol's framing both ways.
+struct Client {
+    input: Vec<u8>,
+    next_id: i64,
+}
+
+impl Client {
+    fn new() -> Client {
+        let mut client = Client {
+            input: vec![],
+            next_id: 0,
+        };
+        client.request("initialize", json!({"capabilities": {}}));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        self.input
+            .extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).bytes());
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> i64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}));
+        id
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}));
+    }
+
+    fn open(&mut self, text: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            json!({"textDocument": {"uri": URI, "languageId": "lox", "version": 1, "text": text}}),
+        );
+    }
+
+    fn at(&mut self, method: &str, line: u32, character: u32) -> i64 {
+        self.request(
+            method,
+            json!({
+                "textDocument": {"uri": URI},
+                "position": {"line": line, "character": character},
+                "context": {"includeDeclaration": true},
+            }),
+        )
+    }
+
+    fn document(&mut self, method: &str) -> i64 {
+        self.request(method, json!({"textDocument": {"uri": URI}, "options": {}}))
+    }
+
+    // ends the session properly and returns whether the server agreed, and what it sent.
+    fn finish(mut self) -> Session {
+        self.request("shutdown", json!(null));
+        self.notify("exit", json!(null));
+        let mut output = vec![];
+        let clean = lsp::serve(&self.input[..], &mut output).unwrap();
+        Session {
+            clean,
+            messages: parse_messages(&output),
+        }
+    }
+}
+
+struct Session {
+    clean: bool,
+    messages: Vec<Value>,
+}
+
+impl Session {
+    fn result(&self, id: i64) -> &Value {
+        let response = self
+            .messages
+            .iter()
+            .find(|message| message["id"] == json!(id))
+            .unwrap_or_else(|| panic!("no response to {}", id));
+        &response["result"]
+    }
+
+    fn diagnostics(&self) -> Vec<&Value> {
+        self.messages
+            .iter()
+            .filter(|message| message["method"] == "textDocument/publishDiagnostics")
+            .map(|message| &message["params"]["diagnostics"])
+            .collect()
+    }
+}
+
+fn parse_messages(mut output: &[u8]) -> Vec<Value> {
+    let mut messages = vec![];
+    while !output.is_empty() {
+        let text = std::str::from_utf8(output).unwrap();
+        let header_end = text.find("\r\n\r\n").unwrap();
+        let length: usize = text[..header_end]
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let body = &output[header_end + 4..header_end + 4 + length];
+        messages.push(serde_json::from_slice(body).unwrap());
+        output = &output[header_end + 4 + length..];
+    }
+    messages
+}
+
+fn range(line: u32, start: u32, end: u32) -> Value {
+    json!({
+        "start": {"line": line, "character": start},
+        "end": {"line": line, "character": end},
+    })
+}
+
+const PROGRAM: &str = "var greeting = \"hi\";
+fun greet(name) {
+  var message = greeting + name;
+  print message;
+}
+class Greeter {
+  init(name) { this.name = name; }
+  greet() { greet(this.name); }
+}
+Greeter(\"you\").greet();
+";
+
+#[test]
+fn initialize_advertises_the_capabilities() {
+    let mut client = Client::new();
+    client.request("textDocument/unknown", json!({}));
+    let session = client.finish();
+    assert!(session.clean);
+    let capabilities = &session.result(1)["capabilities"];
+    for capability in &[
+        "definitionProvider",
+        "referencesProvider",
+        "hoverProvider",
+        "documentSymbolProvider",
+        "documentFormattingProvider",
+    ] {
+        assert_eq!(capabilities[capability], json!(true), "{}", capability);
+    }
+    assert_eq!(
+        capabilities["semanticTokensProvider"]["legend"]["tokenTypes"],
+        json!(lsp::TOKEN_TYPES)
+    );
+    let unknown = session
+        .messages
+        .iter()
+        .find(|message| message["id"] == json!(2))
+        .unwrap();
+    assert_eq!(unknown["error"]["code"], json!(-32601));
+}
+
+#[test]
+fn exiting_without_shutdown_is_not_clean() {
+    let mut client = Client::new();
+    client.notify("exit", json!(null));
+    let mut output = vec![];
+    assert!(!lsp::serve(&client.input[..], &mut output).unwrap());
+}
+
+#[test]
+fn diagnostics_follow_the_document() {
+    let mut client = Client::new();
+    client.open("print 1 +;\nvar a = @;\n");
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": {"uri": URI, "version": 2},
+            "contentChanges": [{"text": "{ var unused; }\nreturn 1;\n"}],
+        }),
+    );
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": {"uri": URI, "version": 3},
+            "contentChanges": [{"text": "fun f() {}\nf(1);\n"}],
+        }),
+    );
+    let session = client.finish();
+    let published = session.diagnostics();
+    assert_eq!(published.len(), 3);
+
+    // scanner and parser errors.
+    let errors: Vec<(&Value, &Value)> = published[0]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|diagnostic| (&diagnostic["range"], &diagnostic["message"]))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (&range(0, 9, 10), &json!("Error at ';': Expect expression.")),
+            (&range(1, 8, 9), &json!("Unexpected character")),
+            (&range(1, 9, 10), &json!("Error at ';': Expect expression.")),
+        ]
+    );
+    // resolver errors.
+    assert_eq!(published[1][0]["range"], range(1, 0, 6));
+    assert_eq!(
+        published[1][0]["message"],
+        "Error at 'return': Can't return from top-level code."
+    );
+    // lints, once the program compiles.
+    assert_eq!(published[2][0]["code"], "wrong-arity");
+    assert_eq!(published[2][0]["severity"], 1);
+    assert_eq!(published[2][0]["range"], range(1, 0, 4));
+}
+
+#[test]
+fn definition_and_references_follow_bindings() {
+    let mut client = Client::new();
+    client.open(PROGRAM);
+    // `greeting` in greet's body, `name` in the initializer, `greet` in the method.
+    let greeting = client.at("textDocument/definition", 2, 18);
+    let name = client.at("textDocument/definition", 6, 28);
+    let references = client.at("textDocument/references", 1, 5);
+    let parameter = client.at("textDocument/references", 1, 11);
+    let nothing = client.at("textDocument/definition", 9, 16);
+    let session = client.finish();
+
+    let location = |line, start, end| json!({"uri": URI, "range": range(line, start, end)});
+    assert_eq!(session.result(greeting), &location(0, 4, 12));
+    assert_eq!(session.result(name), &location(6, 7, 11));
+    assert_eq!(
+        session.result(references),
+        &json!([location(1, 4, 9), location(7, 12, 17)])
+    );
+    assert_eq!(
+        session.result(parameter),
+        &json!([location(1, 10, 14), location(2, 27, 31)])
+    );
+    assert_eq!(session.result(nothing), &Value::Null);
+}
+
+#[test]
+fn hover_shows_the_kind_of_declaration() {
+    let mut client = Client::new();
+    client.open(PROGRAM);
+    let requests = [
+        client.at("textDocument/hover", 0, 6),
+        client.at("textDocument/hover", 2, 8),
+        client.at("textDocument/hover", 2, 29),
+        client.at("textDocument/hover", 9, 2),
+        client.at("textDocument/hover", 7, 3),
+    ];
+    let session = client.finish();
+    let hovers: Vec<&Value> = requests
+        .iter()
+        .map(|id| &session.result(*id)["contents"]["value"])
+        .collect();
+    assert_eq!(
+        hovers,
+        vec![
+            "```lox\nvar greeting\n```\nglobal variable",
+            "```lox\nvar message\n```\nlocal variable",
+            "```lox\nname\n```\nparameter",
+            "```lox\nclass Greeter\n```\nclass",
+            "```lox\ngreet()\n```\nmethod of Greeter",
+        ]
+    );
+    assert_eq!(session.result(requests[2])["range"], range(2, 27, 31));
+}
+
+#[test]
+fn document_symbols_list_the_top_level_declarations() {
+    let mut client = Client::new();
+    client.open(PROGRAM);
+    let symbols = client.document("textDocument/documentSymbol");
+    let session = client.finish();
+    let summary: Vec<(Value, Value, Vec<Value>)> = session
+        .result(symbols)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| {
+            let children = symbol["children"].as_array().unwrap();
+            let children = children.iter().map(|child| child["detail"].clone());
+            (
+                symbol["name"].clone(),
+                symbol["kind"].clone(),
+                children.collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (json!("greeting"), json!(13), vec![]),
+            (json!("greet"), json!(12), vec![]),
+            (
+                json!("Greeter"),
+                json!(5),
+                vec![json!("init(name)"), json!("greet()")]
+            ),
+        ]
+    );
+    let greet = &session.result(symbols)[1];
+    assert_eq!(greet["selectionRange"], range(1, 4, 9));
+    assert_eq!(greet["range"]["end"], json!({"line": 4, "character": 1}));
+}
+
+#[test]
+fn semantic_tokens_classify_the_scanner_tokens() {
+    let mut client = Client::new();
+    client.open("// hi\nfun f(a) {\n  print a.b + \"x\ny\" ;\n}\n");
+    let tokens = client.document("textDocument/semanticTokens/full");
+    let session = client.finish();
+    let data: Vec<u64> = session.result(tokens)["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| n.as_u64().unwrap())
+        .collect();
+    let kind = |name: &str| lsp::TOKEN_TYPES.iter().position(|t| *t == name).unwrap() as u64;
+    let expected: Vec<[u64; 5]> = vec![
+        [0, 0, 5, kind("comment"), 0],
+        [1, 0, 3, kind("keyword"), 0],
+        [0, 4, 1, kind("function"), 0],
+        [0, 2, 1, kind("parameter"), 0],
+        [1, 2, 5, kind("keyword"), 0],
+        [0, 6, 1, kind("parameter"), 0],
+        [0, 2, 1, kind("property"), 0],
+        [0, 2, 1, kind("operator"), 0],
+        // a string over two lines is split at the line break.
+        [0, 2, 2, kind("string"), 0],
+        [1, 0, 2, kind("string"), 0],
+    ];
+    assert_eq!(data, expected.concat());
+}
+
+#[test]
+fn formatting_replaces_the_document() {
+    let mut client = Client::new();
+    client.open("var a=1;\nfun f(){return a;}\n");
+    let edits = client.document("textDocument/formatting");
+    client.notify(
+        "textDocument/didChange",
+        json!({"textDocument": {"uri": URI}, "contentChanges": [{"text": "var a = 1;\n"}]}),
+    );
+    let formatted = client.document("textDocument/formatting");
+    client.notify(
+        "textDocument/didChange",
+        json!({"textDocument": {"uri": URI}, "contentChanges": [{"text": "var a = ;\n"}]}),
+    );
+    let broken = client.document("textDocument/formatting");
+    let session = client.finish();
+    assert_eq!(
+        session.result(edits),
+        &json!([{
+            "range": {"start": {"line": 0, "character": 0}, "end": {"line": 2, "character": 0}},
+            "newText": "var a = 1;\nfun f() {\n  return a;\n}\n",
+        }])
+    );
+    assert_eq!(session.result(formatted), &json!([]));
+    assert_eq!(session.result(broken), &Value::Null);
+}
+
+#[test]
+fn formatting_keeps_the_braces_of_comment_only_blocks() {
+    let mut client = Client::new();
+    client.open("while (true) { // loop\n}\n");
+    let edits = client.document("textDocument/formatting");
+    let session = client.finish();
+    assert_eq!(
+        session.result(edits),
+        &json!([{
+            "range": {"start": {"line": 0, "character": 0}, "end": {"line": 2, "character": 0}},
+            "newText": "while (true) {\n  // loop\n}\n",
+        }])
+    );
+}
+
+#[test]
+fn positions_count_utf16_code_units() {
+    let mut client = Client::new();
+    client.open("var s = \"\u{1F600}\"; var t = s;\n");
+    let definition = client.at("textDocument/definition", 0, 22);
+    let session = client.finish();
+    assert_eq!(
+        session.result(definition),
+        &json!({"uri": URI, "range": range(0, 4, 5)})
+    );
+}
@@ -0,0 +1,69 @@
+use rlox::lox::Lox;
+use rlox::optimizer;
+use rlox::parser::Parser;
+use rlox::printer::AstPrinter;
+use rlox::resolver::Resolver;
+use rlox::scanner::Scanner;
+
+// a program as the optimizer leaves it, printed back as source.
+fn optimized(source: &str) -> String {
+    let mut lox = Lox::new();
+    let tokens = Scanner::new(source).scan_tokens(&mut lox);
+    let statements = Parser::new(&tokens).parse(&mut lox);
+    Resolver::new().resolve(&statements, &mut lox);
+    assert!(lox.take_errors().is_empty());
+    AstPrinter::Source.print_program(&optimizer::optimize(statements))
+}
+
+#[test]
+fn operators_on_literals_are_folded() {
+    assert_eq!(optimized("print 1 + 2 * 3;"), "print 7;\n");
+    assert_eq!(optimized("print (1 + 2) * 3 < 10;"), "print true;\n");
+    assert_eq!(optimized("print \"a\" + \"b\" + x;"), "print \"ab\" + x;\n");
+    assert_eq!(optimized("print \"a\" == \"a\";"), "print true;\n");
+    assert_eq!(optimized("print nil != false;"), "print true;\n");
+    assert_eq!(optimized("print !nil;"), "print true;\n");
+    assert_eq!(optimized("print -(2 - 5);"), "print 3;\n");
+}
+
+#[test]
+fn operators_that_would_fail_are_left_to_fail() {
+    assert_eq!(optimized("print 1 + \"b\";"), "print 1 + \"b\";\n");
+    assert_eq!(optimized("print -\"b\";"), "print -\"b\";\n");
+    assert_eq!(optimized("print nil < 1;"), "print nil < 1;\n");
+}
+
+#[test]
+fn logical_operators_on_literals_become_the_operand_they_return() {
+    assert_eq!(optimized("print nil or x;"), "print x;\n");
+    assert_eq!(optimized("print 0 or x;"), "print 0;\n");
+    assert_eq!(optimized("print false and x;"), "print false;\n");
+    assert_eq!(optimized("print x and false;"), "print x and false;\n");
+}
+
+#[test]
+fn double_negations_are_dropped_when_they_keep_the_value() {
+    assert_eq!(optimized("print !!(a < b);"), "print (a < b);\n");
+    assert_eq!(optimized("print -(-(a * 2));"), "print (a * 2);\n");
+    assert_eq!(optimized("print !!!a;"), "print !a;\n");
+    // `!!a` is a boolean and `-(-a)` fails unless `a` is a number.
+    assert_eq!(optimized("print !!a;"), "print !!a;\n");
+    assert_eq!(optimized("print -(-a);"), "print -(-a);\n");
+    assert_eq!(
+        optimized("while (!!a) a = false;"),
+        "while (a) a = false;\n"
+    );
+}
+
+#[test]
+fn branches_that_never_run_are_removed() {
+    assert_eq!(optimized("if (false) print 1; else print 2;"), "print 2;\n");
+    assert_eq!(optimized("if (1 > 2) { print 1; }"), "");
+    assert_eq!(optimized("if (!nil) { print 1; }"), "{\n  print 1;\n}\n");
+    assert_eq!(optimized("while (nil) print 1;"), "");
+    assert_eq!(optimized("while (x) if (false) print 1;"), "while (x) {}\n");
+    assert_eq!(
+        optimized("fun f() { if (true) return 1; return 2; }"),
+        "fun f() {\n  return 1;\n  return 2;\n}\n"
+    );
+}
@@ -0,0 +1,199 @@
+use proptest::prelude::*;
+
+use rlox::lox::Lox;
+use rlox::parser::{BinaryOp, Expr, ExprKind, LiteralValue, LogicalOp, Name, Parser, UnaryOp};
+use rlox::printer::AstPrinter;
+use rlox::scanner::Scanner;
+use rlox::types::Span;
+
+fn parse(source: &str) -> Expr<'_> {
+    let mut lox = Lox::new();
+    let scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens(&mut lox);
+    Parser::new(&tokens)
+        .parse_expression(&mut lox)
+        .unwrap_or_else(|| panic!("failed to parse {:?}", source))
+}
+
+// groupings only record where parentheses were written, so trees are compared without them.
+fn without_groupings(expr: Expr<'_>) -> Expr<'_> {
+    fn strip<'a>(expr: &Expr<'a>) -> Box<Expr<'a>> {
+        Box::new(without_groupings(expr.clone()))
+    }
+    let kind = match expr.kind {
+        ExprKind::Grouping(expr) => return without_groupings(*expr),
+        ExprKind::Assign(name, value) => ExprKind::Assign(name, strip(&value)),
+        ExprKind::Binary(left, operator, right) => {
+            ExprKind::Binary(strip(&left), operator, strip(&right))
+        }
+        ExprKind::Logical(left, operator, right) => {
+            ExprKind::Logical(strip(&left), operator, strip(&right))
+        }
+        ExprKind::Unary(operator, expr) => ExprKind::Unary(operator, strip(&expr)),
+        ExprKind::Call(callee, arguments) => ExprKind::Call(
+            strip(&callee),
+            arguments.into_iter().map(without_groupings).collect(),
+        ),
+        ExprKind::Get(object, name) => ExprKind::Get(strip(&object), name),
+        ExprKind::Set(object, name, value) => ExprKind::Set(strip(&object), name, strip(&value)),
+        kind => kind,
+    };
+    Expr { kind, ..expr }
+}
+
+fn binary(left: Expr<'static>, operator: BinaryOp, right: Expr<'static>) -> Expr<'static> {
+    Expr::from(ExprKind::Binary(Box::new(left), operator, Box::new(right)))
+}
+
+fn unary(operator: UnaryOp, operand: Expr<'static>) -> Expr<'static> {
+    Expr::from(ExprKind::Unary(operator, Box::new(operand)))
+}
+
+fn literal(value: LiteralValue<'static>) -> Expr<'static> {
+    Expr::from(ExprKind::Literal(value))
+}
+
+fn binary_op() -> impl Strategy<Value = BinaryOp> {
+    prop::sample::select(vec![
+        BinaryOp::EqualEqual,
+        BinaryOp::BangEqual,
+        BinaryOp::Less,
+        BinaryOp::LessEqual,
+        BinaryOp::Greater,
+        BinaryOp::GreaterEqual,
+        BinaryOp::Plus,
+        BinaryOp::Minus,
+        BinaryOp::Star,
+        BinaryOp::Slash,
+    ])
+}
+
+fn name() -> impl Strategy<Value = Name<'static>> {
+    prop::sample::select(vec!["a", "foo", "_bar", "x1"]).prop_map(|lexeme| Name {
+        lexeme,
+        span: Span::default(),
+    })
+}
+
+fn expr() -> impl Strategy<Value = Expr<'static>> {
+    let leaf = prop_oneof![
+        Just(literal(LiteralValue::Nil)),
+        Just(literal(LiteralValue::True)),
+        Just(literal(LiteralValue::False)),
+        (0u32..10_000, 0u32..4).prop_map(|(n, quarters)| literal(LiteralValue::Number(
+            n as f64 + quarters as f64 / 4.0
+        ))),
+        prop::sample::select(vec!["", "lox", "two words"])
+            .prop_map(|s| literal(LiteralValue::String(s.into()))),
+        name().prop_map(|name| Expr::from(ExprKind::Variable(name.lexeme))),
+        Just(Expr::from(ExprKind::This)),
+        name().prop_map(|method| Expr::from(ExprKind::Super(method))),
+    ];
+    leaf.prop_recursive(6, 64, 3, |inner| {
+        prop_oneof![
+            (inner.clone(), binary_op(), inner.clone()).prop_map(|(l, op, r)| binary(l, op, r)),
+            (
+                inner.clone(),
+                prop::sample::select(vec![LogicalOp::And, LogicalOp::Or]),
+                inner.clone()
+            )
+                .prop_map(|(l, op, r)| Expr::from(ExprKind::Logical(
+                    Box::new(l),
+                    op,
+                    Box::new(r)
+                ))),
+            (
+                prop::sample::select(vec![UnaryOp::Minus, UnaryOp::Bang]),
+                inner.clone()
+            )
+                .prop_map(|(op, e)| unary(op, e)),
+            inner
+                .clone()
+                .prop_map(|e| Expr::from(ExprKind::Grouping(Box::new(e)))),
+            (name(), inner.clone())
+                .prop_map(|(name, value)| Expr::from(ExprKind::Assign(name, Box::new(value)))),
+            (inner.clone(), prop::collection::vec(inner.clone(), 0..3)).prop_map(
+                |(callee, arguments)| Expr::from(ExprKind::Call(Box::new(callee), arguments))
+            ),
+            (inner.clone(), name())
+                .prop_map(|(object, name)| Expr::from(ExprKind::Get(Box::new(object), name))),
+            (inner.clone(), name(), inner).prop_map(|(object, name, value)| Expr::from(
+                ExprKind::Set(Box::new(object), name, Box::new(value))
+            )),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn source_form_reparses_to_the_same_tree(ast in expr()) {
+        let printed = AstPrinter::source(&ast);
+        let reparsed = parse(&printed);
+        prop_assert_eq!(without_groupings(reparsed), without_groupings(ast));
+    }
+
+    #[test]
+    fn source_form_round_trips_exactly_once_parenthesized(ast in expr()) {
+        // after one trip every required parenthesis is an explicit grouping in the tree.
+        let printed = AstPrinter::source(&ast);
+        let canonical = parse(&printed);
+        let reprinted = AstPrinter::source(&canonical);
+        prop_assert_eq!(&reprinted, &printed);
+        prop_assert_eq!(parse(&reprinted), canonical);
+    }
+}
+
+#[test]
+fn lisp_form_distinguishes_grouping() {
+    assert_eq!(
+        AstPrinter::lisp(&parse("1 * (2 + 3)")),
+        "(* 1 (group (+ 2 3)))"
+    );
+    assert_eq!(AstPrinter::lisp(&parse("1 * 2 + 3")), "(+ (* 1 2) 3)");
+}
+
+#[test]
+fn source_form_inserts_only_necessary_parentheses() {
+    let ast = binary(
+        literal(LiteralValue::Number(1.0)),
+        BinaryOp::Minus,
+        binary(
+            literal(LiteralValue::Number(2.0)),
+            BinaryOp::Minus,
+            binary(
+                literal(LiteralValue::Number(3.0)),
+                BinaryOp::Star,
+                unary(UnaryOp::Minus, Expr::from(ExprKind::Variable("x"))),
+            ),
+        ),
+    );
+    assert_eq!(AstPrinter::source(&ast), "1 - (2 - 3 * -x)");
+    assert_eq!(AstPrinter::source(&parse("(1 + 2) * 3")), "(1 + 2) * 3");
+}
+
+#[test]
+fn source_form_of_a_program_reparses_to_the_same_statements() {
+    let source = "
+        class Greeter < Base {
+            init(name) { this.name = name; }
+            greet() { print \"hi \" + this.name; return super.greet(); }
+        }
+        fun count(n) {
+            for (var i = 0; i < n; i = i + 1) if (i > 2 and i != 4) print i; else {}
+            while (!done) done = check();
+        }
+        if (a) if (b) print 1; else print 2;
+        var g = Greeter(\"lox\").greet;
+    ";
+    let mut lox = Lox::new();
+    let tokens = Scanner::new(source).scan_tokens(&mut lox);
+    let statements = Parser::new(&tokens).parse(&mut lox);
+    let printed = AstPrinter::Source.print_program(&statements);
+
+    let tokens = Scanner::new(&printed).scan_tokens(&mut lox);
+    let reparsed = Parser::new(&tokens).parse(&mut lox);
+    assert_eq!(AstPrinter::Source.print_program(&reparsed), printed);
+    // the dangling else is disambiguated with braces, everything else is unchanged.
+    assert_eq!(reparsed[..2], statements[..2]);
+    assert_eq!(reparsed[3], statements[3]);
+}
@@ -0,0 +1,182 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use rlox::lox::Lox;
+use rlox::repl::complete;
+
+// drives the REPL with piped input; without a terminal there's no prompt or line editing, so
+// standard output holds only what the entries print.
+fn repl(input: &str) -> Output {
+    let history = std::env::temp_dir().join(format!("rlox-test-history-{}", std::process::id()));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("repl")
+        .env("NO_COLOR", "1")
+        .env("RLOX_HISTORY", &history)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let _ = std::fs::remove_file(history);
+    output
+}
+
+fn stdout_lines(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip(1) // the greeting
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn entries_continue_while_incomplete() {
+    let output = repl("fun greet(name) {\n  print \"hi \" + name;\n}\ngreet(\"lox\");\n");
+    assert_eq!(stdout_lines(&output), vec!["hi lox"]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn meta_commands() {
+    let output = repl(":ast 1 + 2 * 3;\nvar a = \"x\";\n:env\n:reset\n:env\n:nope\n");
+    assert_eq!(
+        stdout_lines(&output),
+        vec![
+            "(; (+ 1 (* 2 3)))",
+            "a = \"x\"",
+            "clock = <native fn>",
+            "clock = <native fn>",
+        ]
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Unknown command ':nope'. Try :help.\n"
+    );
+}
+
+#[test]
+fn commands_are_one_line_even_when_their_code_is_incomplete() {
+    let output = repl(":ast 1 + (2\n:tokens \"abc\n1 + 2;\n");
+    assert_eq!(stdout_lines(&output).last().map(String::as_str), Some("3"));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1] Error at end: Expect ')' after expression.\n[line 1] Error: Unterminated string\n"
+    );
+}
+
+#[test]
+fn loaded_files_run_without_echoing_their_values() {
+    let path = std::env::temp_dir().join(format!("rlox-test-load-{}.lox", std::process::id()));
+    std::fs::write(&path, "1 + 2;\nvar loaded = \"yes\";\nprint loaded;\n").unwrap();
+    let output = repl(&format!(":load {}\n4 + 5;\n", path.display()));
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(stdout_lines(&output), vec!["yes", "9"]);
+}
+
+#[test]
+fn expression_statements_print_their_value_and_keep_it_in_underscore() {
+    let output = repl("1 + 2;\n_ * 10;\n\"lox\";\nfun f() {}\nf();\n_;\n");
+    assert_eq!(stdout_lines(&output), vec!["3", "30", "\"lox\"", "\"lox\""]);
+}
+
+#[test]
+fn nil_results_leave_underscore_alone() {
+    let output = repl("\"kept\";\nnil;\n_;\nvar a;\na;\n_;\n");
+    assert_eq!(
+        stdout_lines(&output),
+        vec!["\"kept\"", "\"kept\"", "\"kept\""]
+    );
+}
+
+#[test]
+fn state_survives_errors() {
+    let output = repl(concat!(
+        "var a = 1;\n",
+        "fun f() { return a; }\n",
+        "a = a + nil;\n",
+        "{ var a = 2; -\"x\"; }\n",
+        "print f();\n",
+        "var = 3;\n",
+        "print a;\n",
+    ));
+    assert_eq!(stdout_lines(&output), vec!["1", "1"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Operands must be two numbers or two strings."));
+    assert!(stderr.contains("Operand must be a number."));
+    assert!(stderr.contains("Expect variable name."));
+    assert!(output.status.success());
+}
+
+#[test]
+fn history_is_kept_across_resets_and_saved() {
+    let history = std::env::temp_dir().join(format!("rlox-test-saved-{}", std::process::id()));
+    std::fs::write(&history, "").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("repl")
+        .env("NO_COLOR", "1")
+        .env("RLOX_HISTORY", &history)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"var before = 1;\n:reset\nvar after = 2;\n")
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+    let saved = std::fs::read_to_string(&history).unwrap();
+    let _ = std::fs::remove_file(&history);
+    let entries: Vec<&str> = saved
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    assert_eq!(entries, ["var before = 1;", ":reset", "var after = 2;"]);
+}
+
+#[test]
+fn completes_keywords_globals_and_members() {
+    let mut lox = Lox::new();
+    let mut interpreter = lox.interpreter(&[]);
+    let source = "
+        class Shape { area() {} }
+        class Circle < Shape { init(r) { this.radius = r; } }
+        var circle = Circle(1);
+        var wrapper = Circle(2);
+        wrapper.inner = circle;
+        var count = 0;
+    ";
+    lox.run_in(&mut interpreter, source).unwrap();
+    let globals = interpreter.globals();
+
+    assert_eq!(
+        complete("c", &globals),
+        (
+            0,
+            vec![
+                "circle".into(),
+                "class".into(),
+                "clock".into(),
+                "count".into()
+            ]
+        )
+    );
+    assert_eq!(complete("print wh", &globals), (6, vec!["while".into()]));
+    assert_eq!(
+        complete("circle.", &globals),
+        (7, vec!["area".into(), "init".into(), "radius".into()])
+    );
+    assert_eq!(
+        complete("f(wrapper.inner.r", &globals),
+        (16, vec!["radius".into()])
+    );
+    assert_eq!(complete("count.", &globals), (6, vec![]));
+}
@@ -0,0 +1,116 @@
+use rlox::lox::{Lox, Report};
+use rlox::scanner::Scanner;
+use rlox::types::{Literal, Token};
+
+// each token as its book name and the source text it covers, without the final EOF.
+fn tokens(source: &str) -> Vec<(&'static str, &str)> {
+    let mut lox = Lox::new().collect_errors();
+    let tokens = Scanner::new(source).scan_tokens(&mut lox);
+    assert!(lox.take_errors().is_empty(), "{:?}", source);
+    tokens
+        .iter()
+        .filter(|token| !matches!(token, Token::Eof { .. }))
+        .map(|token| {
+            let span = token.span();
+            (token.kind_name(), &source[span.start..span.end])
+        })
+        .collect()
+}
+
+fn errors(source: &str) -> Vec<Report> {
+    let mut lox = Lox::new().collect_errors();
+    Scanner::new(source).scan_tokens(&mut lox);
+    lox.take_errors()
+}
+
+#[test]
+fn two_character_operators_are_one_token() {
+    assert_eq!(
+        tokens("!= == <= >= ! = < >"),
+        vec![
+            ("BANG_EQUAL", "!="),
+            ("EQUAL_EQUAL", "=="),
+            ("LESS_EQUAL", "<="),
+            ("GREATER_EQUAL", ">="),
+            ("BANG", "!"),
+            ("EQUAL", "="),
+            ("LESS", "<"),
+            ("GREATER", ">"),
+        ]
+    );
+    assert_eq!(
+        tokens("a>=b"),
+        vec![
+            ("IDENTIFIER", "a"),
+            ("GREATER_EQUAL", ">="),
+            ("IDENTIFIER", "b")
+        ]
+    );
+}
+
+#[test]
+fn identifiers_may_start_with_and_contain_underscores() {
+    assert_eq!(
+        tokens("_ _private snake_case var_"),
+        vec![
+            ("IDENTIFIER", "_"),
+            ("IDENTIFIER", "_private"),
+            ("IDENTIFIER", "snake_case"),
+            ("IDENTIFIER", "var_"),
+        ]
+    );
+}
+
+#[test]
+fn numbers_end_before_a_dot_without_digits_after_it() {
+    assert_eq!(
+        tokens("1.5 2.foo 3."),
+        vec![
+            ("NUMBER", "1.5"),
+            ("NUMBER", "2"),
+            ("DOT", "."),
+            ("IDENTIFIER", "foo"),
+            ("NUMBER", "3"),
+            ("DOT", "."),
+        ]
+    );
+    let mut lox = Lox::new();
+    let scanned = Scanner::new("12.25").scan_tokens(&mut lox);
+    assert!(matches!(
+        scanned[0],
+        Token::Literal {
+            token: Literal::Number { literal },
+            ..
+        } if literal == 12.25
+    ));
+}
+
+#[test]
+fn spans_are_byte_ranges_around_multibyte_characters() {
+    assert_eq!(
+        tokens("print \"héllo → wörld\" + x;"),
+        vec![
+            ("PRINT", "print"),
+            ("STRING", "\"héllo → wörld\""),
+            ("PLUS", "+"),
+            ("IDENTIFIER", "x"),
+            ("SEMICOLON", ";"),
+        ]
+    );
+    // identifiers are ASCII, and each other character outside a string is one error.
+    let reported = errors("ü + ï;");
+    assert_eq!(reported.len(), 2);
+    assert!(reported
+        .iter()
+        .all(|report| report.message == "Unexpected character"));
+}
+
+#[test]
+fn errors_report_the_line_they_are_on() {
+    let reported = errors("var a = 1;\n\"multi\nline\" @\n");
+    assert_eq!(reported.len(), 1);
+    assert_eq!(reported[0].line, 3);
+    assert_eq!(reported[0].message, "Unexpected character");
+    let unterminated = errors("print \"open");
+    assert_eq!(unterminated[0].message, "Unterminated string");
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use proptest::prelude::*;
+
+use rlox::vm::object::{Heap, ObjRef};
+use rlox::vm::table::{Key, Table};
+
+// a key whose hash is its value divided by 4, so that keys collide in runs of four.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Colliding(u32);
+
+impl Key for Colliding {
+    fn hash(&self) -> u32 {
+        self.0 / 4
+    }
+}
+
+fn strings(count: usize) -> Vec<ObjRef> {
+    let mut heap = Heap::new();
+    (0..count).map(|i| heap.intern(&i.to_string())).collect()
+}
+
+#[test]
+fn inserted_keys_are_found_until_removed() {
+    let keys = strings(100);
+    let mut table = Table::new();
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(table.insert(*key, i), None);
+    }
+    assert_eq!(table.insert(keys[7], 700), Some(7));
+    assert_eq!(table.len(), 100);
+    assert_eq!(table.get(&keys[7]), Some(&700));
+    assert_eq!(table.remove(&keys[7]), Some(700));
+    assert_eq!(table.remove(&keys[7]), None);
+    assert_eq!(table.get(&keys[7]), None);
+    assert_eq!(table.len(), 99);
+    for (i, key) in keys.iter().enumerate().filter(|(i, _)| *i != 7) {
+        assert_eq!(table.get(key), Some(&i));
+    }
+}
+
+#[test]
+fn removed_keys_leave_tombstones_that_keep_collisions_reachable() {
+    let mut table = Table::new();
+    table.extend((0..4).map(|i| (Colliding(i), i)));
+    table.remove(&Colliding(1));
+    // 2 and 3 were probed past 1's bucket.
+    assert_eq!(table.get(&Colliding(2)), Some(&2));
+    assert_eq!(table.get(&Colliding(3)), Some(&3));
+    // an insertion reuses the tombstone rather than the next empty bucket.
+    let capacity = table.capacity();
+    table.insert(Colliding(1), 10);
+    assert_eq!(table.get(&Colliding(1)), Some(&10));
+    assert_eq!(table.capacity(), capacity);
+}
+
+#[test]
+fn tables_grow_with_their_entries_but_not_their_tombstones() {
+    let keys = strings(1000);
+    let mut table = Table::new();
+    for key in &keys {
+        table.insert(*key, ());
+    }
+    assert!(table.capacity() * 3 >= table.len() * 4);
+    assert!(table.capacity() <= 2048);
+
+    let mut churned = Table::new();
+    for key in &keys {
+        churned.insert(*key, ());
+        churned.remove(key);
+    }
+    assert!(churned.is_empty());
+    assert!(churned.capacity() <= 8, "{}", churned.capacity());
+}
+
+#[test]
+fn retain_keeps_the_accepted_entries() {
+    let mut table = Table::new();
+    table.extend((0..40).map(|i| (Colliding(i), i)));
+    table.retain(|key, _| key.0 % 3 == 0);
+    let mut kept: Vec<u32> = table.values().copied().collect();
+    kept.sort();
+    assert_eq!(kept, (0..40).filter(|i| i % 3 == 0).collect::<Vec<_>>());
+    for i in 0..40 {
+        assert_eq!(table.contains_key(&Colliding(i)), i % 3 == 0);
+    }
+}
+
+#[test]
+fn keys_stay_in_their_buckets_until_the_table_is_resized() {
+    let mut first = Table::new();
+    first.extend((0..3).map(|i| (Colliding(i), i)));
+    let index = first.index_of(&Colliding(2)).unwrap();
+    assert_eq!(first.get_at(index, &Colliding(2)), Some(&2));
+    assert_eq!(first.get_at(index, &Colliding(1)), None);
+    *first.get_at_mut(index, &Colliding(2)).unwrap() = 20;
+    assert_eq!(first.get(&Colliding(2)), Some(&20));
+    assert_eq!(first.index_of(&Colliding(9)), None);
+
+    // the same keys in the same order are in the same buckets; in another order, colliding
+    // keys aren't.
+    let mut same = Table::new();
+    same.extend((0..3).map(|i| (Colliding(i), i)));
+    assert_eq!(same.index_of(&Colliding(2)), Some(index));
+    let mut reversed = Table::new();
+    reversed.extend((0..3).rev().map(|i| (Colliding(i), i)));
+    assert_eq!(reversed.get_at(index, &Colliding(2)), None);
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(u32, u32),
+    Remove(u32),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (0..64u32, any::<u32>()).prop_map(|(key, value)| Op::Insert(key, value)),
+        (0..64u32).prop_map(Op::Remove),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn tables_behave_like_hash_maps(ops in prop::collection::vec(op(), 0..300)) {
+        let mut table = Table::new();
+        let mut map = HashMap::new();
+        for op in ops {
+            match op {
+                Op::Insert(key, value) => prop_assert_eq!(
+                    table.insert(Colliding(key), value),
+                    map.insert(Colliding(key), value)
+                ),
+                Op::Remove(key) => {
+                    prop_assert_eq!(table.remove(&Colliding(key)), map.remove(&Colliding(key)))
+                }
+            }
+            prop_assert_eq!(table.len(), map.len());
+        }
+        for key in 0..64 {
+            prop_assert_eq!(table.get(&Colliding(key)), map.get(&Colliding(key)));
+        }
+    }
+}
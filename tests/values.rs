@@ -0,0 +1,55 @@
+// these hold for both representations of the vm's values; run with and without
+// `--features nan-boxing`.
+use rlox::vm::object::Heap;
+use rlox::vm::value::Value;
+
+#[test]
+fn values_round_trip() {
+    let mut heap = Heap::new();
+    let string = heap.intern("string");
+    assert!(Value::nil().is_nil());
+    assert_eq!(Value::bool(true).as_bool(), Some(true));
+    assert_eq!(Value::bool(false).as_bool(), Some(false));
+    assert_eq!(Value::obj(string).as_obj(), Some(string));
+    for number in [
+        0.0,
+        -0.0,
+        1.5,
+        -7.0,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::MAX,
+    ] {
+        let value = Value::number(number);
+        assert_eq!(value.as_number().map(f64::to_bits), Some(number.to_bits()));
+        assert_eq!((value.as_bool(), value.as_obj()), (None, None));
+        assert!(!value.is_nil());
+    }
+    assert!(Value::number(f64::NAN).as_number().unwrap().is_nan());
+    assert!(Value::number(-f64::NAN).as_number().unwrap().is_nan());
+    assert_eq!(Value::nil().as_number(), None);
+    assert_eq!(Value::bool(false).as_number(), None);
+    assert_eq!(Value::obj(string).as_number(), None);
+}
+
+#[test]
+fn numbers_compare_as_floats() {
+    assert_ne!(Value::number(f64::NAN), Value::number(f64::NAN));
+    assert_eq!(Value::number(0.0), Value::number(-0.0));
+    assert_ne!(Value::number(0.0), Value::bool(false));
+    assert_ne!(Value::number(0.0), Value::nil());
+    assert_ne!(Value::nil(), Value::bool(false));
+    assert_eq!(Value::bool(true), Value::bool(true));
+}
+
+#[test]
+fn only_nil_and_false_are_falsey() {
+    let mut heap = Heap::new();
+    let empty = heap.intern("");
+    assert!(!Value::nil().is_truthy());
+    assert!(!Value::bool(false).is_truthy());
+    assert!(Value::bool(true).is_truthy());
+    assert!(Value::number(0.0).is_truthy());
+    assert!(Value::number(f64::NAN).is_truthy());
+    assert!(Value::obj(empty).is_truthy());
+}